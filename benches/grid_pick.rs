@@ -0,0 +1,68 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use image::{Rgba, RgbaImage};
+use logo_process::{
+    generate_canvas_grids_from_logo_image, generate_canvas_grids_with_integrals, Color, EliminateBgColorParam,
+    GridFillOptions, GridPickCmd, LogoIntegrals,
+};
+
+
+/// 白底上的红色圆环，模拟单色logo
+fn synthetic_logo(size: u32) -> RgbaImage {
+    let center = size as f32 / 2.0;
+    RgbaImage::from_fn(size, size, |x, y| {
+        let d = ((x as f32 - center).powi(2) + (y as f32 - center).powi(2)).sqrt();
+        if d > size as f32 * 0.25 && d < size as f32 * 0.4 {
+            Rgba([200, 30, 30, 255])
+        } else {
+            Rgba([255, 255, 255, 255])
+        }
+    })
+}
+
+
+fn bench_eliminate_bg_color(c: &mut Criterion) {
+    let pick_strategy = GridPickCmd::EliminateBgColor(EliminateBgColorParam {
+        color: Color::WHITE,
+        min_remaining_ratio: 0.5,
+    });
+
+    let mut group = c.benchmark_group("eliminate_bg_color");
+    group.sample_size(10);
+    for size in [1000, 4000] {
+        let logo = synthetic_logo(size);
+        for fill_options in [GridFillOptions::triangle(5, 4), GridFillOptions::rectangle(5, 5)] {
+            let id = BenchmarkId::new(format!("{:?}", fill_options), size);
+            group.bench_with_input(id, &logo, |b, logo| {
+                b.iter(|| generate_canvas_grids_from_logo_image(logo, fill_options, pick_strategy.clone()).unwrap())
+            });
+        }
+    }
+    group.finish();
+}
+
+
+/// 同一张logo反复调整阈值（如界面上拖动滑块），复用积分图
+fn bench_repick_with_integrals(c: &mut Criterion) {
+    let logo = synthetic_logo(2000);
+    let integrals = LogoIntegrals::new();
+    let mut group = c.benchmark_group("repick_with_integrals");
+    group.sample_size(10);
+    for fill_options in [GridFillOptions::triangle(10, 8), GridFillOptions::rectangle(10, 10)] {
+        group.bench_function(format!("{:?}", fill_options), |b| {
+            let mut threshold = 0.1;
+            b.iter(|| {
+                threshold = if threshold > 0.9 { 0.1 } else { threshold + 0.1 };
+                let pick_strategy = GridPickCmd::EliminateBgColor(EliminateBgColorParam {
+                    color: Color::WHITE,
+                    min_remaining_ratio: threshold,
+                });
+                generate_canvas_grids_with_integrals(&logo, &integrals, fill_options, pick_strategy).unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+
+criterion_group!(benches, bench_eliminate_bg_color, bench_repick_with_integrals);
+criterion_main!(benches);
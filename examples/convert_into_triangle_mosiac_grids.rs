@@ -1,14 +1,14 @@
 use image::{ImageBuffer, Rgba};
-use logo_process::{draw_canvas_with_grids, generate_canvas_grids_from_logo_image, AvgColorCompareParam, Color, GridFillOptions, GridPickCmd};
+use logo_process::{draw_canvas_with_grids, generate_canvas_grids_from_logo_image, AvgColorCompareParam, Color, GridFillOptions, GridPickCmd, RenderOptions};
 use anyhow::Result;
 
 fn main() -> Result<()>{
 
     let image_path = "images/logo1.png";
-    let fill_options = GridFillOptions::Triangle(10, 8);
-    // let fill_options = GridFillOptions::Rectangle(10, 10);
+    let fill_options = GridFillOptions::triangle(10, 8);
+    // let fill_options = GridFillOptions::rectangle(10, 10);
     let pick_strategy = GridPickCmd::AvgColorCompare(AvgColorCompareParam {
-        color: Into::<Color>::into("#ffffffff"),
+        color: "#ffffffff".parse::<Color>()?,
         min_distance: 50.0,
         max_distance: 100.0
     });
@@ -16,27 +16,30 @@ fn main() -> Result<()>{
     let img: ImageBuffer<Rgba<u8>, Vec<u8>> = image::open(image_path).unwrap().to_rgba8();
     let canvas_width = img.width();
     let canvas_height = img.height();
-    let canvas_color = Into::<Color>::into("#373737ff").into();
+    let canvas_color = "#373737ff".parse::<Color>()?;
     let mut grids = generate_canvas_grids_from_logo_image(
         &img, fill_options, pick_strategy)?;
 
+    let border_color: Color = "#ffffffff".parse()?;
+    let unselected_color: Color = "#9099A2ff".parse()?;
+    let selected_color: Color = "#ff0000ff".parse()?;
     grids.iter_mut().for_each(|grid| {
         let avg_color = grid.ext.avg_color.unwrap();
         let color_distance = grid.ext.color_distance.unwrap();
         println!(">>>>>>grid, seq:{:?}, selected: {}, avg_color:{:?}, color_distance: {}", 
             grid.seq, grid.ext.selected.unwrap(), avg_color.to_rgb(), color_distance);
 
-        grid.ext.border_color = Some("#ffffffff".into());
-        grid.ext.fill_color = Some("#9099A2ff".into());
+        grid.ext.border_color = Some(border_color);
+        grid.ext.fill_color = Some(unselected_color);
         if grid.ext.selected.unwrap() {
-            grid.ext.fill_color = Some("#ff0000ff".into());
+            grid.ext.fill_color = Some(selected_color);
         }
     });
 
     // 填充格子
     draw_canvas_with_grids(
-        canvas_width, canvas_height, canvas_color,
-        grids,
+        &grids,
+        &RenderOptions::new(canvas_width, canvas_height, canvas_color),
         "output/logo_mosaic_grids.png".into())?;
     println!(">>>>>>image saved as output/logo_mosaic_grids.png");
     Ok(())
@@ -1,22 +1,22 @@
 use anyhow::Result;
-use logo_process::{draw_canvas_with_grids, generate_enmty_canvas_grids, Color, GridFillOptions};
+use logo_process::{draw_canvas_with_grids, generate_enmty_canvas_grids, Color, GridFillOptions, RenderOptions};
 
 
 fn main() -> Result<()> {
     // 设置画布的尺寸,颜色
     let canvas_width = 1000;
     let canvas_height = 800;
-    let canvas_color = Into::<Color>::into("#373737ff").into();
+    let canvas_color = "#373737ff".parse::<Color>()?;
     // 设置填充画布的矩形的尺寸和颜色
-    let grid_fill_color = "#9099A2ff".into();
-    let grid_border_color = "#ffffffff".into();
+    let grid_fill_color = "#9099A2ff".parse()?;
+    let grid_border_color = "#ffffffff".parse()?;
     let rectangle_width = 50;
     let rectangle_height = 50;
 
     //生成格子
     let mut grids = generate_enmty_canvas_grids( 
         canvas_width, canvas_height,
-        GridFillOptions::Rectangle(rectangle_width, rectangle_height))?;
+        GridFillOptions::rectangle(rectangle_width, rectangle_height))?;
     // 设置格子颜色
     grids.iter_mut().for_each(|grid| {
         grid.ext.border_color = Some(grid_border_color);
@@ -24,10 +24,8 @@ fn main() -> Result<()> {
     });
     //在画布上画格子
     draw_canvas_with_grids(
-        canvas_width, 
-        canvas_height, 
-        canvas_color,
-        grids, 
+        &grids,
+        &RenderOptions::new(canvas_width, canvas_height, canvas_color),
         "output/restangle_grids.png".into())?;
     Ok(())
 }
@@ -6,7 +6,7 @@ use logo_process::Color;
 fn main() -> Result<()>  {
     // 创建一个800x600的灰色背景图像
 
-    let fill_color = Into::<Color>::into("#aaff55ff");
+    let fill_color = "#aaff55ff".parse::<Color>()?;
     let mut img = ImageBuffer::from_pixel(800, 600, Rgba([128u8, 128u8, 128u8, 255u8]));
     println!(">>>>>>image width: {:?}, height: {:?}", img.width(), img.height());
 
@@ -0,0 +1,611 @@
+use std::{fs, io::Write, path::{Path, PathBuf}};
+use anyhow::{Context, Result};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+use logo_process::{
+    batch_summary_csv, check_canvas_grids, encode_image, generate_canvas_grids_with_integrals, run_batch, BatchImage, BatchOptions,
+    BatchParamMatrix, GridShape, load_logo_image, paint_grids, render_canvas_to_svg,
+    render_canvas_with_grids, resolve_pick_palettes, search_grids_for_target_count, AvgColorCompareParam, Color,
+    EdgeAwareParam, EliminateBgColorParam, Grid, GridColorMode, GridColorOptions, GridFillOptions, GridAlign, GridLayout, Margins, GridPickCmd, Point,
+    LogoIntegrals, PaletteClassifyParam, PickProgress, RenderFormat, RenderOptions, TargetSearchMode, MAX_GRID_COUNT,
+};
+
+
+/// 离线生成logo马赛克，无需启动服务
+#[derive(Debug, Parser)]
+#[command(name = "logo-mosaic", version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// 生成格子并按策略选取，输出格子json
+    Grids(GridsArgs),
+    /// 生成格子、选取并着色，按输出文件扩展名输出png/jpeg/webp/svg/json
+    Convert(ConvertArgs),
+    /// 将grids/convert输出的json渲染为png/jpeg/webp/svg
+    Render(RenderArgs),
+    /// 输出每个格子的统计信息(csv)
+    Inspect(InspectArgs),
+    /// 多个logo × 参数矩阵批量转换，输出联系表和汇总(csv/json)
+    Batch(BatchArgs),
+}
+
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum ShapeArg {
+    Triangle,
+    Rectangle,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum AlignArg {
+    /// 从左上角开始铺排
+    Start,
+    /// 居中
+    Center,
+    /// 拉伸格子恰好铺满
+    Stretch,
+    /// 铺满并裁剪边缘的格子
+    Clip,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum PickArg {
+    /// 剔除背景色后按剩余像素占比选取
+    EliminateBg,
+    /// 区域平均色值与目标颜色比较
+    AvgColor,
+    /// 按调色板分类
+    Palette,
+    /// 剔除背景色并保留细笔画穿过的格子
+    EdgeAware,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum ColorModeArg {
+    Fixed,
+    AvgColor,
+    Palette,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum OutputFormat {
+    Png,
+    Jpeg,
+    Webp,
+    Svg,
+    Json,
+}
+
+
+/// logo及格子生成、选取参数
+#[derive(Debug, Args)]
+struct MosaicArgs {
+    /// logo图片路径
+    input: PathBuf,
+    /// 格子形状
+    #[arg(long, value_enum, default_value_t = ShapeArg::Triangle)]
+    shape: ShapeArg,
+    /// 格子尺寸，格式为宽x高
+    #[arg(long, default_value = "10x8", value_parser = parse_size)]
+    size: (u32, u32),
+    /// 画布四周的留白(像素)，格式为 全部 / 上下,左右 / 上,右,下,左
+    #[arg(long, default_value = "0", value_parser = parse_margin)]
+    margin: Margins,
+    /// 格子的对齐方式
+    #[arg(long, value_enum, default_value_t = AlignArg::Start)]
+    align: AlignArg,
+    /// 网格的旋转角度(度)，如矩形旋转45度为菱形
+    #[arg(long, default_value_t = 0.0, allow_negative_numbers = true)]
+    rotation: f32,
+    /// 网格原点，格式为x,y
+    #[arg(long, value_parser = parse_point)]
+    origin: Option<Point>,
+    #[command(flatten)]
+    pick: PickArgs,
+    /// 目标选中格子数，指定时搜索最接近的格子尺寸和选取阈值
+    #[arg(long)]
+    target_count: Option<usize>,
+}
+
+/// 选取策略参数
+#[derive(Debug, Args)]
+struct PickArgs {
+    /// 选取策略
+    #[arg(long, value_enum, default_value_t = PickArg::EliminateBg)]
+    pick: PickArg,
+    /// 选取命令json(GridPickCmd)，指定时忽略--pick及其参数，可用于组合策略
+    #[arg(long, value_parser = parse_pick_cmd)]
+    pick_cmd: Option<GridPickCmd>,
+    /// 背景色
+    #[arg(long, default_value = "#ffffff")]
+    bg_color: Color,
+    /// 前景像素的最小占比
+    #[arg(long, default_value_t = 0.5)]
+    min_ratio: f32,
+    /// avg-color策略的目标颜色
+    #[arg(long, default_value = "#ffffff")]
+    target_color: Color,
+    /// avg-color策略的最小色差
+    #[arg(long, default_value_t = 50.0)]
+    min_distance: f32,
+    /// avg-color策略的最大色差
+    #[arg(long, default_value_t = 100.0)]
+    max_distance: f32,
+    /// 调色板颜色，多个用逗号分隔；palette策略未指定时从logo中提取
+    #[arg(long, value_delimiter = ',')]
+    palette: Vec<Color>,
+    /// 自动提取调色板时的颜色数量
+    #[arg(long, default_value_t = 4)]
+    palette_size: usize,
+    /// edge-aware策略中细笔画的最小宽度(像素)
+    #[arg(long, default_value_t = 2.0)]
+    min_stroke_width: f32,
+    /// edge-aware策略中细笔画格子的边缘像素最小占比
+    #[arg(long, default_value_t = 0.05)]
+    min_edge_ratio: f32,
+}
+
+/// 着色及画布参数
+#[derive(Debug, Args)]
+struct PaintArgs {
+    /// 选中格子的着色方式
+    #[arg(long, value_enum, default_value_t = ColorModeArg::Fixed)]
+    color_mode: ColorModeArg,
+    /// 选中格子的颜色
+    #[arg(long, default_value = "#ff0000")]
+    selected_color: Color,
+    /// 未选中格子的颜色，不指定时不绘制
+    #[arg(long)]
+    unselected_color: Option<Color>,
+    /// 格子边框颜色，不指定时不绘制
+    #[arg(long)]
+    border_color: Option<Color>,
+    /// 画布底色
+    #[arg(long, default_value = "#ffffff")]
+    canvas_color: Color,
+}
+
+#[derive(Debug, Args)]
+struct GridsArgs {
+    #[command(flatten)]
+    mosaic: MosaicArgs,
+    /// 输出json路径，不指定时输出到标准输出
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+struct ConvertArgs {
+    #[command(flatten)]
+    mosaic: MosaicArgs,
+    #[command(flatten)]
+    paint: PaintArgs,
+    /// 输出路径
+    #[arg(short, long)]
+    output: PathBuf,
+    /// 输出格式，不指定时按输出文件扩展名确定
+    #[arg(long, value_enum)]
+    format: Option<OutputFormat>,
+}
+
+#[derive(Debug, Args)]
+struct RenderArgs {
+    /// grids/convert输出的json
+    input: PathBuf,
+    /// 输出路径
+    #[arg(short, long)]
+    output: PathBuf,
+    /// 输出格式，不指定时按输出文件扩展名确定
+    #[arg(long, value_enum)]
+    format: Option<OutputFormat>,
+}
+
+#[derive(Debug, Args)]
+struct InspectArgs {
+    #[command(flatten)]
+    mosaic: MosaicArgs,
+    /// 只输出选中的格子
+    #[arg(long)]
+    selected_only: bool,
+    /// 输出csv路径，不指定时输出到标准输出
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+
+#[derive(Debug, Args)]
+struct BatchArgs {
+    /// logo图片路径
+    #[arg(required = true)]
+    inputs: Vec<PathBuf>,
+    /// 格子形状，多个用逗号分隔
+    #[arg(long, value_enum, value_delimiter = ',', default_value = "triangle")]
+    shapes: Vec<ShapeArg>,
+    /// 格子尺寸，格式为宽x高，多个用逗号分隔
+    #[arg(long, value_delimiter = ',', default_value = "10x8", value_parser = parse_size)]
+    sizes: Vec<(u32, u32)>,
+    /// 选取阈值，多个用逗号分隔；不指定时使用选取策略自身的阈值
+    #[arg(long, value_delimiter = ',')]
+    thresholds: Vec<f32>,
+    #[command(flatten)]
+    pick: PickArgs,
+    /// 联系表中缩略图的宽度
+    #[arg(long, default_value_t = 160)]
+    cell_width: u32,
+    /// 联系表输出路径(png/jpeg/webp)
+    #[arg(long)]
+    sheet: Option<PathBuf>,
+    /// 汇总输出路径，按扩展名输出csv或json；不指定时以csv输出到标准输出
+    #[arg(long)]
+    summary: Option<PathBuf>,
+}
+
+
+/// json输出：画布信息及格子
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MosaicDocument {
+    canvas_width: u32,
+    canvas_height: u32,
+    #[serde(default)]
+    canvas_color: Option<Color>,
+    #[serde(default)]
+    palette: Option<Vec<Color>>,
+    grids: Vec<Grid>,
+}
+
+
+fn main() -> Result<()> {
+    match Cli::parse().command {
+        Command::Grids(args) => {
+            let (img, mut pick_cmd) = load(&args.mosaic)?;
+            let palette = resolve_pick_palettes(&img, &mut pick_cmd);
+            let grids = generate(&img, &args.mosaic, pick_cmd)?;
+            let doc = MosaicDocument { canvas_width: img.width(), canvas_height: img.height(), canvas_color: None, palette, grids };
+            write_output(args.output.as_deref(), &serde_json::to_vec_pretty(&doc)?)
+        },
+        Command::Convert(args) => {
+            let (img, mut pick_cmd) = load(&args.mosaic)?;
+            let mut color_options = build_color_options(&args.mosaic, &args.paint);
+            if let Some(palette) = resolve_pick_palettes(&img, &mut pick_cmd) {
+                color_options.palette = Some(palette);
+            }
+            let mut grids = generate(&img, &args.mosaic, pick_cmd)?;
+            paint_grids(&img, &mut grids, &color_options)?;
+            let doc = MosaicDocument {
+                canvas_width: img.width(),
+                canvas_height: img.height(),
+                canvas_color: Some(args.paint.canvas_color),
+                palette: color_options.palette,
+                grids,
+            };
+            write_document(&doc, &args.output, args.format)
+        },
+        Command::Render(args) => {
+            let content = fs::read(&args.input)
+                .with_context(|| format!("failed to read {}", args.input.display()))?;
+            let doc: MosaicDocument = serde_json::from_slice(&content)
+                .with_context(|| format!("failed to parse {}", args.input.display()))?;
+            write_document(&doc, &args.output, args.format)
+        },
+        Command::Inspect(args) => {
+            let (img, mut pick_cmd) = load(&args.mosaic)?;
+            resolve_pick_palettes(&img, &mut pick_cmd);
+            let grids = generate(&img, &args.mosaic, pick_cmd)?;
+            let csv = inspect_grids(&img, &grids, args.mosaic.pick.bg_color, args.selected_only)?;
+            let selected = grids.iter().filter(|g| g.ext.selected.unwrap_or(false)).count();
+            eprintln!("grids: {}, selected: {}", grids.len(), selected);
+            write_output(args.output.as_deref(), csv.as_bytes())
+        },
+        Command::Batch(args) => batch(args),
+    }
+}
+
+
+fn batch(args: BatchArgs) -> Result<()> {
+    let logos = args.inputs.iter()
+        .map(|path| load_logo_image(&path.to_string_lossy()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let images: Vec<BatchImage> = args.inputs.iter().zip(&logos)
+        .map(|(path, image)| BatchImage { name: path.to_string_lossy().into_owned(), image })
+        .collect();
+    let matrix = BatchParamMatrix {
+        grid_shapes: args.shapes.iter().map(|shape| match shape {
+            ShapeArg::Triangle => GridShape::Triangle,
+            ShapeArg::Rectangle => GridShape::Rectangle,
+        }).collect(),
+        grid_sizes: args.sizes.clone(),
+        pick_strategy: build_pick_cmd(&args.pick),
+        thresholds: args.thresholds.clone(),
+    };
+    for (fill_options, _) in matrix.combinations() {
+        for image in &images {
+            check_canvas_grids(image.image.width(), image.image.height(), &fill_options)
+                .with_context(|| format!("invalid grids for {}", image.name))?;
+        }
+    }
+    let options = BatchOptions { cell_width: args.cell_width.max(1), ..Default::default() };
+    let report = run_batch(&images, &matrix, &options)?;
+
+    if let Some(sheet) = &args.sheet {
+        let format = match format_from_extension(sheet)? {
+            OutputFormat::Jpeg => RenderFormat::Jpeg,
+            OutputFormat::Webp => RenderFormat::Webp,
+            OutputFormat::Png => RenderFormat::Png,
+            _ => anyhow::bail!("contact sheet must be png, jpeg or webp"),
+        };
+        write_output(Some(sheet), &encode_image(&report.contact_sheet, format)?)?;
+    }
+    let summary = match args.summary.as_deref().map(format_from_extension).transpose()? {
+        Some(OutputFormat::Json) => serde_json::to_vec_pretty(&report.rows)?,
+        _ => batch_summary_csv(&report.rows).into_bytes(),
+    };
+    write_output(args.summary.as_deref(), &summary)
+}
+
+
+fn load(args: &MosaicArgs) -> Result<(RgbaImage, GridPickCmd)> {
+    let img = load_logo_image(&args.input.to_string_lossy())?;
+    Ok((img, build_pick_cmd(&args.pick)))
+}
+
+fn build_pick_cmd(args: &PickArgs) -> GridPickCmd {
+    if let Some(cmd) = &args.pick_cmd {
+        return cmd.clone();
+    }
+    match args.pick {
+        PickArg::EliminateBg => GridPickCmd::EliminateBgColor(EliminateBgColorParam {
+            color: args.bg_color,
+            min_remaining_ratio: args.min_ratio,
+        }),
+        PickArg::AvgColor => GridPickCmd::AvgColorCompare(AvgColorCompareParam {
+            color: args.target_color,
+            min_distance: args.min_distance,
+            max_distance: args.max_distance,
+        }),
+        PickArg::Palette => GridPickCmd::PaletteClassify(PaletteClassifyParam {
+            palette: args.palette.clone(),
+            palette_size: args.palette_size,
+            bg_color: args.bg_color,
+            min_remaining_ratio: args.min_ratio,
+        }),
+        PickArg::EdgeAware => GridPickCmd::EdgeAware(EdgeAwareParam {
+            bg_color: args.bg_color,
+            min_remaining_ratio: args.min_ratio,
+            min_stroke_width: args.min_stroke_width,
+            min_edge_ratio: args.min_edge_ratio,
+        }),
+    }
+}
+
+fn build_color_options(mosaic: &MosaicArgs, paint: &PaintArgs) -> GridColorOptions {
+    let mode = match paint.color_mode {
+        ColorModeArg::Fixed => GridColorMode::Fixed,
+        ColorModeArg::AvgColor => GridColorMode::AvgColor,
+        ColorModeArg::Palette => GridColorMode::Palette,
+    };
+    let mut options = GridColorOptions::new(mode, paint.selected_color);
+    options.unselected_color = paint.unselected_color;
+    options.border_color = paint.border_color;
+    options.palette = Some(mosaic.pick.palette.clone()).filter(|p| !p.is_empty());
+    options
+}
+
+/// 生成格子并选取；指定了目标格子数时搜索最接近的参数
+fn generate(img: &RgbaImage, args: &MosaicArgs, pick_cmd: GridPickCmd) -> Result<Vec<Grid>> {
+    let (width, height) = args.size;
+    let fill_options = match args.shape {
+        ShapeArg::Triangle => GridFillOptions::triangle(width, height),
+        ShapeArg::Rectangle => GridFillOptions::rectangle(width, height),
+    };
+    let align = match args.align {
+        AlignArg::Start => GridAlign::Start,
+        AlignArg::Center => GridAlign::Center,
+        AlignArg::Stretch => GridAlign::Stretch,
+        AlignArg::Clip => GridAlign::Clip,
+    };
+    let fill_options = fill_options.with_layout(GridLayout { margin: args.margin, align, rotation: args.rotation, origin: args.origin });
+    // 与接口相同的限制，留白过大或格子过多时直接报错
+    check_canvas_grids(img.width(), img.height(), &fill_options)?;
+    let integrals = LogoIntegrals::new();
+    match args.target_count {
+        Some(target_count) => {
+            let result = search_grids_for_target_count(
+                img, &integrals, &PickProgress::new(), fill_options, pick_cmd, target_count, MAX_GRID_COUNT, TargetSearchMode::default())?;
+            eprintln!("target count search, fill_options: {:?}, pick_threshold: {}, selected: {}",
+                result.fill_options, result.pick_strategy.pick_threshold(), result.selected_count);
+            Ok(result.grids)
+        },
+        None => Ok(generate_canvas_grids_with_integrals(img, &integrals, fill_options, pick_cmd)?),
+    }
+}
+
+
+/// 每个格子一行：序号、形状、是否选中、平均色值、前景占比及各策略的计算结果
+fn inspect_grids(img: &RgbaImage, grids: &[Grid], bg_color: Color, selected_only: bool) -> Result<String> {
+    let integrals = LogoIntegrals::new();
+    let (color_integral, foreground_integral) = (integrals.color(img), integrals.foreground(img, bg_color));
+    let mut csv = String::from("seq,shape,centroid_x,centroid_y,area,selected,avg_color,remaining_area_ratio,color_distance,palette_index,edge_ratio,stroke_width\n");
+    let opt = |v: Option<String>| v.unwrap_or_default();
+    for grid in grids {
+        let selected = grid.ext.selected.unwrap_or(false);
+        if selected_only && !selected {
+            continue;
+        }
+        let avg_color = match grid.ext.avg_color {
+            Some(color) => color,
+            None => color_integral.avg_color_of_grid(grid)?,
+        };
+        let remaining_area_ratio = match grid.ext.remaining_area_ratio {
+            Some(ratio) => ratio,
+            None => foreground_integral.remaining_area_ratio_of_grid(grid)?,
+        };
+        let centroid = grid.centroid();
+        csv.push_str(&format!("{},{},{:.2},{:.2},{:.1},{},{},{:.4},{},{},{},{}\n",
+            grid.seq,
+            String::from(grid.shape),
+            centroid.x,
+            centroid.y,
+            grid.area(),
+            selected,
+            avg_color.to_rgba_string(),
+            remaining_area_ratio,
+            opt(grid.ext.color_distance.map(|v| format!("{:.2}", v))),
+            opt(grid.ext.palette_index.map(|v| v.to_string())),
+            opt(grid.ext.edge_ratio.map(|v| format!("{:.4}", v))),
+            opt(grid.ext.stroke_width.map(|v| format!("{:.2}", v))),
+        ));
+    }
+    Ok(csv)
+}
+
+
+fn write_document(doc: &MosaicDocument, output: &Path, format: Option<OutputFormat>) -> Result<()> {
+    let format = match format {
+        Some(format) => format,
+        None => format_from_extension(output)?,
+    };
+    let options = RenderOptions::new(doc.canvas_width, doc.canvas_height, doc.canvas_color.unwrap_or(Color::WHITE));
+    let bytes = match format {
+        OutputFormat::Json => serde_json::to_vec_pretty(doc)?,
+        OutputFormat::Svg => render_canvas_to_svg(&doc.grids, &options).into_bytes(),
+        OutputFormat::Png | OutputFormat::Jpeg | OutputFormat::Webp => {
+            let render_format = match format {
+                OutputFormat::Jpeg => RenderFormat::Jpeg,
+                OutputFormat::Webp => RenderFormat::Webp,
+                _ => RenderFormat::Png,
+            };
+            encode_image(&render_canvas_with_grids(&doc.grids, &options)?, render_format)?
+        },
+    };
+    write_output(Some(output), &bytes)
+}
+
+fn format_from_extension(path: &Path) -> Result<OutputFormat> {
+    let extension = path.extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("png") => Ok(OutputFormat::Png),
+        Some("jpg") | Some("jpeg") => Ok(OutputFormat::Jpeg),
+        Some("webp") => Ok(OutputFormat::Webp),
+        Some("svg") => Ok(OutputFormat::Svg),
+        Some("json") => Ok(OutputFormat::Json),
+        _ => anyhow::bail!("cannot infer output format from {}, please specify --format", path.display()),
+    }
+}
+
+fn write_output(output: Option<&Path>, bytes: &[u8]) -> Result<()> {
+    match output {
+        Some(path) => fs::write(path, bytes).with_context(|| format!("failed to write {}", path.display())),
+        None => Ok(std::io::stdout().write_all(bytes)?),
+    }
+}
+
+
+fn parse_size(s: &str) -> Result<(u32, u32), String> {
+    let (width, height) = s.split_once(['x', 'X'])
+        .ok_or_else(|| format!("invalid size {}, expected WIDTHxHEIGHT", s))?;
+    let parse = |v: &str| v.trim().parse::<u32>().ok().filter(|v| *v > 0)
+        .ok_or_else(|| format!("invalid size {}, expected positive integers", s));
+    Ok((parse(width)?, parse(height)?))
+}
+
+fn parse_margin(s: &str) -> Result<Margins, String> {
+    let values = s.split(',')
+        .map(|v| v.trim().parse::<u32>().map_err(|_| format!("invalid margin {}, expected non-negative integers", s)))
+        .collect::<Result<Vec<_>, _>>()?;
+    match *values.as_slice() {
+        [all] => Ok(Margins::uniform(all)),
+        [vertical, horizontal] => Ok(Margins { top: vertical, right: horizontal, bottom: vertical, left: horizontal }),
+        [top, right, bottom, left] => Ok(Margins { top, right, bottom, left }),
+        _ => Err(format!("invalid margin {}, expected 1, 2 or 4 values", s)),
+    }
+}
+
+fn parse_point(s: &str) -> Result<Point, String> {
+    let (x, y) = s.split_once(',').ok_or_else(|| format!("invalid point {}, expected x,y", s))?;
+    let parse = |v: &str| v.trim().parse::<f32>().ok().filter(|v| v.is_finite())
+        .ok_or_else(|| format!("invalid point {}, expected numbers", s));
+    Ok(Point::new(parse(x)?, parse(y)?))
+}
+
+fn parse_pick_cmd(s: &str) -> Result<GridPickCmd, String> {
+    serde_json::from_str(s).map_err(|e| e.to_string())
+}
+
+
+#[cfg(test)]
+#[test]
+fn test_parse_args() {
+    assert_eq!(parse_size("10x8"), Ok((10, 8)));
+    assert_eq!(parse_size(" 12 X 6 "), Ok((12, 6)));
+    assert!(parse_size("10").is_err());
+    assert!(parse_size("0x8").is_err());
+    assert!(parse_size("10x-1").is_err());
+
+    assert_eq!(parse_margin("4"), Ok(Margins::uniform(4)));
+    assert_eq!(parse_margin("1,2"), Ok(Margins { top: 1, right: 2, bottom: 1, left: 2 }));
+    assert_eq!(parse_margin("1,2,3,4"), Ok(Margins { top: 1, right: 2, bottom: 3, left: 4 }));
+    assert!(parse_margin("1,2,3").is_err());
+    assert!(parse_margin("-1").is_err());
+
+    assert_eq!(parse_point("1.5, -2"), Ok(Point::new(1.5, -2.0)));
+    assert!(parse_point("1").is_err());
+    assert!(parse_point("1,inf").is_err());
+
+    assert!(matches!(format_from_extension(Path::new("out.PNG")), Ok(OutputFormat::Png)));
+    assert!(matches!(format_from_extension(Path::new("out.jpg")), Ok(OutputFormat::Jpeg)));
+    assert!(matches!(format_from_extension(Path::new("out.jpeg")), Ok(OutputFormat::Jpeg)));
+    assert!(matches!(format_from_extension(Path::new("out.webp")), Ok(OutputFormat::Webp)));
+    assert!(matches!(format_from_extension(Path::new("out.svg")), Ok(OutputFormat::Svg)));
+    assert!(matches!(format_from_extension(Path::new("out.json")), Ok(OutputFormat::Json)));
+    assert!(format_from_extension(Path::new("out")).is_err());
+    assert!(format_from_extension(Path::new("out.gif")).is_err());
+}
+
+
+#[cfg(test)]
+#[test]
+fn test_render_grids_json_round_trip() {
+    // grids输出的json经render重新输出后内容不变，并能渲染为图片
+    let img = RgbaImage::from_pixel(40, 30, image::Rgba([0, 0, 0, 255]));
+    let pick_cmd = GridPickCmd::EliminateBgColor(EliminateBgColorParam { color: Color::WHITE, min_remaining_ratio: 0.5 });
+    let grids = generate_canvas_grids_with_integrals(&img, &LogoIntegrals::new(), GridFillOptions::triangle(10, 8), pick_cmd).unwrap();
+    let doc = MosaicDocument { canvas_width: 40, canvas_height: 30, canvas_color: None, palette: None, grids };
+    let json = serde_json::to_vec_pretty(&doc).unwrap();
+
+    let dir = std::env::temp_dir().join(format!("logo-mosaic-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(&dir).unwrap();
+    let parsed: MosaicDocument = serde_json::from_slice(&json).unwrap();
+    write_document(&parsed, &dir.join("out.json"), None).unwrap();
+    write_document(&parsed, &dir.join("out.png"), None).unwrap();
+    let rendered_json = fs::read(dir.join("out.json")).unwrap();
+    let rendered_png = image::open(dir.join("out.png")).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(rendered_json, json);
+    assert_eq!((rendered_png.width(), rendered_png.height()), (40, 30));
+}
+
+
+#[cfg(test)]
+#[test]
+fn test_generate_rejects_oversized_margin() {
+    // 留白超过画布时报错，而不是生成格子
+    let cli = Cli::try_parse_from(["logo-mosaic", "grids", "logo.png", "--align", "stretch", "--margin", "0,5000"]).unwrap();
+    let Command::Grids(args) = cli.command else { panic!("expected grids command") };
+    let img = RgbaImage::from_pixel(100, 80, image::Rgba([0, 0, 0, 255]));
+    let err = generate(&img, &args.mosaic, build_pick_cmd(&args.mosaic.pick)).unwrap_err();
+    assert!(err.to_string().contains("gridLayout.margin"), "{err}");
+
+    // 格子过小，数量超过上限
+    let cli = Cli::try_parse_from(["logo-mosaic", "grids", "logo.png", "--size", "1x1"]).unwrap();
+    let Command::Grids(args) = cli.command else { panic!("expected grids command") };
+    let img = RgbaImage::from_pixel(400, 400, image::Rgba([0, 0, 0, 255]));
+    let err = generate(&img, &args.mosaic, build_pick_cmd(&args.mosaic.pick)).unwrap_err();
+    assert!(err.to_string().contains("exceed the limit"), "{err}");
+}
@@ -0,0 +1,309 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    fs,
+    hash::{Hash, Hasher},
+    sync::{atomic::{AtomicU64, Ordering}, Arc, Mutex},
+    time::SystemTime,
+};
+use anyhow::{Context, Result};
+use dashmap::DashMap;
+use image::RgbaImage;
+use lru::LruCache;
+use serde::{Deserialize, Serialize};
+use crate::LogoIntegrals;
+
+/// 默认最多缓存的logo数
+pub const DEFAULT_LOGO_CACHE_ENTRIES: usize = 16;
+/// 默认logo缓存的像素数据及积分图上限(字节)
+pub const DEFAULT_LOGO_CACHE_BYTES: usize = 512 * 1024 * 1024;
+/// 默认最多缓存的转换结果数
+pub const DEFAULT_CONVERT_CACHE_ENTRIES: usize = 64;
+/// 默认转换结果缓存的上限(字节，按结果中的格子估算)
+pub const DEFAULT_CONVERT_CACHE_BYTES: usize = 256 * 1024 * 1024;
+
+
+/// 缓存命中统计
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    // 当前缓存的条目数
+    pub entries: usize,
+    // 当前缓存的总权重（logo缓存为像素数据及已计算的积分图的字节数，结果缓存为估算的结果字节数）
+    pub weight: usize,
+}
+
+
+/// 同时限制条目数和总权重的LRU缓存，线程安全
+pub struct WeightedLruCache<K: Hash + Eq, V: Clone> {
+    inner: Mutex<WeightedLruInner<K, V>>,
+    max_entries: usize,
+    max_weight: usize,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+struct WeightedLruInner<K: Hash + Eq, V> {
+    lru: LruCache<K, (V, usize)>,
+    weight: usize,
+}
+
+impl<K: Hash + Eq, V: Clone> WeightedLruCache<K, V> {
+    pub fn new(max_entries: usize, max_weight: usize) -> Self {
+        Self {
+            inner: Mutex::new(WeightedLruInner { lru: LruCache::unbounded(), weight: 0 }),
+            max_entries,
+            max_weight,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// 查询缓存并更新命中统计
+    pub fn get(&self, key: &K) -> Option<V> {
+        let value = self.lock().lru.get(key).map(|(v, _)| v.clone());
+        let counter = if value.is_some() { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+        value
+    }
+
+    /// 写入缓存，超出条目数或总权重上限时淘汰最久未使用的条目；权重超过上限的条目不缓存
+    pub fn insert(&self, key: K, value: V, weight: usize) {
+        if self.max_entries == 0 || weight > self.max_weight {
+            return;
+        }
+        let mut inner = self.lock();
+        if let Some((_, (_, old_weight))) = inner.lru.push(key, (value, weight)) {
+            inner.weight -= old_weight;
+        }
+        inner.weight += weight;
+        self.evict(&mut inner);
+    }
+
+    /// 条目占用的内存变化后更新其权重，超出上限时淘汰最久未使用的条目
+    pub fn update_weight(&self, key: &K, weight: usize) {
+        let mut inner = self.lock();
+        let old_weight = match inner.lru.peek_mut(key) {
+            Some((_, w)) => std::mem::replace(w, weight),
+            None => return,
+        };
+        inner.weight = inner.weight - old_weight + weight;
+        self.evict(&mut inner);
+    }
+
+    fn evict(&self, inner: &mut WeightedLruInner<K, V>) {
+        while inner.lru.len() > self.max_entries || inner.weight > self.max_weight {
+            match inner.lru.pop_lru() {
+                Some((_, (_, w))) => inner.weight -= w,
+                None => break,
+            }
+        }
+    }
+
+    /// 移除满足条件的条目
+    pub fn remove_if<F: Fn(&K) -> bool>(&self, predicate: F) {
+        let mut inner = self.lock();
+        let stale = inner.lru.iter().filter(|(k, _)| predicate(k)).count();
+        if stale == 0 {
+            return;
+        }
+        // 从最久未使用的条目开始依次弹出，保留的条目按原顺序放回
+        let mut kept = Vec::with_capacity(inner.lru.len() - stale);
+        while let Some((k, v)) = inner.lru.pop_lru() {
+            if predicate(&k) {
+                inner.weight -= v.1;
+            } else {
+                kept.push((k, v));
+            }
+        }
+        for (k, v) in kept {
+            inner.lru.push(k, v);
+        }
+    }
+
+    pub fn clear(&self) {
+        let mut inner = self.lock();
+        inner.lru.clear();
+        inner.weight = 0;
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let inner = self.lock();
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: inner.lru.len(),
+            weight: inner.weight,
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, WeightedLruInner<K, V>> {
+        // 缓存中只有可丢弃的数据，锁中毒时继续使用
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+
+/// 解码后的logo及其积分图
+pub struct CachedLogo {
+    pub image: RgbaImage,
+    // 积分图在首次选取时计算，随logo一起缓存，同一张logo调整参数时直接复用
+    pub integrals: LogoIntegrals,
+    // 图片文件内容的哈希，文件变化后哈希随之变化
+    pub file_hash: u64,
+}
+
+impl CachedLogo {
+    /// 缓存权重：像素数据及已计算的积分图的字节数
+    pub fn weight(&self) -> usize {
+        self.image.as_raw().len() + self.integrals.memory_bytes()
+    }
+}
+
+
+/// 图片文件的指纹：修改时间和大小不变时，沿用上次计算的内容哈希
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FileFingerprint {
+    modified: Option<SystemTime>,
+    len: u64,
+    hash: u64,
+}
+
+
+/// 解码后的logo缓存，按图片id和文件内容哈希索引
+pub struct LogoCache {
+    logos: WeightedLruCache<(String, u64), Arc<CachedLogo>>,
+    fingerprints: DashMap<String, FileFingerprint>,
+}
+
+impl Default for LogoCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_LOGO_CACHE_ENTRIES, DEFAULT_LOGO_CACHE_BYTES)
+    }
+}
+
+impl LogoCache {
+    pub fn new(max_entries: usize, max_bytes: usize) -> Self {
+        Self {
+            logos: WeightedLruCache::new(max_entries, max_bytes),
+            fingerprints: DashMap::new(),
+        }
+    }
+
+    /// 加载logo：文件未变化时直接返回缓存，否则重新解码并淘汰该图片的旧缓存
+    pub fn load(&self, image_id: &str, image_path: &str) -> Result<Arc<CachedLogo>> {
+        let metadata = fs::metadata(image_path)
+            .with_context(|| format!("failed to open logo image: {}", image_path))?;
+        let modified = metadata.modified().ok();
+        let cached_fingerprint = self.fingerprints.get(image_id).map(|f| *f)
+            .filter(|f| modified.is_some() && f.modified == modified && f.len == metadata.len());
+
+        let (hash, bytes) = match cached_fingerprint {
+            Some(fingerprint) => (fingerprint.hash, None),
+            None => {
+                let bytes = fs::read(image_path)
+                    .with_context(|| format!("failed to open logo image: {}", image_path))?;
+                let hash = hash_bytes(&bytes);
+                self.fingerprints.insert(image_id.to_string(), FileFingerprint { modified, len: metadata.len(), hash });
+                (hash, Some(bytes))
+            },
+        };
+
+        let key = (image_id.to_string(), hash);
+        if let Some(logo) = self.logos.get(&key) {
+            return Ok(logo);
+        }
+
+        let bytes = match bytes {
+            Some(bytes) => bytes,
+            None => fs::read(image_path).with_context(|| format!("failed to open logo image: {}", image_path))?,
+        };
+        let image = image::load_from_memory(&bytes)
+            .with_context(|| format!("failed to decode logo image: {}", image_path))?
+            .to_rgba8();
+        let logo = Arc::new(CachedLogo { image, integrals: LogoIntegrals::new(), file_hash: hash });
+
+        // 图片内容已变化，旧版本的缓存不再有用
+        self.logos.remove_if(|(id, h)| id == image_id && *h != hash);
+        self.logos.insert(key, logo.clone(), logo.weight());
+        Ok(logo)
+    }
+
+    /// 选取过程中计算了积分图后，按logo当前占用的内存更新缓存权重
+    pub fn update_weight(&self, image_id: &str, logo: &CachedLogo) {
+        self.logos.update_weight(&(image_id.to_string(), logo.file_hash), logo.weight());
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.logos.stats()
+    }
+
+    pub fn clear(&self) {
+        self.logos.clear();
+        self.fingerprints.clear();
+    }
+}
+
+
+/// 可序列化对象的规范文本：先转为json值（对象的键有序），再输出为json文本
+pub fn canonical_json<T: Serialize>(value: &T) -> Result<String> {
+    Ok(serde_json::to_value(value)?.to_string())
+}
+
+/// 计算可序列化对象的规范哈希，即规范文本的哈希
+pub fn canonical_hash<T: Serialize>(value: &T) -> Result<u64> {
+    Ok(hash_bytes(canonical_json(value)?.as_bytes()))
+}
+
+
+pub(crate) fn hash_bytes(bytes: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    bytes.hash(&mut hasher);
+    hasher.finish()
+}
+
+
+#[cfg(test)]
+#[test]
+fn test_weighted_lru_cache_evicts_by_weight() {
+    let cache: WeightedLruCache<&str, u32> = WeightedLruCache::new(3, 10);
+    cache.insert("a", 1, 4);
+    cache.insert("b", 2, 4);
+    assert_eq!(cache.get(&"a"), Some(1));
+    // 超出总权重，淘汰最久未使用的b
+    cache.insert("c", 3, 4);
+    assert_eq!(cache.get(&"b"), None);
+    cache.remove_if(|k| *k == "a");
+    assert_eq!(cache.get(&"a"), None);
+
+    let stats = cache.stats();
+    assert_eq!((stats.hits, stats.misses, stats.entries, stats.weight), (1, 2, 1, 4));
+}
+
+
+#[cfg(test)]
+#[test]
+fn test_update_weight_evicts_when_entries_grow() {
+    let cache: WeightedLruCache<&str, u32> = WeightedLruCache::new(3, 10);
+    cache.insert("a", 1, 3);
+    cache.insert("b", 2, 3);
+    // a的权重增长后超出总权重上限，淘汰最久未使用的a
+    cache.update_weight(&"a", 8);
+    assert_eq!(cache.get(&"a"), None);
+    assert_eq!(cache.stats().weight, 3);
+    cache.update_weight(&"b", 5);
+    assert_eq!(cache.get(&"b"), Some(2));
+    assert_eq!(cache.stats().weight, 5);
+}
+
+
+#[cfg(test)]
+#[test]
+fn test_canonical_json_ignores_key_order() {
+    let a: serde_json::Value = serde_json::from_str(r#"{"b": 1, "a": [2, 3]}"#).unwrap();
+    let b: serde_json::Value = serde_json::from_str(r#"{"a": [2, 3], "b": 1}"#).unwrap();
+    assert_eq!(canonical_json(&a).unwrap(), r#"{"a":[2,3],"b":1}"#);
+    assert_eq!(canonical_json(&a).unwrap(), canonical_json(&b).unwrap());
+    assert_eq!(canonical_hash(&a).unwrap(), canonical_hash(&b).unwrap());
+}
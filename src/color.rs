@@ -0,0 +1,237 @@
+use std::fmt;
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{error::Result, Color, ProcessError};
+
+
+/// 解析颜色，支持：
+/// - #RGB、#RGBA、#RRGGBB、#RRGGBBAA
+/// - CSS颜色名称，如red、rebeccapurple、transparent
+/// - rgb()/rgba()/hsl()/hsla()，参数可用逗号或空格分隔，透明度可用`/`分隔
+pub(crate) fn parse_color(value: &str) -> Result<Color> {
+    let invalid = |reason| ProcessError::InvalidColor { value: value.to_string(), reason };
+    let s = value.trim();
+    if let Some(digits) = s.strip_prefix('#') {
+        return parse_hex(digits).ok_or_else(|| invalid("hex color must have 3, 4, 6 or 8 hex digits"));
+    }
+    let lower = s.to_ascii_lowercase();
+    if let Some((name, args)) = lower.strip_suffix(')').and_then(|s| s.split_once('(')) {
+        let args = split_args(args).ok_or_else(|| invalid("malformed color function arguments"))?;
+        return match name.trim() {
+            "rgb" | "rgba" => parse_rgb(&args).ok_or_else(|| invalid("rgb() expects 3 channels in [0, 255] or percentages and an optional alpha")),
+            "hsl" | "hsla" => parse_hsl(&args).ok_or_else(|| invalid("hsl() expects a hue, two percentages and an optional alpha")),
+            _ => Err(invalid("unknown color function")),
+        };
+    }
+    named_color(&lower).ok_or_else(|| invalid("unknown color name"))
+}
+
+fn parse_hex(digits: &str) -> Option<Color> {
+    if !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let nibble = |i: usize| u8::from_str_radix(&digits[i..i + 1], 16).ok().map(|v| v * 17);
+    let byte = |i: usize| u8::from_str_radix(&digits[i * 2..i * 2 + 2], 16).ok();
+    let rgba = match digits.len() {
+        3 => (nibble(0)?, nibble(1)?, nibble(2)?, 255),
+        4 => (nibble(0)?, nibble(1)?, nibble(2)?, nibble(3)?),
+        6 => (byte(0)?, byte(1)?, byte(2)?, 255),
+        8 => (byte(0)?, byte(1)?, byte(2)?, byte(3)?),
+        _ => return None,
+    };
+    Some(Color::from_rgba(rgba))
+}
+
+/// 拆分函数参数，支持`1, 2, 3, 0.5`和`1 2 3 / 0.5`两种写法，返回的第4项为透明度
+fn split_args(args: &str) -> Option<Vec<&str>> {
+    let (channels, alpha) = match args.split_once('/') {
+        Some((channels, alpha)) => (channels, Some(alpha.trim())),
+        None => (args, None),
+    };
+    let mut parts: Vec<&str> = if channels.contains(',') {
+        channels.split(',').map(str::trim).collect()
+    } else {
+        channels.split_whitespace().collect()
+    };
+    if parts.iter().any(|p| p.is_empty()) {
+        return None;
+    }
+    if let Some(alpha) = alpha {
+        if parts.len() != 3 || alpha.is_empty() {
+            return None;
+        }
+        parts.push(alpha);
+    }
+    matches!(parts.len(), 3 | 4).then_some(parts)
+}
+
+/// 解析数值或百分比，百分比按scale换算
+fn parse_number(arg: &str, scale: f32) -> Option<f32> {
+    let value = match arg.strip_suffix('%') {
+        Some(percent) => percent.trim().parse::<f32>().ok()? / 100.0 * scale,
+        None => arg.parse::<f32>().ok()?,
+    };
+    value.is_finite().then_some(value)
+}
+
+fn parse_alpha(args: &[&str]) -> Option<u8> {
+    match args.get(3) {
+        Some(arg) => {
+            let alpha = parse_number(arg, 1.0)?;
+            (0.0..=1.0).contains(&alpha).then(|| (alpha * 255.0).round() as u8)
+        },
+        None => Some(255),
+    }
+}
+
+fn parse_rgb(args: &[&str]) -> Option<Color> {
+    let channel = |arg: &str| {
+        let value = parse_number(arg, 255.0)?;
+        (0.0..=255.0).contains(&value).then(|| value.round() as u8)
+    };
+    Some(Color::from_rgba((channel(args[0])?, channel(args[1])?, channel(args[2])?, parse_alpha(args)?)))
+}
+
+fn parse_hsl(args: &[&str]) -> Option<Color> {
+    let hue = args[0].strip_suffix("deg").unwrap_or(args[0]).trim().parse::<f32>().ok()?;
+    let percent = |arg: &str| {
+        let value = arg.strip_suffix('%')?.trim().parse::<f32>().ok()? / 100.0;
+        (0.0..=1.0).contains(&value).then_some(value)
+    };
+    if !hue.is_finite() {
+        return None;
+    }
+    let (r, g, b) = hsl_to_rgb(hue.rem_euclid(360.0), percent(args[1])?, percent(args[2])?);
+    Some(Color::from_rgba((r, g, b, parse_alpha(args)?)))
+}
+
+fn hsl_to_rgb(hue: f32, saturation: f32, lightness: f32) -> (u8, u8, u8) {
+    let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+    let h = hue / 60.0;
+    let x = chroma * (1.0 - (h % 2.0 - 1.0).abs());
+    let (r, g, b) = match h as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = lightness - chroma / 2.0;
+    let to_u8 = |v: f32| ((v + m) * 255.0).round().clamp(0.0, 255.0) as u8;
+    (to_u8(r), to_u8(g), to_u8(b))
+}
+
+fn named_color(name: &str) -> Option<Color> {
+    if name == "transparent" {
+        return Some(Color::from_rgba((0, 0, 0, 0)));
+    }
+    NAMED_COLORS.iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, rgb)| Color::from_rgb(((rgb >> 16) as u8, (rgb >> 8) as u8, *rgb as u8)))
+}
+
+/// CSS颜色名称
+const NAMED_COLORS: &[(&str, u32)] = &[
+    ("aliceblue", 0xF0F8FF), ("antiquewhite", 0xFAEBD7), ("aqua", 0x00FFFF), ("aquamarine", 0x7FFFD4),
+    ("azure", 0xF0FFFF), ("beige", 0xF5F5DC), ("bisque", 0xFFE4C4), ("black", 0x000000),
+    ("blanchedalmond", 0xFFEBCD), ("blue", 0x0000FF), ("blueviolet", 0x8A2BE2), ("brown", 0xA52A2A),
+    ("burlywood", 0xDEB887), ("cadetblue", 0x5F9EA0), ("chartreuse", 0x7FFF00), ("chocolate", 0xD2691E),
+    ("coral", 0xFF7F50), ("cornflowerblue", 0x6495ED), ("cornsilk", 0xFFF8DC), ("crimson", 0xDC143C),
+    ("cyan", 0x00FFFF), ("darkblue", 0x00008B), ("darkcyan", 0x008B8B), ("darkgoldenrod", 0xB8860B),
+    ("darkgray", 0xA9A9A9), ("darkgreen", 0x006400), ("darkgrey", 0xA9A9A9), ("darkkhaki", 0xBDB76B),
+    ("darkmagenta", 0x8B008B), ("darkolivegreen", 0x556B2F), ("darkorange", 0xFF8C00), ("darkorchid", 0x9932CC),
+    ("darkred", 0x8B0000), ("darksalmon", 0xE9967A), ("darkseagreen", 0x8FBC8F), ("darkslateblue", 0x483D8B),
+    ("darkslategray", 0x2F4F4F), ("darkslategrey", 0x2F4F4F), ("darkturquoise", 0x00CED1), ("darkviolet", 0x9400D3),
+    ("deeppink", 0xFF1493), ("deepskyblue", 0x00BFFF), ("dimgray", 0x696969), ("dimgrey", 0x696969),
+    ("dodgerblue", 0x1E90FF), ("firebrick", 0xB22222), ("floralwhite", 0xFFFAF0), ("forestgreen", 0x228B22),
+    ("fuchsia", 0xFF00FF), ("gainsboro", 0xDCDCDC), ("ghostwhite", 0xF8F8FF), ("gold", 0xFFD700),
+    ("goldenrod", 0xDAA520), ("gray", 0x808080), ("green", 0x008000), ("greenyellow", 0xADFF2F),
+    ("grey", 0x808080), ("honeydew", 0xF0FFF0), ("hotpink", 0xFF69B4), ("indianred", 0xCD5C5C),
+    ("indigo", 0x4B0082), ("ivory", 0xFFFFF0), ("khaki", 0xF0E68C), ("lavender", 0xE6E6FA),
+    ("lavenderblush", 0xFFF0F5), ("lawngreen", 0x7CFC00), ("lemonchiffon", 0xFFFACD), ("lightblue", 0xADD8E6),
+    ("lightcoral", 0xF08080), ("lightcyan", 0xE0FFFF), ("lightgoldenrodyellow", 0xFAFAD2), ("lightgray", 0xD3D3D3),
+    ("lightgreen", 0x90EE90), ("lightgrey", 0xD3D3D3), ("lightpink", 0xFFB6C1), ("lightsalmon", 0xFFA07A),
+    ("lightseagreen", 0x20B2AA), ("lightskyblue", 0x87CEFA), ("lightslategray", 0x778899), ("lightslategrey", 0x778899),
+    ("lightsteelblue", 0xB0C4DE), ("lightyellow", 0xFFFFE0), ("lime", 0x00FF00), ("limegreen", 0x32CD32),
+    ("linen", 0xFAF0E6), ("magenta", 0xFF00FF), ("maroon", 0x800000), ("mediumaquamarine", 0x66CDAA),
+    ("mediumblue", 0x0000CD), ("mediumorchid", 0xBA55D3), ("mediumpurple", 0x9370DB), ("mediumseagreen", 0x3CB371),
+    ("mediumslateblue", 0x7B68EE), ("mediumspringgreen", 0x00FA9A), ("mediumturquoise", 0x48D1CC), ("mediumvioletred", 0xC71585),
+    ("midnightblue", 0x191970), ("mintcream", 0xF5FFFA), ("mistyrose", 0xFFE4E1), ("moccasin", 0xFFE4B5),
+    ("navajowhite", 0xFFDEAD), ("navy", 0x000080), ("oldlace", 0xFDF5E6), ("olive", 0x808000),
+    ("olivedrab", 0x6B8E23), ("orange", 0xFFA500), ("orangered", 0xFF4500), ("orchid", 0xDA70D6),
+    ("palegoldenrod", 0xEEE8AA), ("palegreen", 0x98FB98), ("paleturquoise", 0xAFEEEE), ("palevioletred", 0xDB7093),
+    ("papayawhip", 0xFFEFD5), ("peachpuff", 0xFFDAB9), ("peru", 0xCD853F), ("pink", 0xFFC0CB),
+    ("plum", 0xDDA0DD), ("powderblue", 0xB0E0E6), ("purple", 0x800080), ("rebeccapurple", 0x663399),
+    ("red", 0xFF0000), ("rosybrown", 0xBC8F8F), ("royalblue", 0x4169E1), ("saddlebrown", 0x8B4513),
+    ("salmon", 0xFA8072), ("sandybrown", 0xF4A460), ("seagreen", 0x2E8B57), ("seashell", 0xFFF5EE),
+    ("sienna", 0xA0522D), ("silver", 0xC0C0C0), ("skyblue", 0x87CEEB), ("slateblue", 0x6A5ACD),
+    ("slategray", 0x708090), ("slategrey", 0x708090), ("snow", 0xFFFAFA), ("springgreen", 0x00FF7F),
+    ("steelblue", 0x4682B4), ("tan", 0xD2B48C), ("teal", 0x008080), ("thistle", 0xD8BFD8),
+    ("tomato", 0xFF6347), ("turquoise", 0x40E0D0), ("violet", 0xEE82EE), ("wheat", 0xF5DEB3),
+    ("white", 0xFFFFFF), ("whitesmoke", 0xF5F5F5), ("yellow", 0xFFFF00), ("yellowgreen", 0x9ACD32),
+];
+
+
+/// 序列化为#RRGGBBAA
+impl Serialize for Color {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_rgba_string())
+    }
+}
+
+/// 反序列化时支持颜色字符串，以及旧格式的[r, g, b]/[r, g, b, a]数组
+impl<'de> Deserialize<'de> for Color {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        deserializer.deserialize_any(ColorVisitor)
+    }
+}
+
+struct ColorVisitor;
+
+impl<'de> de::Visitor<'de> for ColorVisitor {
+    type Value = Color;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a color string or an [r, g, b, a] array")
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> std::result::Result<Color, E> {
+        parse_color(value).map_err(E::custom)
+    }
+
+    fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> std::result::Result<Color, A::Error> {
+        let mut channel = |i: usize| seq.next_element::<u8>()?.ok_or_else(|| de::Error::invalid_length(i, &self));
+        let (r, g, b) = (channel(0)?, channel(1)?, channel(2)?);
+        let a = seq.next_element::<u8>()?.unwrap_or(255);
+        if seq.next_element::<de::IgnoredAny>()?.is_some() {
+            return Err(de::Error::invalid_length(5, &self));
+        }
+        Ok(Color::from_rgba((r, g, b, a)))
+    }
+}
+
+
+#[cfg(test)]
+#[test]
+fn test_parse_color_syntaxes() {
+    let rgba = |s: &str| parse_color(s).unwrap().to_rgba();
+    assert_eq!(rgba("#f0a"), (255, 0, 170, 255));
+    assert_eq!(rgba("#f0a8"), (255, 0, 170, 136));
+    assert_eq!(rgba("RebeccaPurple"), (102, 51, 153, 255));
+    assert_eq!(rgba("transparent"), (0, 0, 0, 0));
+    assert_eq!(rgba("rgb(255, 0, 0)"), (255, 0, 0, 255));
+    assert_eq!(rgba("rgba(0, 128, 255, 0.5)"), (0, 128, 255, 128));
+    assert_eq!(rgba("rgb(100% 0% 0% / 50%)"), (255, 0, 0, 128));
+    assert_eq!(rgba("hsl(120, 100%, 25%)"), (0, 128, 0, 255));
+    assert_eq!(rgba("hsla(240deg 100% 50% / 0.2)"), (0, 0, 255, 51));
+    for bad in ["rgb(256, 0, 0)", "rgb(1, 2)", "hsl(0, 50, 50%)", "notacolor", "#12345", "rgb(1, 2, 3"] {
+        assert!(parse_color(bad).is_err(), "{}", bad);
+    }
+
+    let color: Color = serde_json::from_str("\"#ff000080\"").unwrap();
+    assert_eq!(serde_json::to_string(&color).unwrap(), "\"#FF000080\"");
+    let legacy: Color = serde_json::from_str("[255, 255, 255]").unwrap();
+    assert_eq!(legacy.to_rgba(), (255, 255, 255, 255));
+}
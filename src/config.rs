@@ -0,0 +1,148 @@
+use std::{fs, net::SocketAddr, path::{Path, PathBuf}};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use crate::{
+    DEFAULT_CONVERT_CACHE_BYTES, DEFAULT_CONVERT_CACHE_ENTRIES, DEFAULT_JOB_QUEUE_CAPACITY, DEFAULT_JOB_WORKERS,
+    DEFAULT_LOGO_CACHE_BYTES, DEFAULT_LOGO_CACHE_ENTRIES,
+};
+
+
+/// 服务配置，可从TOML文件加载，未配置的项使用默认值
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// 监听地址
+    pub bind: SocketAddr,
+    /// logo图片存储目录
+    pub image_dir: PathBuf,
+    /// 前端静态资源目录
+    pub static_dir: PathBuf,
+    /// 日志级别，支持tracing的过滤指令，如"info,logo_process=debug"
+    pub log_level: String,
+    /// 允许跨域访问的来源，"*"表示任意来源；为空时不启用跨域
+    pub cors_origins: Vec<String>,
+    pub repo: RepoConfig,
+    pub limits: LimitsConfig,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind: SocketAddr::from(([0, 0, 0, 0], 8002)),
+            image_dir: PathBuf::from("images"),
+            static_dir: PathBuf::from("./logo-mosaic-web/dist"),
+            log_level: "info".to_string(),
+            cors_origins: Vec::new(),
+            repo: RepoConfig::default(),
+            limits: LimitsConfig::default(),
+        }
+    }
+}
+
+impl ServerConfig {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("failed to read config file: {}", path.display()))?;
+        toml::from_str(&content)
+            .with_context(|| format!("failed to parse config file: {}", path.display()))
+    }
+
+    /// 检查各项配置能否一起使用
+    pub fn validate(&self) -> Result<()> {
+        self.repo.validate()
+    }
+}
+
+
+/// 活动和图片数据的存储方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum RepoBackend {
+    /// 内存存储，重启后数据丢失
+    #[default]
+    Memory,
+}
+
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RepoConfig {
+    pub backend: RepoBackend,
+    /// 数据存储路径，内存存储不支持设置
+    pub path: Option<PathBuf>,
+}
+
+impl RepoConfig {
+    /// 存储路径须与存储方式匹配，避免配置了路径却没有持久化
+    pub fn validate(&self) -> Result<()> {
+        match (self.backend, &self.path) {
+            (RepoBackend::Memory, Some(path)) if !path.as_os_str().is_empty() => anyhow::bail!(
+                "repo.path ({}) is not supported by the memory backend, data is not persisted; remove it", path.display()),
+            _ => Ok(()),
+        }
+    }
+}
+
+
+/// 资源限制
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitsConfig {
+    /// 请求体的最大字节数
+    pub max_body_bytes: usize,
+    /// 同时执行的异步转换任务数
+    pub job_workers: usize,
+    /// 最多排队(含执行中)的异步转换任务数
+    pub job_queue_capacity: usize,
+    /// 最多缓存的logo数
+    pub logo_cache_entries: usize,
+    /// logo缓存的像素数据及积分图上限(字节)
+    pub logo_cache_bytes: usize,
+    /// 最多缓存的转换结果数
+    pub convert_cache_entries: usize,
+    /// 转换结果缓存的上限(字节)
+    pub convert_cache_bytes: usize,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_body_bytes: 2 * 1024 * 1024,
+            job_workers: DEFAULT_JOB_WORKERS,
+            job_queue_capacity: DEFAULT_JOB_QUEUE_CAPACITY,
+            logo_cache_entries: DEFAULT_LOGO_CACHE_ENTRIES,
+            logo_cache_bytes: DEFAULT_LOGO_CACHE_BYTES,
+            convert_cache_entries: DEFAULT_CONVERT_CACHE_ENTRIES,
+            convert_cache_bytes: DEFAULT_CONVERT_CACHE_BYTES,
+        }
+    }
+}
+
+
+#[cfg(test)]
+#[test]
+fn test_partial_config_uses_defaults() {
+    let config: ServerConfig = toml::from_str(r#"
+        bind = "127.0.0.1:9000"
+        cors_origins = ["http://localhost:5173"]
+
+        [limits]
+        job_workers = 4
+    "#).unwrap();
+    assert_eq!(config.bind.port(), 9000);
+    assert_eq!(config.image_dir, PathBuf::from("images"));
+    assert_eq!(config.repo.backend, RepoBackend::Memory);
+    assert_eq!(config.limits.job_workers, 4);
+    assert_eq!(config.limits.job_queue_capacity, DEFAULT_JOB_QUEUE_CAPACITY);
+    assert_eq!(config.limits.convert_cache_bytes, DEFAULT_CONVERT_CACHE_BYTES);
+    assert!(toml::from_str::<ServerConfig>("port = 8002").is_err());
+    assert!(config.validate().is_ok());
+
+    let config: ServerConfig = toml::from_str(r#"
+        [repo]
+        backend = "memory"
+        path = "data"
+    "#).unwrap();
+    assert!(config.validate().unwrap_err().to_string().contains("repo.path"));
+}
@@ -0,0 +1,68 @@
+use thiserror::Error;
+
+
+/// logo处理过程中的错误；参数类错误带有出错的字段名(与接口字段名一致)
+#[derive(Debug, Error)]
+pub enum ProcessError {
+    #[error("invalid color {value:?}: {reason}")]
+    InvalidColor { value: String, reason: &'static str },
+    #[error("invalid {field}: {message}")]
+    InvalidParameter { field: &'static str, code: &'static str, message: String },
+    #[error("grid {seq} is invalid: {message}")]
+    InvalidGrid { seq: String, message: String },
+    #[error("grid {0} does not cover any pixels")]
+    EmptyGrid(String),
+    #[error("{kind} {name} is not registered")]
+    NotRegistered { kind: &'static str, name: String },
+    #[error("failed to load logo image {path}: {source}")]
+    LoadImage { path: String, source: image::ImageError },
+    #[error("grid picking is cancelled")]
+    Cancelled,
+    #[error(transparent)]
+    Image(#[from] image::ImageError),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl ProcessError {
+    pub(crate) fn invalid_parameter(field: &'static str, message: impl Into<String>) -> Self {
+        Self::invalid_parameter_with_code(field, "invalid", message)
+    }
+
+    /// 带错误码的参数错误，错误码与接口校验错误的code一致
+    pub(crate) fn invalid_parameter_with_code(field: &'static str, code: &'static str, message: impl Into<String>) -> Self {
+        ProcessError::InvalidParameter { field, code, message: message.into() }
+    }
+
+    /// 参数类错误对应的字段名；颜色和格子错误不知道来自哪个字段，由调用方映射
+    pub fn field(&self) -> Option<&'static str> {
+        match self {
+            ProcessError::InvalidParameter { field, .. } => Some(field),
+            ProcessError::NotRegistered { kind, .. } => Some(kind),
+            _ => None,
+        }
+    }
+}
+
+
+pub type Result<T, E = ProcessError> = std::result::Result<T, E>;
+
+
+#[cfg(test)]
+#[test]
+fn test_invalid_color_and_grid_size() {
+    use crate::{generate_enmty_canvas_grids, Color, GridFillOptions};
+
+    assert_eq!("#ff000080".parse::<Color>().unwrap().to_rgba(), (255, 0, 0, 128));
+    for bad in ["ff0000", "#ff000", "#gg0000", "#ff00é0", ""] {
+        let err = bad.parse::<Color>().unwrap_err();
+        assert!(matches!(err, ProcessError::InvalidColor { .. }), "{bad}: {err}");
+        assert_eq!(err.field(), None);
+    }
+    let err = generate_enmty_canvas_grids(10, 10, GridFillOptions::rectangle(0, 10)).unwrap_err();
+    assert_eq!(err.field(), Some("gridSize"));
+}
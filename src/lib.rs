@@ -2,24 +2,45 @@ mod process;
 mod web;
 mod repo;
 mod utils;
+mod cache;
+mod config;
+mod error;
+mod color;
 
 use anyhow::Result;
 use image::Rgba;
 use serde::{Deserialize, Serialize};
-use std::str::FromStr;
+use std::{fmt, str::FromStr};
 pub use process::*;
 pub use web::*;
 pub use repo::*;
-pub use utils::calc_color_distance;
+pub use cache::*;
+pub use config::*;
+pub use error::ProcessError;
+pub use utils::{calc_color_distance, find_nearest_color};
 
 
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum GridShape {
     #[serde(rename = "triangle")]
     Triangle,
     #[serde(rename = "rectangle")]
     Rectangle,
+    // 被画布边界裁剪后的格子，不能用于铺排
+    #[serde(rename = "polygon")]
+    Polygon,
+}
+
+impl GridShape {
+    /// 格子的顶点数，多边形的顶点数不固定
+    pub fn vertex_count(&self) -> Option<usize> {
+        match self {
+            GridShape::Triangle => Some(3),
+            GridShape::Rectangle => Some(4),
+            GridShape::Polygon => None,
+        }
+    }
 }
 
 impl From<GridShape> for String {
@@ -27,6 +48,7 @@ impl From<GridShape> for String {
         match shape {
             GridShape::Triangle => "triangle".to_string(),
             GridShape::Rectangle => "rectangle".to_string(),
+            GridShape::Polygon => "polygon".to_string(),
         }
     }
 }
@@ -42,7 +64,7 @@ pub struct Grid {
 }
 
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct GridExt {
     pub avg_color: Option<Color>,
@@ -51,35 +73,51 @@ pub struct GridExt {
     pub selected: Option<bool>,
     pub fill_color: Option<Color>,
     pub border_color: Option<Color>,
+    // 按调色板分类时，格子归属的调色板下标
+    pub palette_index: Option<usize>,
+    // 边缘像素占比
+    pub edge_ratio: Option<f32>,
+    // 穿过格子的最粗笔画宽度(像素)
+    pub stroke_width: Option<f32>,
+    // 各基础选取策略的匹配度，组合策略下按命令树的先序依次记录
+    pub pick_scores: Option<Vec<GridPickScore>>,
 }
 
-impl Default for GridExt {
-    fn default() -> Self {
-        Self {
-            avg_color: None,
-            color_distance: None,
-            remaining_area_ratio: None,
-            selected: None,
-            fill_color: None,
-            border_color: None,
-        }
-    }
-}
 
+/// 单个基础选取策略对格子的评估结果
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GridPickScore {
+    pub strategy: GridPickStrategy,
+    // 匹配度，范围[0, 1]
+    pub score: f32,
+    pub selected: bool,
+}
 
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+/// 画布上的点，坐标可以落在像素之间；整数坐标仍序列化为整数
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
 pub struct Point {
-    pub x: u32,
-    pub y: u32,
+    #[serde(serialize_with = "serialize_coord")]
+    pub x: f32,
+    #[serde(serialize_with = "serialize_coord")]
+    pub y: f32,
 }
 
 impl Point {
-    pub fn new(x: u32, y: u32) -> Self {
+    pub fn new(x: f32, y: f32) -> Self {
         Self { x, y }
     }
 }
 
+fn serialize_coord<S: serde::Serializer>(value: &f32, serializer: S) -> Result<S::Ok, S::Error> {
+    if value.fract() == 0.0 && value.abs() < (1 << 24) as f32 {
+        serializer.serialize_i64(*value as i64)
+    } else {
+        serializer.serialize_f32(*value)
+    }
+}
+
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CanvasGridGenerateParams {
@@ -93,27 +131,231 @@ pub struct CanvasGridGenerateParams {
 
 /// 画布填充选项
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
-pub enum GridFillOptions {
-    // 三角形（宽，高）
-    Triangle(u32, u32),
-    // // 矩形（宽，高）
-    Rectangle(u32, u32),
+#[serde(rename_all = "camelCase")]
+pub struct GridFillOptions {
+    // 格子形状
+    pub shape: GridShape,
+    // 格子宽度
+    pub width: u32,
+    // 格子高度
+    pub height: u32,
+    // 画布留白及格子的对齐方式
+    #[serde(default)]
+    pub layout: GridLayout,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+impl GridFillOptions {
+    pub fn new(shape: GridShape, width: u32, height: u32) -> Self {
+        Self { shape, width, height, layout: GridLayout::default() }
+    }
+
+    /// 三角形（宽，高）
+    pub fn triangle(width: u32, height: u32) -> Self {
+        Self::new(GridShape::Triangle, width, height)
+    }
+
+    /// 矩形（宽，高）
+    pub fn rectangle(width: u32, height: u32) -> Self {
+        Self::new(GridShape::Rectangle, width, height)
+    }
+
+    pub fn with_layout(mut self, layout: GridLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    /// 格子形状
+    pub fn shape(&self) -> GridShape {
+        self.shape
+    }
+
+    /// 格子尺寸（宽，高）
+    pub fn grid_size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// 画布上生成的格子数量(估算)；三角形每行有正、倒两种三角形
+    pub fn grid_count(&self, canvas_width: u32, canvas_height: u32) -> u64 {
+        let (w, h) = self.grid_size();
+        if w == 0 || h == 0 {
+            return 0;
+        }
+        let (content_width, content_height) = self.layout.margin.content_size(canvas_width, canvas_height);
+        if self.layout.is_transformed() {
+            // 按铺排区域在网格坐标系中的外接矩形估算，四周各多算一格
+            let (sin, cos) = self.layout.rotation.to_radians().sin_cos();
+            let (cw, ch) = (content_width as f32, content_height as f32);
+            let (bw, bh) = (cw * cos.abs() + ch * sin.abs(), cw * sin.abs() + ch * cos.abs());
+            let cells = ((bw / w as f32).ceil() as u64 + 2) * ((bh / h as f32).ceil() as u64 + 2);
+            return match self.shape {
+                GridShape::Triangle => cells * 2,
+                _ => cells,
+            };
+        }
+        let count = |content: u32, size: u32| match self.layout.align {
+            GridAlign::Start | GridAlign::Center => (content / size) as u64,
+            GridAlign::Stretch if content == 0 => 0,
+            GridAlign::Stretch => ((content as f32 / size as f32).round() as u64).max(1),
+            GridAlign::Clip => content.div_ceil(size) as u64 + 1,
+        };
+        let cells = count(content_width, w) * count(content_height, h);
+        match self.shape {
+            GridShape::Triangle => cells * 2,
+            _ => cells,
+        }
+    }
+
+    /// 保持形状和布局不变，替换格子尺寸
+    pub fn with_grid_size(&self, width: u32, height: u32) -> Self {
+        Self { width, height, ..*self }
+    }
+}
+
+
+/// 画布四周的留白(像素)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Margins {
+    pub top: u32,
+    pub right: u32,
+    pub bottom: u32,
+    pub left: u32,
+}
+
+impl Margins {
+    /// 四周相同的留白
+    pub fn uniform(margin: u32) -> Self {
+        Self { top: margin, right: margin, bottom: margin, left: margin }
+    }
+
+    /// 去掉留白后可铺排格子的区域宽高
+    pub fn content_size(&self, canvas_width: u32, canvas_height: u32) -> (u32, u32) {
+        (
+            canvas_width.saturating_sub(self.left + self.right),
+            canvas_height.saturating_sub(self.top + self.bottom),
+        )
+    }
+}
+
+/// 格子在铺排区域内的对齐方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum GridAlign {
+    // 从左上角开始铺排，右侧和底部放不下整格的部分留空
+    #[default]
+    Start,
+    // 放不下整格的部分平均分到两侧，铺排结果居中
+    Center,
+    // 调整格子尺寸使整数个格子恰好铺满，不能与旋转或平移同时使用
+    Stretch,
+    // 铺满整个区域，边缘不完整的格子按区域边界裁剪
+    Clip,
+}
+
+/// 画布留白及格子的对齐方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct GridLayout {
+    pub margin: Margins,
+    pub align: GridAlign,
+    // 网格绕原点旋转的角度(度，y轴向下时顺时针为正)，如45度的矩形为菱形
+    pub rotation: f32,
+    // 网格原点(画布坐标)，网格的一个顶点落在原点上；未指定时居中对齐取铺排区域的中心，否则取左上角
+    pub origin: Option<Point>,
+}
+
+impl GridLayout {
+    /// 是否旋转或平移了网格；此时格子的顶点可能是小数，只有裁剪模式会保留边缘不完整的格子，不支持拉伸对齐
+    pub fn is_transformed(&self) -> bool {
+        self.rotation % 360.0 != 0.0 || self.origin.is_some()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum GridPickCmd{
     // 区域平均色值比较
     AvgColorCompare(AvgColorCompareParam),
     // 剔除背景色后，
     EliminateBgColor(EliminateBgColorParam),
+    // 按调色板对格子分类
+    PaletteClassify(PaletteClassifyParam),
+    // 在剔除背景色的基础上，保留细笔画穿过的格子
+    EdgeAware(EdgeAwareParam),
+    // 所有子命令都选中时选中
+    All(Vec<GridPickCmd>),
+    // 任一子命令选中时选中
+    Any(Vec<GridPickCmd>),
+    // 子命令未选中时选中
+    Not(Box<GridPickCmd>),
+    // 子命令匹配度加权平均，不低于min_score时选中
+    Weighted(WeightedPickParam),
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+impl GridPickCmd {
+    /// 选取阈值：平均色值比较策略为最小色差，加权组合为最小匹配度，其余策略为前景像素最小占比；
+    /// All/Any/Not组合取第一个子命令的阈值
+    pub fn pick_threshold(&self) -> f32 {
+        match self {
+            GridPickCmd::AvgColorCompare(param) => param.min_distance,
+            GridPickCmd::EliminateBgColor(param) => param.min_remaining_ratio,
+            GridPickCmd::PaletteClassify(param) => param.min_remaining_ratio,
+            GridPickCmd::EdgeAware(param) => param.min_remaining_ratio,
+            GridPickCmd::All(cmds) | GridPickCmd::Any(cmds) => {
+                cmds.first().map(|cmd| cmd.pick_threshold()).unwrap_or(0.0)
+            },
+            GridPickCmd::Not(cmd) => cmd.pick_threshold(),
+            GridPickCmd::Weighted(param) => param.min_score,
+        }
+    }
+
+    /// 选取阈值的合理取值范围，超出该范围时logo的形状难以保持；
+    /// 从范围的第一个值调整到第二个值时，选中的格子逐渐变少（Not组合的范围是反向的）
+    pub fn pick_threshold_range(&self) -> (f32, f32) {
+        match self {
+            GridPickCmd::AvgColorCompare(param) => (0.0, param.max_distance),
+            GridPickCmd::All(cmds) | GridPickCmd::Any(cmds) => {
+                cmds.first().map(|cmd| cmd.pick_threshold_range()).unwrap_or((0.0, 0.0))
+            },
+            GridPickCmd::Not(cmd) => {
+                let (lo, hi) = cmd.pick_threshold_range();
+                (hi, lo)
+            },
+            GridPickCmd::Weighted(_) => (0.0, 1.0),
+            _ => (0.05, 0.95),
+        }
+    }
+
+    /// 替换选取阈值，其余参数不变
+    pub fn with_pick_threshold(&self, threshold: f32) -> Self {
+        let mut cmd = self.clone();
+        match &mut cmd {
+            GridPickCmd::AvgColorCompare(param) => param.min_distance = threshold,
+            GridPickCmd::EliminateBgColor(param) => param.min_remaining_ratio = threshold,
+            GridPickCmd::PaletteClassify(param) => param.min_remaining_ratio = threshold,
+            GridPickCmd::EdgeAware(param) => param.min_remaining_ratio = threshold,
+            GridPickCmd::All(cmds) | GridPickCmd::Any(cmds) => {
+                if let Some(first) = cmds.first_mut() {
+                    *first = first.with_pick_threshold(threshold);
+                }
+            },
+            GridPickCmd::Not(inner) => **inner = inner.with_pick_threshold(threshold),
+            GridPickCmd::Weighted(param) => param.min_score = threshold,
+        }
+        cmd
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
 pub enum GridPickStrategy{
     // 计算格子内像素点的平均色值，与目标色值比较差值
     AvgColorCompare,
     // 剔除背景色，根据剩余像素点的占比来选择格子
+    #[default]
     EliminateBgColor,
+    // 将格子内像素归类到调色板颜色，支持多色logo
+    PaletteClassify,
+    // 结合覆盖率与边缘/笔画检测，保留细线条和小字
+    EdgeAware,
 }
 
 
@@ -137,6 +379,52 @@ pub struct EliminateBgColorParam{
 
 
 
+/// 按调色板分类
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PaletteClassifyParam{
+    // 调色板，为空时从logo中自动提取
+    pub palette: Vec<Color>,
+    // 自动提取调色板时的颜色数量
+    pub palette_size: usize,
+    // 背景色
+    pub bg_color: Color,
+    // 前景像素的最小占比
+    pub min_remaining_ratio: f32,
+}
+
+
+
+/// 边缘感知选取
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct EdgeAwareParam{
+    // 背景色
+    pub bg_color: Color,
+    // 前景像素的最小占比，达到该占比的格子直接选中
+    pub min_remaining_ratio: f32,
+    // 细笔画的最小宽度(像素)，更细的线条视为噪点
+    pub min_stroke_width: f32,
+    // 细笔画格子中边缘像素的最小占比
+    pub min_edge_ratio: f32,
+}
+
+
+
+/// 加权组合
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WeightedPickParam{
+    pub items: Vec<WeightedPickItem>,
+    // 加权平均匹配度的最小值
+    pub min_score: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WeightedPickItem{
+    pub cmd: GridPickCmd,
+    pub weight: f32,
+}
+
+
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ImageInfo {
     pub id: String,
@@ -147,21 +435,22 @@ pub struct ImageInfo {
     pub bg_color: (u8, u8, u8),
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+/// 颜色，序列化为#RRGGBBAA格式的字符串
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Color((u8, u8, u8, u8));
 
 impl Color {
-    pub fn from_rgb((r,g, b): (u8,u8,u8)) -> Self {
+    pub const WHITE: Color = Color((255, 255, 255, 255));
+    pub const BLACK: Color = Color((0, 0, 0, 255));
+
+    pub const fn from_rgb((r,g, b): (u8,u8,u8)) -> Self {
         Color((r, g, b, 255))
     }
 
-    pub fn from_rgba((r,g, b, a): (u8,u8,u8,u8)) -> Self {
+    pub const fn from_rgba((r,g, b, a): (u8,u8,u8,u8)) -> Self {
         Color((r, g, b, a))
     }
 
-    pub fn to_string(&self) -> String {
-        self.to_rgba_string()
-    }
     pub fn to_rgb_string(&self) -> String {
         format!("#{:02X}{:02X}{:02X}", self.0.0, self.0.1, self.0.2)
     }
@@ -178,48 +467,30 @@ impl Color {
 }
 
 impl FromStr for Color{
-    type Err = anyhow::Error;
-
-    fn from_str(hex: &str) -> Result<Self, Self::Err> {
-        assert!(hex.starts_with('#'), "Hex color must start with '#'");
-        let (r, g, b, a) = if hex.len() == 7 {
-            (
-                u8::from_str_radix(&hex[1..3], 16).unwrap(),
-                u8::from_str_radix(&hex[3..5], 16).unwrap(),
-                u8::from_str_radix(&hex[5..7], 16).unwrap(),
-                255,
-            )
-        } else if hex.len() == 9 {
-            (
-                u8::from_str_radix(&hex[1..3], 16).unwrap(),
-                u8::from_str_radix(&hex[3..5], 16).unwrap(),
-                u8::from_str_radix(&hex[5..7], 16).unwrap(),
-                u8::from_str_radix(&hex[7..9], 16).unwrap(),
-            )
-        } else {
-            panic!("Invalid hex color length")
-        };
-        
-        Ok(Color((r, g, b, a)))
+    type Err = ProcessError;
+
+    /// 解析#RGB/#RRGGBB/#RRGGBBAA、CSS颜色名称及rgb()/hsl()格式的颜色
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        color::parse_color(value)
     }
-    
 }
 
-impl ToString for Color {
-    fn to_string(&self) -> String {
-        self.to_rgba_string()
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_rgba_string())
     }
-    
 }
 
-impl Into<Rgba<u8>> for Color {
-    fn into(self) -> Rgba<u8> {
-        Rgba([self.0.0, self.0.1, self.0.2, self.0.3])
+impl From<Color> for Rgba<u8> {
+    fn from(color: Color) -> Self {
+        Rgba([color.0.0, color.0.1, color.0.2, color.0.3])
     }
 }
 
-impl Into<Color> for &str {
-    fn into(self) -> Color {
-        Color::from_str(self).unwrap()
+impl TryFrom<&str> for Color {
+    type Error = ProcessError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        Color::from_str(value)
     }
 }
\ No newline at end of file
@@ -1,65 +1,159 @@
 use anyhow::Result;
-use axum::{ http::StatusCode, routing::get_service};
-use logo_process::{api_routes, ActivityMemoryRepo, AppState, ImageDO,  ImageMemoryRepo, ImageRepo};
-use tower_http::services::ServeDir;
-use std::{net::SocketAddr, sync::Arc};
+use axum::{extract::DefaultBodyLimit, http::{HeaderValue, StatusCode}, routing::get_service};
+use clap::Parser;
+use logo_process::{
+    api_routes, ActivityMemoryRepo, AppState, ImageDO, ImageMemoryRepo, ImageRepo, JobManager, LogoCache,
+    RepoBackend, ServerConfig, StrategyRegistry, WeightedLruCache,
+};
+use tower_http::{cors::{AllowOrigin, CorsLayer}, services::ServeDir};
+use tracing_subscriber::EnvFilter;
+use std::{net::SocketAddr, path::{Path, PathBuf}, sync::Arc};
 use tracing::{debug, info};
 
 
+/// logo马赛克服务；命令行参数优先于环境变量，环境变量优先于配置文件
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct ServerArgs {
+    /// TOML配置文件路径
+    #[arg(short, long, env = "LOGO_MOSAIC_CONFIG")]
+    config: Option<PathBuf>,
+    /// 监听地址，如0.0.0.0:8002
+    #[arg(long, env = "LOGO_MOSAIC_BIND")]
+    bind: Option<SocketAddr>,
+    /// logo图片存储目录
+    #[arg(long, env = "LOGO_MOSAIC_IMAGE_DIR")]
+    image_dir: Option<PathBuf>,
+    /// 前端静态资源目录
+    #[arg(long, env = "LOGO_MOSAIC_STATIC_DIR")]
+    static_dir: Option<PathBuf>,
+    /// 日志级别或tracing过滤指令
+    #[arg(long, env = "RUST_LOG")]
+    log_level: Option<String>,
+    /// 允许跨域访问的来源，多个用逗号分隔
+    #[arg(long, env = "LOGO_MOSAIC_CORS_ORIGINS", value_delimiter = ',')]
+    cors_origins: Option<Vec<String>>,
+    /// 数据存储方式
+    #[arg(long, env = "LOGO_MOSAIC_REPO_BACKEND", value_enum)]
+    repo_backend: Option<RepoBackend>,
+    /// 数据存储路径，内存存储不支持设置
+    #[arg(long, env = "LOGO_MOSAIC_REPO_PATH")]
+    repo_path: Option<PathBuf>,
+    /// 请求体的最大字节数
+    #[arg(long, env = "LOGO_MOSAIC_MAX_BODY_BYTES")]
+    max_body_bytes: Option<usize>,
+    /// 同时执行的异步转换任务数
+    #[arg(long, env = "LOGO_MOSAIC_JOB_WORKERS")]
+    job_workers: Option<usize>,
+    /// 最多排队的异步转换任务数
+    #[arg(long, env = "LOGO_MOSAIC_JOB_QUEUE_CAPACITY")]
+    job_queue_capacity: Option<usize>,
+}
+
+impl ServerArgs {
+    /// 读取配置文件，并用命令行参数(或环境变量)覆盖
+    fn into_config(self) -> Result<ServerConfig> {
+        let mut config = match &self.config {
+            Some(path) => ServerConfig::from_file(path)?,
+            None => ServerConfig::default(),
+        };
+        if let Some(v) = self.bind { config.bind = v; }
+        if let Some(v) = self.image_dir { config.image_dir = v; }
+        if let Some(v) = self.static_dir { config.static_dir = v; }
+        if let Some(v) = self.log_level { config.log_level = v; }
+        if let Some(v) = self.cors_origins { config.cors_origins = v; }
+        if let Some(v) = self.repo_backend { config.repo.backend = v; }
+        if let Some(v) = self.repo_path { config.repo.path = Some(v); }
+        if let Some(v) = self.max_body_bytes { config.limits.max_body_bytes = v; }
+        if let Some(v) = self.job_workers { config.limits.job_workers = v; }
+        if let Some(v) = self.job_queue_capacity { config.limits.job_queue_capacity = v; }
+        config.validate()?;
+        Ok(config)
+    }
+}
+
 
 #[tokio::main]
 pub async fn main() -> Result<()> {
-    tracing_subscriber::fmt::init();
-    let addr = SocketAddr::from(([0, 0, 0, 0], 8002));
-    debug!("debug log is enabled");
-    info!("Serving on {}", addr);
+    let config = ServerArgs::parse().into_config()?;
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::try_new(&config.log_level)?)
+        .init();
+    debug!("debug log is enabled, config: {:?}", config);
+    info!("Serving on {}", config.bind);
 
-    let logo_image_dir_path= "images";
     // 加载logo图片
     let image_repo =  ImageMemoryRepo::new();
-    init_builtin_images(&image_repo)?;
+    init_builtin_images(&image_repo, &config.image_dir)?;
 
     let activity_repo =  ActivityMemoryRepo::new();
 
+    let limits = &config.limits;
     let app_state = Arc::new(AppState { 
-        logo_image_dir_path,
-        static_path: "./logo-mosaic-web/dist",
+        logo_image_dir_path: config.image_dir.clone(),
+        static_path: config.static_dir.clone(),
         image_repo,
         activity_repo,
+        strategy_registry: StrategyRegistry::with_builtins(),
+        logo_cache: LogoCache::new(limits.logo_cache_entries, limits.logo_cache_bytes),
+        convert_cache: WeightedLruCache::new(limits.convert_cache_entries, limits.convert_cache_bytes),
+        job_manager: JobManager::new(limits.job_workers, limits.job_queue_capacity),
      });
      
 
     let static_service = get_service(
-        ServeDir::new(app_state.static_path),)
+        ServeDir::new(&app_state.static_path),)
             .handle_error(|_| async move { (StatusCode::INTERNAL_SERVER_ERROR, "internal server error") }
     );
     let image_service = get_service(
-        ServeDir::new(app_state.logo_image_dir_path),)
+        ServeDir::new(&app_state.logo_image_dir_path),)
             .handle_error(|_| async move { (StatusCode::INTERNAL_SERVER_ERROR, "internal server error") }
     );
 
-    let router = axum::Router::new()
+    let mut router = axum::Router::new()
         .nest("/api", api_routes())
         .nest_service("/images", image_service)
         .fallback(static_service)
+        .layer(DefaultBodyLimit::max(limits.max_body_bytes))
         .with_state(app_state);
+    if let Some(cors) = build_cors_layer(&config.cors_origins)? {
+        router = router.layer(cors);
+    }
 
-    let listener = tokio::net::TcpListener::bind(addr).await?;
+    let listener = tokio::net::TcpListener::bind(config.bind).await?;
     axum::serve(listener, router).await?;
     Ok(())
 }
 
 
+/// 根据配置的来源创建跨域中间件；未配置来源时不启用跨域
+fn build_cors_layer(origins: &[String]) -> Result<Option<CorsLayer>> {
+    if origins.is_empty() {
+        return Ok(None);
+    }
+    let allow_origin = if origins.iter().any(|o| o == "*") {
+        AllowOrigin::any()
+    } else {
+        let origins = origins.iter()
+            .map(|o| HeaderValue::from_str(o))
+            .collect::<Result<Vec<_>, _>>()?;
+        AllowOrigin::list(origins)
+    };
+    Ok(Some(CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods(tower_http::cors::Any)
+        .allow_headers(tower_http::cors::Any)))
+}
 
 
-fn init_builtin_images(image_repo: &ImageMemoryRepo) -> Result<()> {
+fn init_builtin_images(image_repo: &ImageMemoryRepo, image_dir: &Path) -> Result<()> {
     image_repo.insert_image(ImageDO{
         id: "1".to_string(),
         width: 968,
         height: 698,
         name: "logo1.png".to_string(),
-        path: "images/logo1.png".to_string(),
+        path: image_dir.join("logo1.png").to_string_lossy().into_owned(),
         bg_color: (255, 255, 255)
     })?;
     Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,175 @@
+use crate::error::Result;
+use image::{imageops, RgbaImage};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use crate::{Color, GridFillOptions, GridPickCmd, GridShape};
+use super::{generate_canvas_grids_from_logo_image, recommend::render_thumbnail};
+
+
+/// 批量转换的参数矩阵：格子形状 × 格子尺寸 × 选取阈值
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchParamMatrix {
+    pub grid_shapes: Vec<GridShape>,
+    pub grid_sizes: Vec<(u32, u32)>,
+    pub pick_strategy: GridPickCmd,
+    // 选取阈值，为空时使用pick_strategy自身的阈值
+    #[serde(default)]
+    pub thresholds: Vec<f32>,
+}
+
+impl BatchParamMatrix {
+    /// 展开为参数组合，依次按形状、尺寸、阈值排列
+    pub fn combinations(&self) -> Vec<(GridFillOptions, GridPickCmd)> {
+        let thresholds = if self.thresholds.is_empty() {
+            vec![self.pick_strategy.pick_threshold()]
+        } else {
+            self.thresholds.clone()
+        };
+        let mut combinations = Vec::with_capacity(self.grid_shapes.len() * self.grid_sizes.len() * thresholds.len());
+        for shape in &self.grid_shapes {
+            for &(width, height) in &self.grid_sizes {
+                let fill_options = GridFillOptions::new(*shape, width, height);
+                for &threshold in &thresholds {
+                    combinations.push((fill_options, self.pick_strategy.with_pick_threshold(threshold)));
+                }
+            }
+        }
+        combinations
+    }
+}
+
+
+/// 参与批量转换的logo
+pub struct BatchImage<'a> {
+    pub name: String,
+    pub image: &'a RgbaImage,
+}
+
+
+/// 联系表(contact sheet)选项
+#[derive(Debug, Clone, Copy)]
+pub struct BatchOptions {
+    // 每个缩略图的宽度
+    pub cell_width: u32,
+    // 缩略图之间的间距
+    pub gap: u32,
+    pub background: Color,
+}
+
+impl Default for BatchOptions {
+    fn default() -> Self {
+        Self { cell_width: 160, gap: 8, background: Color::WHITE }
+    }
+}
+
+
+/// 一个logo在一组参数下的转换结果；row、col为其在联系表中的位置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchResultRow {
+    pub image: String,
+    pub row: usize,
+    pub col: usize,
+    pub grid_shape: GridShape,
+    pub grid_size: (u32, u32),
+    pub pick_threshold: f32,
+    pub grid_count: usize,
+    pub selected_count: usize,
+}
+
+
+pub struct BatchReport {
+    pub rows: Vec<BatchResultRow>,
+    // 每行一个logo，每列一组参数
+    pub contact_sheet: RgbaImage,
+}
+
+
+/// 对每个logo按参数矩阵中的每组参数并行转换，汇总选中格子数并拼接联系表
+pub fn run_batch(images: &[BatchImage], matrix: &BatchParamMatrix, options: &BatchOptions) -> Result<BatchReport> {
+    let combinations = matrix.combinations();
+    let cols = combinations.len();
+    let cells: Vec<(BatchResultRow, RgbaImage)> = (0..images.len() * cols).into_par_iter().map(|i| {
+        let (row, col) = (i / cols, i % cols);
+        let (fill_options, pick_strategy) = &combinations[col];
+        let image = &images[row];
+        let grids = generate_canvas_grids_from_logo_image(image.image, *fill_options, pick_strategy.clone())?;
+        let result = BatchResultRow {
+            image: image.name.clone(),
+            row,
+            col,
+            grid_shape: fill_options.shape(),
+            grid_size: fill_options.grid_size(),
+            pick_threshold: pick_strategy.pick_threshold(),
+            grid_count: grids.len(),
+            selected_count: grids.iter().filter(|g| g.ext.selected.unwrap_or(false)).count(),
+        };
+        Ok((result, render_thumbnail(image.image, grids, options.cell_width)?))
+    }).collect::<Result<_>>()?;
+
+    // 同一logo的缩略图高度相同，各行高度按logo分别计算
+    let row_heights: Vec<u32> = (0..images.len())
+        .map(|row| cells[row * cols..(row + 1) * cols].iter().map(|(_, t)| t.height()).max().unwrap_or(0))
+        .collect();
+    let gap = options.gap;
+    let width = cols as u32 * (options.cell_width + gap) + gap;
+    let height = row_heights.iter().map(|h| h + gap).sum::<u32>() + gap;
+    let mut contact_sheet = RgbaImage::from_pixel(width.max(1), height.max(1), options.background.into());
+    let mut rows = Vec::with_capacity(cells.len());
+    let mut y = gap;
+    for (row, row_cells) in cells.chunks(cols.max(1)).enumerate() {
+        for (col, (result, thumbnail)) in row_cells.iter().enumerate() {
+            let x = gap + col as u32 * (options.cell_width + gap);
+            imageops::overlay(&mut contact_sheet, thumbnail, x as i64, y as i64);
+            rows.push(result.clone());
+        }
+        y += row_heights.get(row).copied().unwrap_or(0) + gap;
+    }
+    Ok(BatchReport { rows, contact_sheet })
+}
+
+
+/// 汇总结果的csv文本
+pub fn batch_summary_csv(rows: &[BatchResultRow]) -> String {
+    let mut csv = String::from("image,row,col,grid_shape,grid_width,grid_height,pick_threshold,grid_count,selected_count\n");
+    for r in rows {
+        // 图片名中的逗号和引号按csv规则转义
+        let image = if r.image.contains([',', '"', '\n']) {
+            format!("\"{}\"", r.image.replace('"', "\"\""))
+        } else {
+            r.image.clone()
+        };
+        csv.push_str(&format!("{},{},{},{},{},{},{},{},{}\n",
+            image, r.row, r.col, String::from(r.grid_shape), r.grid_size.0, r.grid_size.1,
+            r.pick_threshold, r.grid_count, r.selected_count));
+    }
+    csv
+}
+
+
+#[cfg(test)]
+#[test]
+fn test_run_batch_matrix() {
+    use crate::EliminateBgColorParam;
+
+    let logo = RgbaImage::from_fn(40, 20, |x, _| {
+        if x < 20 { image::Rgba([0, 0, 0, 255]) } else { image::Rgba([255, 255, 255, 255]) }
+    });
+    let images = [BatchImage { name: "a".to_string(), image: &logo }, BatchImage { name: "b".to_string(), image: &logo }];
+    let matrix = BatchParamMatrix {
+        grid_shapes: vec![GridShape::Rectangle],
+        grid_sizes: vec![(10, 10), (5, 5)],
+        pick_strategy: GridPickCmd::EliminateBgColor(EliminateBgColorParam { color: Color::WHITE, min_remaining_ratio: 0.5 }),
+        thresholds: vec![0.2, 0.8],
+    };
+    let options = BatchOptions { cell_width: 20, gap: 2, ..Default::default() };
+    let report = run_batch(&images, &matrix, &options).unwrap();
+
+    assert_eq!(report.rows.len(), 8);
+    assert_eq!((report.rows[5].image.as_str(), report.rows[5].col), ("b", 1));
+    assert_eq!(report.rows[0].selected_count, 4);
+    assert_eq!(report.rows[2].selected_count, 16);
+    assert_eq!(report.contact_sheet.dimensions(), (4 * 22 + 2, 2 * 12 + 2));
+    assert_eq!(batch_summary_csv(&report.rows).lines().count(), 9);
+}
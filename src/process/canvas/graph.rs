@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+use crate::Grid;
+
+/// 判断两个顶点重合时允许的误差(像素)，奇数宽度的三角形顶点存在取整误差
+const VERTEX_TOLERANCE: i64 = 1;
+
+
+/// 格子邻接图：记录每个格子共边和共顶点的相邻格子，适用于所有格子形状
+#[derive(Debug, Clone, Default)]
+pub struct GridGraph {
+    seqs: Vec<String>,
+    seq_index: HashMap<String, usize>,
+    // 每个格子的边数
+    sides: Vec<usize>,
+    // 共边的相邻格子下标
+    edge_neighbors: Vec<Vec<usize>>,
+    // 仅共一个顶点的相邻格子下标
+    vertex_neighbors: Vec<Vec<usize>>,
+}
+
+impl GridGraph {
+    /// 根据格子顶点构建邻接图：两个格子有两个及以上顶点重合时视为共边，只有一个顶点重合时视为共顶点
+    pub fn build(grids: &[Grid]) -> Self {
+        let mut vertex_index: HashMap<(i64, i64), Vec<usize>> = HashMap::new();
+        for (i, grid) in grids.iter().enumerate() {
+            for p in &grid.points {
+                vertex_index.entry((p.x as i64, p.y as i64)).or_default().push(i);
+            }
+        }
+
+        let mut edge_neighbors = Vec::with_capacity(grids.len());
+        let mut vertex_neighbors = Vec::with_capacity(grids.len());
+        for (i, grid) in grids.iter().enumerate() {
+            let mut shared: HashMap<usize, usize> = HashMap::new();
+            for p in &grid.points {
+                let mut matched: Vec<usize> = Vec::new();
+                for dx in -VERTEX_TOLERANCE..=VERTEX_TOLERANCE {
+                    for dy in -VERTEX_TOLERANCE..=VERTEX_TOLERANCE {
+                        if let Some(ids) = vertex_index.get(&(p.x as i64 + dx, p.y as i64 + dy)) {
+                            matched.extend(ids.iter().filter(|&&j| j != i));
+                        }
+                    }
+                }
+                matched.sort_unstable();
+                matched.dedup();
+                matched.into_iter().for_each(|j| *shared.entry(j).or_default() += 1);
+            }
+            let mut edges = Vec::new();
+            let mut vertices = Vec::new();
+            for (j, count) in shared {
+                if count >= 2 {
+                    edges.push(j);
+                } else {
+                    vertices.push(j);
+                }
+            }
+            edges.sort_unstable();
+            vertices.sort_unstable();
+            edge_neighbors.push(edges);
+            vertex_neighbors.push(vertices);
+        }
+
+        Self {
+            seqs: grids.iter().map(|g| g.seq.clone()).collect(),
+            seq_index: grids.iter().enumerate().map(|(i, g)| (g.seq.clone(), i)).collect(),
+            sides: grids.iter().map(|g| g.points.len()).collect(),
+            edge_neighbors,
+            vertex_neighbors,
+        }
+    }
+
+    /// 格子数量
+    pub fn len(&self) -> usize {
+        self.seqs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.seqs.is_empty()
+    }
+
+    /// 根据格子序号查找下标
+    pub fn index_of(&self, seq: &str) -> Option<usize> {
+        self.seq_index.get(seq).copied()
+    }
+
+    /// 下标对应的格子序号
+    pub fn seq(&self, index: usize) -> &str {
+        &self.seqs[index]
+    }
+
+    /// 共边的相邻格子下标
+    pub fn edge_neighbors(&self, index: usize) -> &[usize] {
+        &self.edge_neighbors[index]
+    }
+
+    /// 仅共顶点的相邻格子下标
+    pub fn vertex_neighbors(&self, index: usize) -> &[usize] {
+        &self.vertex_neighbors[index]
+    }
+
+    /// 共边的相邻格子序号
+    pub fn edge_neighbor_seqs(&self, seq: &str) -> Option<Vec<&str>> {
+        self.index_of(seq).map(|i| self.edge_neighbors[i].iter().map(|&j| self.seq(j)).collect())
+    }
+
+    /// 仅共顶点的相邻格子序号
+    pub fn vertex_neighbor_seqs(&self, seq: &str) -> Option<Vec<&str>> {
+        self.index_of(seq).map(|i| self.vertex_neighbors[i].iter().map(|&j| self.seq(j)).collect())
+    }
+
+    /// 共边的格子数少于边数时，格子位于铺排区域的边缘
+    pub fn is_boundary(&self, index: usize) -> bool {
+        self.edge_neighbors[index].len() < self.sides[index]
+    }
+}
+
+
+#[cfg(test)]
+#[test]
+fn test_grid_graph_neighbors() {
+    use crate::{generate_enmty_canvas_grids, GridFillOptions};
+
+    let grids = generate_enmty_canvas_grids(30, 30, GridFillOptions::rectangle(10, 10)).unwrap();
+    let graph = GridGraph::build(&grids);
+    assert_eq!(graph.edge_neighbor_seqs("R2C2").unwrap(), vec!["R1C2", "R2C1", "R2C3", "R3C2"]);
+    assert_eq!(graph.vertex_neighbor_seqs("R2C2").unwrap(), vec!["R1C1", "R1C3", "R3C1", "R3C3"]);
+    assert!(graph.is_boundary(graph.index_of("R1C1").unwrap()));
+    assert!(!graph.is_boundary(graph.index_of("R2C2").unwrap()));
+
+    // 奇数宽度的三角形：内部的三角形有3个共边相邻格子
+    let grids = generate_enmty_canvas_grids(200, 80, GridFillOptions::triangle(21, 16)).unwrap();
+    let graph = GridGraph::build(&grids);
+    assert_eq!(graph.edge_neighbor_seqs("R2U3").unwrap().len(), 3);
+    assert_eq!(graph.edge_neighbor_seqs("R3D3").unwrap().len(), 3);
+}
@@ -0,0 +1,254 @@
+use std::{num::NonZeroUsize, sync::{Arc, Mutex, MutexGuard, OnceLock}};
+use crate::error::Result;
+use image::RgbaImage;
+use lru::LruCache;
+use crate::{utils::is_bg_pixel, Color, Grid, GridShape, ProcessError};
+use super::{polygon::for_each_span_in_polygon, reactangle::rectangle_pixel_bounds, sampling_shape, triangle::for_each_span_in_triangle};
+
+
+/// 单次查表区域的最大像素数：每个像素的值不超过255时，区域和不超出u32范围
+const MAX_LOOKUP_PIXELS: usize = (u32::MAX / 255) as usize;
+/// 每张logo最多缓存的前景积分图数，超出时淘汰最久未使用的背景色
+const MAX_FOREGROUND_INTEGRALS: usize = 4;
+
+
+/// 积分图（summed-area table）：任意矩形区域内的像素和只需O(1)次查表。
+/// 前缀和以u32回绕运算存储，只要单次查表区域的和不超出u32范围，相减得到的结果就是准确的
+struct SummedAreaTable<const N: usize> {
+    width: u32,
+    height: u32,
+    // (width+1) * (height+1)，第0行和第0列为0
+    sums: Vec<[u32; N]>,
+}
+
+impl<const N: usize> SummedAreaTable<N> {
+    fn new<F>(width: u32, height: u32, value: F) -> Self
+    where F: Fn(u32, u32) -> [u32; N] {
+        let stride = width as usize + 1;
+        let mut sums = vec![[0u32; N]; stride * (height as usize + 1)];
+        for y in 0..height {
+            let mut row_sum = [0u32; N];
+            for x in 0..width {
+                let v = value(x, y);
+                let i = (y as usize + 1) * stride + x as usize + 1;
+                for c in 0..N {
+                    row_sum[c] = row_sum[c].wrapping_add(v[c]);
+                    sums[i][c] = sums[i - stride][c].wrapping_add(row_sum[c]);
+                }
+            }
+        }
+        Self { width, height, sums }
+    }
+
+    /// 占用的内存(字节)
+    fn memory_bytes(&self) -> usize {
+        self.sums.len() * std::mem::size_of::<[u32; N]>()
+    }
+
+    /// 区域[x0, x1) x [y0, y1)内的像素和，超出图片的部分被裁掉
+    fn rect_sum(&self, x0: u32, y0: u32, x1: u32, y1: u32) -> [u64; N] {
+        let (x0, x1) = (x0.min(self.width) as usize, x1.min(self.width) as usize);
+        let (y0, y1) = (y0.min(self.height) as usize, y1.min(self.height) as usize);
+        let mut result = [0u64; N];
+        if x0 >= x1 || y0 >= y1 {
+            return result;
+        }
+        // 区域较大时按行分段查表，每段的像素和不超出u32范围
+        let band_rows = (MAX_LOOKUP_PIXELS / (x1 - x0)).max(1);
+        let stride = self.width as usize + 1;
+        for top in (y0..y1).step_by(band_rows) {
+            let bottom = (top + band_rows).min(y1);
+            let (a, b) = (self.sums[top * stride + x0], self.sums[top * stride + x1]);
+            let (c, d) = (self.sums[bottom * stride + x0], self.sums[bottom * stride + x1]);
+            for i in 0..N {
+                result[i] += d[i].wrapping_sub(b[i]).wrapping_sub(c[i]).wrapping_add(a[i]) as u64;
+            }
+        }
+        result
+    }
+
+    /// 格子区域内的像素和及像素数：矩形一次查表，三角形、多边形及旋转后的矩形按行扫描，每行一次查表
+    fn grid_sum(&self, grid: &Grid) -> ([u64; N], u64) {
+        let mut total = [0u64; N];
+        let mut pixel_count = 0u64;
+        let mut add_span = |x0: u32, y0: u32, x1: u32, y1: u32| {
+            let sum = self.rect_sum(x0, y0, x1, y1);
+            for c in 0..N {
+                total[c] += sum[c];
+            }
+            pixel_count += (x1.min(self.width).saturating_sub(x0) as u64)
+                * (y1.min(self.height).saturating_sub(y0) as u64);
+        };
+        let points = &grid.points;
+        match sampling_shape(grid) {
+            Ok(GridShape::Rectangle) => {
+                let (min_x, min_y, max_x, max_y) = rectangle_pixel_bounds(points);
+                add_span(min_x, min_y, max_x, max_y);
+            },
+            Ok(GridShape::Triangle) if points.len() == 3 => {
+                let triangle = [(points[0].x, points[0].y), (points[1].x, points[1].y), (points[2].x, points[2].y)];
+                for_each_span_in_triangle(triangle, |y, x_start, x_end| add_span(x_start, y, x_end, y + 1));
+            },
+            Ok(GridShape::Polygon) => {
+                for_each_span_in_polygon(points, |y, x_start, x_end| add_span(x_start, y, x_end, y + 1));
+            },
+            _ => {},
+        }
+        (total, pixel_count)
+    }
+}
+
+
+/// 各通道的积分图，用于O(1)计算格子的平均色值
+pub struct ColorIntegral(SummedAreaTable<4>);
+
+impl ColorIntegral {
+    pub fn new(img: &RgbaImage) -> Self {
+        Self(SummedAreaTable::new(img.width(), img.height(), |x, y| {
+            img.get_pixel(x, y).0.map(|v| v as u32)
+        }))
+    }
+
+    /// 占用的内存(字节)
+    pub fn memory_bytes(&self) -> usize {
+        self.0.memory_bytes()
+    }
+
+    /// 格子的平均色值，与calc_avg_color_of_grid的结果一致
+    pub fn avg_color_of_grid(&self, grid: &Grid) -> Result<Color> {
+        let (total, pixel_count) = self.0.grid_sum(grid);
+        if pixel_count == 0 {
+            return Err(ProcessError::EmptyGrid(grid.seq.clone()));
+        }
+        let avg = total.map(|v| (v as f32 / pixel_count as f32) as u8);
+        Ok(Color::from_rgba((avg[0], avg[1], avg[2], avg[3])))
+    }
+}
+
+
+/// 非背景像素的积分图，用于O(1)计算格子的前景像素占比
+pub struct ForegroundIntegral(SummedAreaTable<1>);
+
+impl ForegroundIntegral {
+    pub fn new(img: &RgbaImage, bg_color: Color) -> Self {
+        let bg = bg_color.to_rgb();
+        Self(SummedAreaTable::new(img.width(), img.height(), |x, y| {
+            let p = img.get_pixel(x, y);
+            [!is_bg_pixel((p[0], p[1], p[2]), bg) as u32]
+        }))
+    }
+
+    /// 占用的内存(字节)
+    pub fn memory_bytes(&self) -> usize {
+        self.0.memory_bytes()
+    }
+
+    /// 格子内剔除背景色后的像素占比，与calc_remaining_area_ratio_in_grid的结果一致
+    pub fn remaining_area_ratio_of_grid(&self, grid: &Grid) -> Result<f32> {
+        let ([remaining_count], pixel_count) = self.0.grid_sum(grid);
+        if pixel_count == 0 {
+            return Err(ProcessError::EmptyGrid(grid.seq.clone()));
+        }
+        Ok(remaining_count as f32 / pixel_count as f32)
+    }
+}
+
+
+/// 一张logo的积分图缓存：首次使用时计算，前景积分图按背景色分别缓存，只保留最近使用的几种背景色。
+/// 同一张logo多次选取（如调整阈值、搜索格子尺寸）时可复用
+pub struct LogoIntegrals {
+    color: OnceLock<Arc<ColorIntegral>>,
+    foreground: Mutex<LruCache<(u8, u8, u8), Arc<ForegroundIntegral>>>,
+}
+
+impl Default for LogoIntegrals {
+    fn default() -> Self {
+        let capacity = NonZeroUsize::new(MAX_FOREGROUND_INTEGRALS).unwrap_or(NonZeroUsize::MIN);
+        Self { color: OnceLock::new(), foreground: Mutex::new(LruCache::new(capacity)) }
+    }
+}
+
+impl LogoIntegrals {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// img必须始终是同一张logo
+    pub fn color(&self, img: &RgbaImage) -> Arc<ColorIntegral> {
+        self.color.get_or_init(|| Arc::new(ColorIntegral::new(img))).clone()
+    }
+
+    /// img必须始终是同一张logo
+    pub fn foreground(&self, img: &RgbaImage, bg_color: Color) -> Arc<ForegroundIntegral> {
+        let key = bg_color.to_rgb();
+        if let Some(integral) = self.lock_foreground().get(&key) {
+            return integral.clone();
+        }
+        // 在锁外计算，同时计算同一背景色时保留先写入的结果
+        let integral = Arc::new(ForegroundIntegral::new(img, bg_color));
+        self.lock_foreground().get_or_insert(key, || integral).clone()
+    }
+
+    /// 已计算的积分图占用的内存(字节)
+    pub fn memory_bytes(&self) -> usize {
+        let color = self.color.get().map_or(0, |c| c.memory_bytes());
+        color + self.lock_foreground().iter().map(|(_, f)| f.memory_bytes()).sum::<usize>()
+    }
+
+    fn lock_foreground(&self) -> MutexGuard<'_, LruCache<(u8, u8, u8), Arc<ForegroundIntegral>>> {
+        // 缓存中只有可重新计算的数据，锁中毒时继续使用
+        self.foreground.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+
+#[cfg(test)]
+#[test]
+fn test_integral_matches_pixel_scan() {
+    use crate::{generate_enmty_canvas_grids, GridFillOptions};
+    use super::{calc_avg_color_of_grid, calc_remaining_area_ratio_in_grid};
+
+    let img = RgbaImage::from_fn(97, 61, |x, y| {
+        if (x * 7 + y * 13) % 5 == 0 {
+            image::Rgba([255, 255, 255, 255])
+        } else {
+            image::Rgba([(x * 3) as u8, (y * 4) as u8, ((x + y) * 2) as u8, 200])
+        }
+    });
+    let integrals = LogoIntegrals::new();
+    let bg = Color::WHITE;
+    for fill_options in [GridFillOptions::rectangle(9, 7), GridFillOptions::triangle(11, 8)] {
+        for grid in generate_enmty_canvas_grids(97, 61, fill_options).unwrap() {
+            assert_eq!(
+                integrals.color(&img).avg_color_of_grid(&grid).unwrap().to_rgba(),
+                calc_avg_color_of_grid(&img, &grid).unwrap().to_rgba());
+            assert_eq!(
+                integrals.foreground(&img, bg).remaining_area_ratio_of_grid(&grid).unwrap(),
+                calc_remaining_area_ratio_in_grid(&img, &grid, bg).unwrap());
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_integral_sum_of_large_region() {
+    // 4200x4100的区域和为255 * 17220000，超出u32范围
+    let table = SummedAreaTable::<1>::new(4200, 4100, |_, _| [255]);
+    assert_eq!(table.rect_sum(0, 0, 4200, 4100), [255 * 4200 * 4100]);
+    assert_eq!(table.rect_sum(100, 50, 4200, 4100), [255 * 4100 * 4050]);
+}
+
+#[cfg(test)]
+#[test]
+fn test_foreground_integrals_are_bounded() {
+    let img = RgbaImage::from_pixel(30, 20, image::Rgba([0, 0, 0, 255]));
+    let integrals = LogoIntegrals::new();
+    assert_eq!(integrals.memory_bytes(), 0);
+    integrals.color(&img);
+    assert_eq!(integrals.memory_bytes(), 31 * 21 * 16);
+    // 轮换背景色时只保留最近使用的几张前景积分图
+    for v in 0..10u8 {
+        integrals.foreground(&img, Color::from_rgb((v * 20, 255, 255)));
+    }
+    assert_eq!(integrals.memory_bytes(), 31 * 21 * (16 + 4 * MAX_FOREGROUND_INTEGRALS));
+}
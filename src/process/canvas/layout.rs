@@ -0,0 +1,314 @@
+use std::ops::Range;
+use crate::{Grid, GridAlign, GridFillOptions, GridShape, Point, Polygon, Rect};
+use super::{for_each_span_in_grid, reactangle::is_axis_aligned_rectangle};
+
+
+/// 铺排网格在一个方向上的格子边界：第j个半格边界位于 origin + floor(j * num / (2 * den))，
+/// 格子尺寸 num / den 在拉伸对齐时可以是分数，此时各格子尺寸相差不超过1像素
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Axis {
+    origin: i64,
+    num: i64,
+    den: i64,
+    // 格子数，裁剪模式下包含边缘不完整的格子
+    cells: i64,
+    // 铺排区域的起止坐标
+    start: i64,
+    end: i64,
+}
+
+impl Axis {
+    fn new(margin_before: u32, content: u32, size: u32, align: GridAlign) -> Self {
+        let (start, content, size) = (margin_before as i64, content as i64, size as i64);
+        let (origin, num, den, cells, end) = match align {
+            GridAlign::Start => (start, size, 1, content / size, start + content),
+            GridAlign::Center => {
+                let cells = content / size;
+                let origin = start + (content - cells * size) / 2;
+                (origin, size, 1, cells, origin + cells * size)
+            },
+            GridAlign::Stretch => {
+                let cells = if content == 0 { 0 } else { ((content as f64 / size as f64).round() as i64).max(1) };
+                (start, content, cells.max(1), cells, start + content)
+            },
+            GridAlign::Clip => {
+                // 超出铺排区域的部分平均分到两侧
+                let cells = (content + size - 1) / size;
+                (start - (cells * size - content) / 2, size, 1, cells, start + content)
+            },
+        };
+        Self { origin, num, den, cells, start, end }
+    }
+
+    /// 第j个半格边界的坐标
+    fn half(&self, j: i64) -> i64 {
+        self.origin + (j * self.num).div_euclid(2 * self.den)
+    }
+
+    /// 第i个格子边界的坐标
+    fn at(&self, i: i64) -> i64 {
+        self.half(2 * i)
+    }
+}
+
+
+/// 旋转或平移后的网格：格子先在以原点为(0, 0)的网格坐标系中按整格排列，再绕原点旋转到画布上
+#[derive(Debug, Clone)]
+struct Transform {
+    origin: Point,
+    rotation: f32,
+    // 格子宽高
+    width: f32,
+    height: f32,
+    // 覆盖铺排区域的格子行列范围
+    cols: Range<i64>,
+    rows: Range<i64>,
+}
+
+impl Transform {
+    fn new(options: &GridFillOptions, content: &Rect) -> Self {
+        let layout = options.layout;
+        let origin = layout.origin.unwrap_or(match layout.align {
+            GridAlign::Center => Point::new(content.x + content.width / 2.0, content.y + content.height / 2.0),
+            _ => Point::new(content.x, content.y),
+        });
+        let (width, height) = (options.width as f32, options.height as f32);
+        // 铺排区域的四个角在网格坐标系中的位置，确定需要生成的格子范围
+        let corners = [
+            Point::new(content.x, content.y),
+            Point::new(content.max_x(), content.y),
+            Point::new(content.max_x(), content.max_y()),
+            Point::new(content.x, content.max_y()),
+        ].map(|p| p.rotate(-layout.rotation, origin));
+        let range = |value: fn(&Point) -> f32, offset: f32, size: f32| {
+            let (min, max) = corners.iter()
+                .map(|p| (value(p) - offset) / size)
+                .fold((f32::MAX, f32::MIN), |(min, max), v| (min.min(v), max.max(v)));
+            min.floor() as i64 - 1..max.ceil() as i64 + 1
+        };
+        Self {
+            origin,
+            rotation: layout.rotation,
+            width,
+            height,
+            cols: range(|p| p.x, origin.x, width),
+            rows: range(|p| p.y, origin.y, height),
+        }
+    }
+
+    /// 网格坐标系中第j个半格、第i行的边界点在画布上的位置
+    fn point(&self, j: i64, i: i64) -> Point {
+        let local = Point::new(self.origin.x + j as f32 * self.width / 2.0, self.origin.y + i as f32 * self.height);
+        local.rotate(self.rotation, self.origin)
+    }
+}
+
+
+/// 画布上的铺排网格：留白后的区域内，按对齐方式确定格子的边界；
+/// 旋转或平移网格时，格子顶点按浮点坐标计算
+#[derive(Debug, Clone)]
+pub(super) struct Lattice {
+    x: Axis,
+    y: Axis,
+    transform: Option<Transform>,
+    // 铺排区域
+    content: Rect,
+    // 边缘格子是否按铺排区域裁剪
+    clip: bool,
+}
+
+impl Lattice {
+    pub fn new(canvas_width: u32, canvas_height: u32, options: &GridFillOptions) -> Self {
+        let layout = options.layout;
+        let (content_width, content_height) = layout.margin.content_size(canvas_width, canvas_height);
+        let content = Rect::new(layout.margin.left as f32, layout.margin.top as f32, content_width as f32, content_height as f32);
+        Self {
+            x: Axis::new(layout.margin.left, content_width, options.width, layout.align),
+            y: Axis::new(layout.margin.top, content_height, options.height, layout.align),
+            transform: layout.is_transformed().then(|| Transform::new(options, &content)),
+            content,
+            clip: layout.align == GridAlign::Clip,
+        }
+    }
+
+    /// 格子的行范围
+    pub fn rows(&self) -> Range<i64> {
+        match &self.transform {
+            Some(t) => t.rows.clone(),
+            None => 0..self.y.cells,
+        }
+    }
+
+    /// 矩形格子的列范围
+    pub fn cols(&self) -> Range<i64> {
+        match &self.transform {
+            Some(t) => t.cols.clone(),
+            None => 0..self.x.cells,
+        }
+    }
+
+    /// 一行三角形左顶点所在的半格下标，first_half为0或1，同一行的三角形相隔两个半格
+    pub fn triangle_columns(&self, first_half: i64) -> Vec<i64> {
+        if let Some(t) = &self.transform {
+            return (t.cols.start * 2 - 2 + first_half..t.cols.end * 2 + 2).step_by(2).collect();
+        }
+        // 裁剪模式下从区域左侧露出一部分的三角形开始
+        let mut i = if self.clip { first_half - 2 } else { first_half };
+        let mut columns = Vec::new();
+        loop {
+            let (left, right) = (self.x.half(i), self.x.half(i + 2));
+            let fits = if self.clip { left < self.x.end } else { right <= self.x.end };
+            if !fits {
+                break;
+            }
+            if right > self.x.start {
+                columns.push(i);
+            }
+            i += 2;
+        }
+        columns
+    }
+
+    /// 三角形的三个顶点：底边的两个顶点在base_row行的第j和第j+2个半格边界上，顶点在apex_row行两者的中点
+    pub fn triangle_points(&self, j: i64, base_row: i64, apex_row: i64) -> Vec<Point> {
+        if let Some(t) = &self.transform {
+            return vec![t.point(j, base_row), t.point(j + 2, base_row), t.point(j + 1, apex_row)];
+        }
+        let (left, right) = (self.x.half(j), self.x.half(j + 2));
+        let (base_y, apex_y) = (self.y.at(base_row), self.y.at(apex_row));
+        [(left, base_y), (right, base_y), ((left + right).div_euclid(2), apex_y)]
+            .map(|(x, y)| Point::new(x as f32, y as f32))
+            .to_vec()
+    }
+
+    /// 矩形的四个顶点，按顺时针排列
+    pub fn rectangle_points(&self, col: i64, row: i64) -> Vec<Point> {
+        let corners = [(col, row), (col + 1, row), (col + 1, row + 1), (col, row + 1)];
+        match &self.transform {
+            Some(t) => corners.map(|(c, r)| t.point(c * 2, r)).to_vec(),
+            None => corners.map(|(c, r)| Point::new(self.x.at(c) as f32, self.y.at(r) as f32)).to_vec(),
+        }
+    }
+
+    /// 生成格子：完全在铺排区域内的格子原样保留，裁剪模式下超出的部分被裁掉，没有覆盖任何像素的格子被丢弃
+    pub fn make_grid(&self, seq: String, shape: GridShape, points: Vec<Point>) -> Option<Grid> {
+        let polygon = Polygon(points);
+        let bbox = polygon.bbox();
+        // 旋转后的顶点有浮点误差
+        let inside = bbox.x >= self.content.x - EPSILON && bbox.y >= self.content.y - EPSILON
+            && bbox.max_x() <= self.content.max_x() + EPSILON && bbox.max_y() <= self.content.max_y() + EPSILON;
+        let grid = if inside {
+            Grid { seq, shape, points: polygon.0, ext: Default::default() }
+        } else if self.clip {
+            let mut clipped = polygon.clip_to_rect(&self.content).0;
+            clipped.dedup_by(|a, b| is_same_point(*a, *b));
+            if clipped.len() > 1 && is_same_point(clipped[0], clipped[clipped.len() - 1]) {
+                clipped.pop();
+            }
+            // 矩形裁剪后仍是矩形(未旋转时)，三角形裁剪后可能变成四边形或五边形
+            let shape = match (shape, clipped.len()) {
+                (GridShape::Triangle, 3) => shape,
+                (GridShape::Rectangle, 4) if is_axis_aligned_rectangle(&clipped) => shape,
+                _ => GridShape::Polygon,
+            };
+            Grid { seq, shape, points: clipped, ext: Default::default() }
+        } else {
+            return None;
+        };
+        if grid.points.len() < 3 || grid.area() < EPSILON {
+            return None;
+        }
+        // 未旋转时完整的格子总能覆盖像素，不再检查，保持原有的结果
+        if (self.transform.is_some() || self.clip) && !covers_any_pixel(&grid) {
+            return None;
+        }
+        Some(grid)
+    }
+}
+
+const EPSILON: f32 = 1e-3;
+
+fn is_same_point(a: Point, b: Point) -> bool {
+    (a.x - b.x).abs() < EPSILON && (a.y - b.y).abs() < EPSILON
+}
+
+fn covers_any_pixel(grid: &Grid) -> bool {
+    let mut covered = false;
+    let _ = for_each_span_in_grid(grid, |_, _, _| covered = true);
+    covered
+}
+
+#[cfg(test)]
+#[test]
+fn test_layout_margins_and_alignment() {
+    use crate::{generate_enmty_canvas_grids, GridLayout, Margins};
+
+    let layout = |margin: u32, align: GridAlign| GridLayout { margin: Margins::uniform(margin), align, ..Default::default() };
+    let bounds = |grids: &[Grid]| grids.iter().map(Grid::bbox).reduce(|a, b| Rect::from_corners(
+        Point::new(a.x.min(b.x), a.y.min(b.y)),
+        Point::new(a.max_x().max(b.max_x()), a.max_y().max(b.max_y())),
+    )).unwrap();
+
+    // 居中：105x65的区域放10x10的矩形，剩余的5像素平均分到两侧
+    let options = GridFillOptions::rectangle(10, 10).with_layout(layout(5, GridAlign::Center));
+    let grids = generate_enmty_canvas_grids(115, 75, options).unwrap();
+    assert_eq!(grids.len(), 60);
+    assert_eq!(bounds(&grids), Rect::new(7.0, 7.0, 100.0, 60.0));
+
+    // 拉伸：格子恰好铺满留白后的区域
+    let options = GridFillOptions::rectangle(10, 10).with_layout(layout(5, GridAlign::Stretch));
+    let grids = generate_enmty_canvas_grids(115, 75, options).unwrap();
+    assert_eq!(grids.len(), 11 * 7);
+    assert_eq!(bounds(&grids), Rect::new(5.0, 5.0, 105.0, 65.0));
+    let area: f32 = grids.iter().map(Grid::area).sum();
+    assert_eq!(area, 105.0 * 65.0);
+
+    // 裁剪：三角形铺满整个区域，边缘的格子被裁剪
+    let options = GridFillOptions::triangle(21, 16).with_layout(layout(3, GridAlign::Clip));
+    let grids = generate_enmty_canvas_grids(206, 86, options).unwrap();
+    assert_eq!(bounds(&grids), Rect::new(3.0, 3.0, 200.0, 80.0));
+    let area: f32 = grids.iter().map(Grid::area).sum();
+    assert!((area - 200.0 * 80.0).abs() < 200.0, "area: {}", area);
+    assert!(grids.iter().any(|g| g.shape == GridShape::Polygon));
+    assert!(grids.iter().all(|g| g.points.len() >= 3));
+
+    // 旋转45度的菱形：铺满并裁剪到区域内，顶点为小数
+    let diamond = GridLayout { align: GridAlign::Clip, rotation: 45.0, origin: Some(Point::new(50.0, 40.0)), ..Default::default() };
+    let grids = generate_enmty_canvas_grids(100, 80, GridFillOptions::rectangle(14, 14).with_layout(diamond)).unwrap();
+    assert_eq!(bounds(&grids), Rect::new(0.0, 0.0, 100.0, 80.0));
+    let area: f32 = grids.iter().map(Grid::area).sum();
+    // 没有覆盖像素的边角碎片被丢弃
+    assert!((area - 100.0 * 80.0).abs() < 10.0, "area: {}", area);
+    let full = grids.iter().find(|g| g.shape == GridShape::Rectangle).unwrap();
+    assert!((full.area() - 196.0).abs() < 1e-2);
+    assert!(full.points.iter().any(|p| p.x.fract() != 0.0));
+
+    // 不裁剪时只保留完整的格子
+    let rotated = GridLayout { rotation: 30.0, ..Default::default() };
+    let grids = generate_enmty_canvas_grids(100, 80, GridFillOptions::triangle(12, 10).with_layout(rotated)).unwrap();
+    assert!(!grids.is_empty());
+    assert!(grids.iter().all(|g| g.shape == GridShape::Triangle && (g.area() - 60.0).abs() < 1e-2));
+    let content = Rect::new(-1e-3, -1e-3, 100.002, 80.002);
+    assert!(grids.iter().all(|g| g.points.iter().all(|&p| content.contains(p))));
+
+    // 拉伸对齐不能与旋转同时使用
+    let stretched = GridLayout { align: GridAlign::Stretch, ..rotated };
+    let err = generate_enmty_canvas_grids(100, 80, GridFillOptions::triangle(12, 10).with_layout(stretched)).unwrap_err();
+    assert_eq!(err.field(), Some("gridLayout.align"));
+}
+
+
+#[cfg(test)]
+#[test]
+fn test_stretch_without_content_area() {
+    use crate::{generate_enmty_canvas_grids, GridLayout, Margins};
+
+    // 左右留白占满画布宽度，拉伸对齐的格子尺寸为0
+    let layout = GridLayout { margin: Margins { top: 0, right: 50, bottom: 0, left: 50 }, align: GridAlign::Stretch, ..Default::default() };
+    for options in [GridFillOptions::triangle(10, 8), GridFillOptions::rectangle(10, 10)] {
+        let options = options.with_layout(layout);
+        assert_eq!(options.grid_count(100, 80), 0);
+        let err = generate_enmty_canvas_grids(100, 80, options).unwrap_err();
+        assert_eq!(err.field(), Some("gridLayout.margin"));
+    }
+}
@@ -1,41 +1,122 @@
 mod triangle;
 mod reactangle;
+mod polygon;
+mod layout;
+mod graph;
+mod integral;
 
-use image::RgbaImage;
-use anyhow::Result;
+use image::{Rgba, RgbaImage};
+use crate::error::Result;
 use self::triangle::{
     calc_average_color_in_triangle, 
     calc_remaining_area_ratio_in_triangle, 
+    for_each_pixel_in_triangle,
+    for_each_span_in_triangle,
     genarate_canvas_grids_filled_with_trianles
 };
-use self::reactangle::{calc_average_color_in_rectangle, calc_remaining_area_ratio_in_rectangle, genarate_canvas_grids_filled_with_rectanles};
-use crate::{Color, Grid, GridFillOptions, GridShape};
+use self::reactangle::{is_axis_aligned_rectangle, rectangle_pixel_bounds, calc_average_color_in_rectangle, calc_remaining_area_ratio_in_rectangle, for_each_pixel_in_rectangle, genarate_canvas_grids_filled_with_rectanles};
+use self::polygon::{calc_average_color_in_polygon, calc_remaining_area_ratio_in_polygon, for_each_pixel_in_polygon, for_each_span_in_polygon};
+use self::layout::Lattice;
+use crate::{Color, Grid, GridAlign, GridFillOptions, GridShape, Point, ProcessError};
 
-/// 生成空画布格子信息(格子形状支持：三角形，矩形)
+pub use graph::GridGraph;
+pub use integral::{ColorIntegral, ForegroundIntegral, LogoIntegrals};
+
+/// 格子宽高上限(像素)
+pub const MAX_GRID_SIDE: u32 = 1024;
+/// 一张画布上格子数量的上限
+pub const MAX_GRID_COUNT: u64 = 100_000;
+
+/// 检查格子能否铺在画布上：格子尺寸、旋转和平移、形状、留白及格子总数；
+/// 接口和命令行在生成格子前调用，避免生成过多的格子
+pub fn check_canvas_grids(canvas_width: u32, canvas_height: u32, options: &GridFillOptions) -> Result<()> {
+    let (w, h) = options.grid_size();
+    if !(1..=MAX_GRID_SIDE).contains(&w) || !(1..=MAX_GRID_SIDE).contains(&h) {
+        return Err(ProcessError::invalid_parameter_with_code("gridSize", "range",
+            format!("grid size {}x{} must be between 1 and {}", w, h, MAX_GRID_SIDE)));
+    }
+    let layout = options.layout;
+    if !layout.rotation.is_finite() || layout.origin.is_some_and(|p| !(p.x.is_finite() && p.y.is_finite())) {
+        return Err(ProcessError::invalid_parameter_with_code("gridLayout", "invalid_transform", "rotation and origin must be finite numbers"));
+    }
+    if layout.align == GridAlign::Stretch && layout.is_transformed() {
+        return Err(ProcessError::invalid_parameter_with_code("gridLayout.align", "unsupported_align", "stretch can not be combined with rotation or origin"));
+    }
+    if options.shape().vertex_count().is_none() {
+        return Err(ProcessError::invalid_parameter_with_code("gridShape", "unsupported_shape",
+            format!("{:?} grids can not be used to fill a canvas", options.shape())));
+    }
+    let (content_width, content_height) = layout.margin.content_size(canvas_width, canvas_height);
+    if content_width == 0 || content_height == 0 {
+        return Err(ProcessError::invalid_parameter_with_code("gridLayout.margin", "margin_too_large",
+            format!("margins leave no room on canvas {}x{}", canvas_width, canvas_height)));
+    }
+    // 裁剪模式下边缘的格子可以超出铺排区域
+    if layout.align != GridAlign::Clip && (w > content_width || h > content_height) {
+        return Err(ProcessError::invalid_parameter_with_code("gridSize", "grid_too_large",
+            format!("grid size {}x{} does not fit area {}x{}", w, h, content_width, content_height)));
+    }
+    let count = options.grid_count(canvas_width, canvas_height);
+    if count > MAX_GRID_COUNT {
+        return Err(ProcessError::invalid_parameter_with_code("gridSize", "too_many_grids",
+            format!("{} grids exceed the limit of {}", count, MAX_GRID_COUNT)));
+    }
+    Ok(())
+}
+
+/// 生成空画布格子信息(格子形状支持：三角形，矩形)；
+/// 格子在去掉留白的区域内按对齐方式铺排，裁剪模式下边缘的格子可能被裁成多边形
 pub fn generate_enmty_canvas_grids(
     canvas_width: u32, canvas_height: u32, options: GridFillOptions) -> Result<Vec<Grid>> {
-    match options {
-        GridFillOptions::Triangle(w, h) => {
-            genarate_canvas_grids_filled_with_trianles(canvas_width, canvas_height, w, h)
-        }
-        GridFillOptions::Rectangle(w, h) => {
-            genarate_canvas_grids_filled_with_rectanles(canvas_width, canvas_height, w, h)
-        },
+    let (width, height) = options.grid_size();
+    if width == 0 || height == 0 {
+        return Err(ProcessError::invalid_parameter("gridSize", format!("grid size {}x{} must be positive", width, height)));
+    }
+    if options.layout.align == GridAlign::Stretch && options.layout.is_transformed() {
+        return Err(ProcessError::invalid_parameter("gridLayout.align", "stretch can not be combined with rotation or origin"));
+    }
+    // 没有铺排区域时无法确定格子边界（拉伸对齐时格子尺寸为0）
+    let (content_width, content_height) = options.layout.margin.content_size(canvas_width, canvas_height);
+    if content_width == 0 || content_height == 0 {
+        return Err(ProcessError::invalid_parameter("gridLayout.margin",
+            format!("margins leave no room on canvas {}x{}", canvas_width, canvas_height)));
+    }
+    let lattice = Lattice::new(canvas_width, canvas_height, &options);
+    let capacity = options.grid_count(canvas_width, canvas_height) as usize;
+    match options.shape {
+        GridShape::Triangle => Ok(genarate_canvas_grids_filled_with_trianles(&lattice, capacity)),
+        GridShape::Rectangle => Ok(genarate_canvas_grids_filled_with_rectanles(&lattice, capacity)),
+        GridShape::Polygon => Err(ProcessError::invalid_parameter("gridShape", "polygon grids can not be used to fill a canvas")),
+    }
+}
+
+/// 生成空画布格子信息，同时构建格子邻接图
+pub fn generate_enmty_canvas_grids_with_graph(
+    canvas_width: u32, canvas_height: u32, options: GridFillOptions) -> Result<(Vec<Grid>, GridGraph)> {
+    let grids = generate_enmty_canvas_grids(canvas_width, canvas_height, options)?;
+    let graph = GridGraph::build(&grids);
+    Ok((grids, graph))
+}
+
+/// 遍历格子区域内的所有像素点，回调参数为像素坐标及像素值；超出图片的部分被裁掉
+pub fn for_each_pixel_in_grid<F>(img: &RgbaImage, grid: &Grid, f: F) -> Result<()>
+where F: FnMut(u32, u32, &Rgba<u8>) {
+    match sampling_shape(grid)? {
+        GridShape::Triangle => for_each_pixel_in_triangle(img, triangle_vertices(grid)?, f),
+        GridShape::Rectangle => for_each_pixel_in_rectangle(img, &grid.points, f),
+        GridShape::Polygon => for_each_pixel_in_polygon(img, &grid.points, f),
     }
+    Ok(())
 }
 
 /// 计算格子的平均色值
 pub fn calc_avg_color_of_grid(img: &RgbaImage, grid: &Grid) -> Result<Color>{
-    match grid.shape {
-        GridShape::Triangle => {
-            let points = &grid.points;
-            calc_average_color_in_triangle(img, points)
-        },
-        GridShape::Rectangle => {
-            let points = &grid.points;
-            calc_average_color_in_rectangle(img, points)
-        }
-    }
+    let color = match sampling_shape(grid)? {
+        GridShape::Triangle => calc_average_color_in_triangle(img, triangle_vertices(grid)?),
+        GridShape::Rectangle => calc_average_color_in_rectangle(img, &grid.points),
+        GridShape::Polygon => calc_average_color_in_polygon(img, &grid.points),
+    };
+    color.ok_or_else(|| ProcessError::EmptyGrid(grid.seq.clone()))
 }
 
 /// 计算格子内像素剔除指定颜色后的像素占比
@@ -43,23 +124,58 @@ pub fn calc_remaining_area_ratio_in_grid(
     img: &RgbaImage, 
     grid: &Grid,
     bg_color: Color) -> Result<f32>{
-    match grid.shape {
-            GridShape::Triangle => {
-                let points = &grid.points;
-                assert_eq!(points.len(), 3, "Each triangle should have 3 points.");
-                let triangle:[(u32, u32); 3] = [
-                    (points[0].x, points[0].y),
-                    (points[1].x, points[1].y),
-                    (points[2].x, points[2].y),
-                ];
-                // 计算剔除背景色后的剩余区域占比
-                calc_remaining_area_ratio_in_triangle(img, bg_color, triangle)
-            },
-            GridShape::Rectangle => {
-                let points = &grid.points;
-                calc_remaining_area_ratio_in_rectangle(img, bg_color, points)
+    // 计算剔除背景色后的剩余区域占比
+    let ratio = match sampling_shape(grid)? {
+        GridShape::Triangle => calc_remaining_area_ratio_in_triangle(img, bg_color, triangle_vertices(grid)?),
+        GridShape::Rectangle => calc_remaining_area_ratio_in_rectangle(img, bg_color, &grid.points),
+        GridShape::Polygon => calc_remaining_area_ratio_in_polygon(img, bg_color, &grid.points),
+    };
+    ratio.ok_or_else(|| ProcessError::EmptyGrid(grid.seq.clone()))
+}
+
+
+/// 按行遍历格子覆盖的像素，回调参数为行号及该行像素区间[x_start, x_end)
+fn for_each_span_in_grid<F>(grid: &Grid, mut f: F) -> Result<()>
+where F: FnMut(u32, u32, u32) {
+    match sampling_shape(grid)? {
+        GridShape::Triangle => for_each_span_in_triangle(triangle_vertices(grid)?, f),
+        GridShape::Rectangle => {
+            let (min_x, min_y, max_x, max_y) = rectangle_pixel_bounds(&grid.points);
+            if min_x < max_x {
+                (min_y..max_y).for_each(|y| f(y, min_x, max_x));
             }
+        },
+        GridShape::Polygon => for_each_span_in_polygon(&grid.points, f),
+    }
+    Ok(())
+}
+
+/// 采样时使用的形状：旋转后的矩形按多边形采样
+fn sampling_shape(grid: &Grid) -> Result<GridShape> {
+    match grid.shape {
+        GridShape::Rectangle if !is_axis_aligned_rectangle(rectangle_points(grid)?) => Ok(GridShape::Polygon),
+        shape => Ok(shape),
+    }
+}
+
+/// 三角形格子的三个顶点
+fn triangle_vertices(grid: &Grid) -> Result<[(f32, f32); 3]> {
+    match grid.points.as_slice() {
+        [a, b, c] => Ok([(a.x, a.y), (b.x, b.y), (c.x, c.y)]),
+        points => Err(ProcessError::InvalidGrid {
+            seq: grid.seq.clone(),
+            message: format!("a triangle should have 3 points, got {}", points.len()),
+        }),
+    }
+}
 
+/// 矩形格子的四个顶点
+fn rectangle_points(grid: &Grid) -> Result<&[Point]> {
+    if grid.points.len() != 4 {
+        return Err(ProcessError::InvalidGrid {
+            seq: grid.seq.clone(),
+            message: format!("a rectangle should have 4 points, got {}", grid.points.len()),
+        });
     }
-    
+    Ok(&grid.points)
 }
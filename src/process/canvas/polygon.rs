@@ -0,0 +1,86 @@
+use image::{Rgba, RgbaImage};
+use crate::{utils::is_bg_pixel, Color, Point};
+
+
+/// 按行遍历多边形区域，回调参数为行号及该行像素区间[x_start, x_end)；
+/// 像素坐标点落在多边形内(奇偶规则，左、上边界闭合，右、下边界开放)的像素属于该多边形，
+/// 与顶点的排列方向无关；与坐标轴平行的矩形的结果与for_each_pixel_in_rectangle一致
+pub fn for_each_span_in_polygon<F>(points: &[Point], mut f: F)
+where F: FnMut(u32, u32, u32) {
+    if points.len() < 3 {
+        return;
+    }
+    let min_y = points.iter().map(|p| p.y).fold(f32::MAX, f32::min) as f64;
+    let max_y = points.iter().map(|p| p.y).fold(f32::MIN, f32::max) as f64;
+    let n = points.len();
+
+    let mut crossings = Vec::with_capacity(n);
+    let mut y = min_y.ceil().max(0.0);
+    while y < max_y {
+        crossings.clear();
+        for i in 0..n {
+            let (a, b) = (points[i], points[(i + 1) % n]);
+            let (ax, ay, bx, by) = (a.x as f64, a.y as f64, b.x as f64, b.y as f64);
+            if (ay > y) != (by > y) {
+                crossings.push(ax + (y - ay) * (bx - ax) / (by - ay));
+            }
+        }
+        crossings.sort_by(|a, b| a.total_cmp(b));
+        for pair in crossings.chunks_exact(2) {
+            // 像素坐标x落在[x0, x1)内
+            let x_start = pair[0].ceil().max(0.0) as u32;
+            let x_end = pair[1].ceil().max(0.0) as u32;
+            if x_start < x_end {
+                f(y as u32, x_start, x_end);
+            }
+        }
+        y += 1.0;
+    }
+}
+
+/// 遍历多边形区域内的所有像素点，超出图片的部分被裁掉
+pub fn for_each_pixel_in_polygon<F>(img: &RgbaImage, points: &[Point], mut f: F)
+where F: FnMut(u32, u32, &Rgba<u8>) {
+    for_each_span_in_polygon(points, |y, x_start, x_end| {
+        if y >= img.height() {
+            return;
+        }
+        for x in x_start..x_end.min(img.width()) {
+            f(x, y, img.get_pixel(x, y));
+        }
+    });
+}
+
+/// 计算多边形区域的平均色值，未覆盖任何像素时返回None
+pub fn calc_average_color_in_polygon(img: &RgbaImage, points: &[Point]) -> Option<Color> {
+    let mut total = [0u64; 4];
+    let mut pixel_count = 0u64;
+    for_each_pixel_in_polygon(img, points, |_, _, pixel| {
+        for c in 0..4 {
+            total[c] += pixel[c] as u64;
+        }
+        pixel_count += 1;
+    });
+    if pixel_count == 0 {
+        return None;
+    }
+    let avg = total.map(|v| (v as f32 / pixel_count as f32) as u8);
+    Some(Color::from_rgba((avg[0], avg[1], avg[2], avg[3])))
+}
+
+/// 计算多边形区域剔除掉背景色后的剩余区域占比，未覆盖任何像素时返回None
+pub fn calc_remaining_area_ratio_in_polygon(img: &RgbaImage, bg_color: Color, points: &[Point]) -> Option<f32> {
+    let mut total_pixel_count = 0;
+    let mut remaining_count = 0;
+    let bg = bg_color.to_rgb();
+    for_each_pixel_in_polygon(img, points, |_, _, pixel| {
+        total_pixel_count += 1;
+        if !is_bg_pixel((pixel[0], pixel[1], pixel[2]), bg) {
+            remaining_count += 1;
+        }
+    });
+    if total_pixel_count == 0 {
+        return None;
+    }
+    Some(remaining_count as f32 / total_pixel_count as f32)
+}
@@ -1,113 +1,138 @@
-use anyhow::Result;
-use image::RgbaImage;
-use tracing::error;
-use crate::{calc_color_distance, Color, Grid, GridShape, Point};
+use image::{Rgba, RgbaImage};
+use crate::{utils::is_bg_pixel, Color, Grid, GridShape};
+#[cfg(test)]
+use crate::Point;
+use super::layout::Lattice;
 
 
 /// 生成由三角形格子填充的画布的所有格子信息
-pub fn genarate_canvas_grids_filled_with_trianles(
-    canvas_width: u32,
-    canvas_height: u32,
-    triangle_width: u32,
-    triangle_height: u32,
-)-> Result<Vec<Grid>>{
-
-    let rows = canvas_height / triangle_height as u32;
-    let mut grids = Vec::with_capacity(1024);
-
-    // 绘制三角形，第一行倒三角形开头，第二行则为正三角形开头
-    for row in 0..rows {
-        // 绘制倒三角形，计算起始x坐标和y坐标
-        let mut x = if row % 2 == 0 { 0 } else { triangle_width / 2 };
-        let y = row * triangle_height;
-
-        let mut seq = 0;
-        while x + triangle_width <= canvas_width {
-            // 计算三角形三个顶点坐标
-            let points = get_down_triangle_points(x, y, triangle_width, triangle_height);
-            // println!("绘制倒三角形, 三点坐标: {:?}", &points);
-            seq += 1;
-            grids.push(Grid{
-                seq: format!("R{}D{}", row+1, seq),
-                shape: GridShape::Triangle,
-                points: points.to_vec(),
-                ext: Default::default(),
-            });
-            x = x + triangle_width;
+pub(super) fn genarate_canvas_grids_filled_with_trianles(lattice: &Lattice, capacity: usize) -> Vec<Grid> {
+    let mut grids = Vec::with_capacity(capacity);
+
+    // 绘制三角形，第一行倒三角形开头，第二行则为正三角形开头；没有格子的行不计入行号
+    let mut row_no = 0;
+    for row in lattice.rows() {
+        let count = grids.len();
+        // 倒三角形：底边在上，偶数行从第0个半格开始，奇数行从第1个半格开始
+        push_triangle_row(&mut grids, lattice, format!("R{}D", row_no + 1), row.rem_euclid(2), row, row + 1);
+        // 正三角形：底边在下，与倒三角形错开半格
+        push_triangle_row(&mut grids, lattice, format!("R{}U", row_no + 1), (row + 1).rem_euclid(2), row + 1, row);
+        if grids.len() > count {
+            row_no += 1;
         }
+    }
+    grids
+}
 
-        // 绘制正三角形
-        seq = 0;
-        let mut x = if row % 2 == 0 { triangle_width / 2 } else { 0 };
-        let y = (row + 1) * triangle_height;
-        while x + triangle_width <= canvas_width {
-            let points = get_up_triangle_points(x, y, triangle_width, triangle_height);
-            // println!("绘制正三角形, 三点坐标: {:?}", &points);
+/// 绘制一行同向的三角形，三角形的底边位于base_row行，顶点位于apex_row行
+fn push_triangle_row(grids: &mut Vec<Grid>, lattice: &Lattice, prefix: String, first_half: i64, base_row: i64, apex_row: i64) {
+    let mut seq = 0;
+    for j in lattice.triangle_columns(first_half) {
+        let points = lattice.triangle_points(j, base_row, apex_row);
+        if let Some(grid) = lattice.make_grid(format!("{}{}", prefix, seq + 1), GridShape::Triangle, points) {
             seq += 1;
-            grids.push(Grid{
-                seq: format!("R{}U{}", row+1, seq),
-                shape: GridShape::Triangle,
-                points: points.to_vec(),
-                ext: Default::default(),
-            });
-            x = x + triangle_width;
+            grids.push(grid);
         }
     }
+}
 
-
-    Ok(grids)
+/// 遍历三角形区域内的所有像素点
+pub fn for_each_pixel_in_triangle<F>(img: &RgbaImage, triangle: [(f32, f32); 3], mut f: F)
+where F: FnMut(u32, u32, &Rgba<u8>) {
+    // 超出图片的部分被裁掉
+    for_each_span_in_triangle(triangle, |y, x_start, x_end| {
+        if y >= img.height() {
+            return;
+        }
+        for x in x_start..x_end.min(img.width()) {
+            f(x, y, img.get_pixel(x, y));
+        }
+    });
 }
 
-/// 计算三角形区域的平均色值
-pub fn calc_average_color_in_triangle(img: &RgbaImage, points: &Vec<Point>) -> Result<Color> {
 
-    assert_eq!(points.len(), 3, "Each polygon should have 3 points.");
-    let triangle:[(u32, u32); 3] = [
-        (points[0].x, points[0].y),
-        (points[1].x, points[1].y),
-        (points[2].x, points[2].y),
-    ];
+/// 按行遍历三角形区域，回调参数为行号及该行像素区间[x_start, x_end)；
+/// 像素坐标点须落在三角形的外接矩形[min, max)内，顶点可以是小数，顺时针、逆时针排列均可
+pub fn for_each_span_in_triangle<F>(triangle: [(f32, f32); 3], mut f: F)
+where F: FnMut(u32, u32, u32) {
     // 计算三角形所在最小矩形区域的边界点，用于遍历该区域内所有的像素点
-    let (min_x, min_y, max_x, max_y) = triangle.iter().fold(
-        (std::u32::MAX, std::u32::MAX, 0, 0),
-        |(min_x, min_y, max_x, max_y), &(x, y)| {
-            (
-                min_x.min(x as u32),
-                min_y.min(y as u32),
-                max_x.max(x as u32),
-                max_y.max(y as u32),
-            )
-        },
+    let vertices = triangle.map(|(x, y)| (x as f64, y as f64));
+    let (min_x, min_y, max_x, max_y) = vertices.iter().fold(
+        (f64::MAX, f64::MAX, f64::MIN, f64::MIN),
+        |(min_x, min_y, max_x, max_y), &(x, y)| (min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y)),
     );
+    if !(min_x.is_finite() && min_y.is_finite() && max_x.is_finite() && max_y.is_finite()) {
+        return;
+    }
+
+    // 按行扫描，求出每行落在三角形内的像素区间，避免逐点做叉乘
+    let edges = [(vertices[0], vertices[1]), (vertices[1], vertices[2]), (vertices[2], vertices[0])];
+    // 三条边的叉乘之和为有向面积的2倍：为正时取严格内部的点，否则取内部及边上的点
+    let signed_area2: f64 = edges.iter()
+        .map(|&((x0, y0), (x1, y1))| (y1 - y0) * (min_x - x0) + (x0 - x1) * (min_y - y0))
+        .sum();
+
+    // 整数顶点时f64运算没有误差，与逐点叉乘的结果一致
+    let (x_lo, x_hi) = (min_x.ceil().max(0.0) as i64, max_x.ceil() as i64 - 1);
+    let mut y = min_y.ceil().max(0.0);
+    while y < max_y {
+        let mut lo = x_lo;
+        let mut hi = x_hi;
+        for &((x0, y0), (x1, y1)) in &edges {
+            // 边函数 a*x + c，与is_point_inside_triangle中的叉乘一致
+            let a = y1 - y0;
+            let c = -a * x0 + (x0 - x1) * (y - y0);
+            let (span_lo, span_hi) = if signed_area2 > 0.0 { positive_span(a, c) } else { non_negative_span(-a, -c) };
+            lo = lo.max(span_lo);
+            hi = hi.min(span_hi);
+        }
+        if lo <= hi {
+            f(y as u32, lo as u32, hi as u32 + 1);
+        }
+        y += 1.0;
+    }
+}
+
+
+/// 满足 a*x + c > 0 的整数x区间
+fn positive_span(a: f64, c: f64) -> (i64, i64) {
+    match a {
+        _ if a == 0.0 && c > 0.0 => (i64::MIN, i64::MAX),
+        _ if a == 0.0 => (i64::MAX, i64::MIN),
+        _ if a > 0.0 => ((-c / a).floor() as i64 + 1, i64::MAX),
+        _ => (i64::MIN, (-c / a).ceil() as i64 - 1),
+    }
+}
+
+/// 满足 a*x + c >= 0 的整数x区间
+fn non_negative_span(a: f64, c: f64) -> (i64, i64) {
+    match a {
+        _ if a == 0.0 && c >= 0.0 => (i64::MIN, i64::MAX),
+        _ if a == 0.0 => (i64::MAX, i64::MIN),
+        _ if a > 0.0 => ((-c / a).ceil() as i64, i64::MAX),
+        _ => (i64::MIN, (-c / a).floor() as i64),
+    }
+}
+
 
+/// 计算三角形区域的平均色值，三角形未覆盖任何像素时返回None
+pub fn calc_average_color_in_triangle(img: &RgbaImage, triangle: [(f32, f32); 3]) -> Option<Color> {
     let mut total_r = 0;
     let mut total_g = 0;
     let mut total_b = 0;
     let mut total_a = 0;
     let mut pixel_count = 0;
 
-    // println!("三角形坐标区域，min_x:{}, min_y:{}, max_x:{}, max_y:{}", min_x, min_y, max_x, max_y);
-
-    // 将坐标点转为imageproc的Point类型
-    let points: [Point; 3] = triangle.map(|(x, y)| Point::new(x, y));
-
-    for x in min_x..max_x {
-        for y in min_y..max_y {
-            if is_point_inside_triangle((x as f32, y as f32), points) {
-
-                let pixel = img.get_pixel(x, y);
-                total_r += pixel[0] as u32;
-                total_g += pixel[1] as u32;
-                total_b += pixel[2] as u32;
-                total_a += pixel[3] as u32;
-                pixel_count += 1;
-            }
-        }
-    }
+    for_each_pixel_in_triangle(img, triangle, |_, _, pixel| {
+        total_r += pixel[0] as u32;
+        total_g += pixel[1] as u32;
+        total_b += pixel[2] as u32;
+        total_a += pixel[3] as u32;
+        pixel_count += 1;
+    });
 
     if pixel_count == 0 {
-        error!("The triangle does not cover any pixels, triangle:{:?}", triangle);
-        panic!("The triangle does not cover any pixels in the image.");
+        return None;
     }
 
     let rgba = (
@@ -116,64 +141,41 @@ pub fn calc_average_color_in_triangle(img: &RgbaImage, points: &Vec<Point>) -> R
         (total_b as f32 / pixel_count as f32 ) as u8,
         (total_a as f32 / pixel_count as f32 ) as u8,
     );
-    Ok(Color::from_rgba(rgba))
+    Some(Color::from_rgba(rgba))
 }
 
 
-/// 计算三角形区域剔除掉背景色后的剩余区域占比
-pub fn calc_remaining_area_ratio_in_triangle(img: &RgbaImage, bg_color: Color, triangle: [(u32, u32); 3]) -> Result<f32> {
-    // 计算三角形边界框，用于遍历
-    let (min_x, min_y, max_x, max_y) = triangle.iter().fold(
-        (std::u32::MAX, std::u32::MAX, 0, 0),
-        |(min_x, min_y, max_x, max_y), &(x, y)| {
-            (
-                min_x.min(x as u32),
-                min_y.min(y as u32),
-                max_x.max(x as u32),
-                max_y.max(y as u32),
-            )
-        },
-    );
-
-    // 将坐标点转为imageproc的Point类型
-    let points: [Point; 3] = triangle.map(|(x, y)| Point::new(x, y));
-
+/// 计算三角形区域剔除掉背景色后的剩余区域占比，三角形未覆盖任何像素时返回None
+pub fn calc_remaining_area_ratio_in_triangle(img: &RgbaImage, bg_color: Color, triangle: [(f32, f32); 3]) -> Option<f32> {
     let mut total_pixel_count = 0;
     let mut remaining_count = 0;
+    let bg = bg_color.to_rgb();
 
-    for x in min_x..max_x {
-        for y in min_y..max_y {
-            if is_point_inside_triangle((x as f32, y as f32), points) {
-                let pixel = img.get_pixel(x, y);
-                total_pixel_count += 1;
-                let color1 = (pixel[0], pixel[1], pixel[2]);
-                let color2 = (bg_color.0 .0, bg_color.0 .1, bg_color.0 .2);
-                let distance = calc_color_distance(color1, color2);
-                if distance > 5.0{
-                    remaining_count += 1;
-                }
-            }
+    for_each_pixel_in_triangle(img, triangle, |_, _, pixel| {
+        total_pixel_count += 1;
+        if !is_bg_pixel((pixel[0], pixel[1], pixel[2]), bg) {
+            remaining_count += 1;
         }
-    }
+    });
 
     if total_pixel_count == 0 {
-        println!("三角形区域未覆盖任何点，triangle:{:?}", triangle);
-        panic!("The triangle does not cover any pixels in the image.");
+        return None;
     }
 
-    Ok(remaining_count as f32 / total_pixel_count as f32)
+    Some(remaining_count as f32 / total_pixel_count as f32)
 
 }
 
 
 /// 判断点是否在三角形内部
+#[cfg(test)]
 fn is_point_inside_triangle(point: (f32, f32), vertices: [Point; 3]) -> bool {
     // 这里简化处理，直接使用向量叉乘法判断，实际应用中可能需要更精确的判断方法
     let (x, y) = point;
     let (v0, v1, v2) = (vertices[0], vertices[1], vertices[2]);
-    let a = ((v1.y as f32 - v0.y as f32) * (x - v0.x as f32) + (v0.x as f32 - v1.x as f32) * (y - v0.y as f32)) > 0.0;
-    let b = ((v2.y as f32 - v1.y as f32) * (x - v1.x as f32) + (v1.x as f32 - v2.x as f32) * (y - v1.y as f32)) > 0.0;
-    let c = ((v0.y as f32 - v2.y as f32) * (x - v2.x as f32) + (v2.x as f32 - v0.x as f32) * (y - v2.y as f32)) > 0.0;
+    let a = ((v1.y - v0.y) * (x - v0.x) + (v0.x - v1.x) * (y - v0.y)) > 0.0;
+    let b = ((v2.y - v1.y) * (x - v1.x) + (v1.x - v2.x) * (y - v1.y)) > 0.0;
+    let c = ((v0.y - v2.y) * (x - v2.x) + (v2.x - v0.x) * (y - v2.y)) > 0.0;
     a == b && b == c
 }
 
@@ -181,24 +183,57 @@ fn is_point_inside_triangle(point: (f32, f32), vertices: [Point; 3]) -> bool {
 
 
 ///计算倒三角形的三角形顶点坐标； 传入水平方向的第一个点的坐标，及三角形的边长和高，返回三个点的坐标
+#[cfg(test)]
 fn get_down_triangle_points(x: u32, y: u32, side: u32, height: u32) -> [Point; 3] {
     [
-        Point { x, y },
-        Point { x: x + side, y },
-        Point {
-            x: x + side / 2,
-            y: y + height,
-        },
+        Point::new(x as f32, y as f32),
+        Point::new((x + side) as f32, y as f32),
+        Point::new((x + side / 2) as f32, (y + height) as f32),
     ]
 }
 /// 计算正三角形的三角形顶点坐标；传入水平方向的第一个点的坐标，及三角形的边长和高，返回三个点的坐标
+#[cfg(test)]
 fn get_up_triangle_points(x: u32, y: u32, width: u32, height: u32) -> [Point; 3] {
     [
-        Point { x, y },
-        Point { x: x + width, y },
-        Point {
-            x: x + width / 2,
-            y: y - height,
-        },
+        Point::new(x as f32, y as f32),
+        Point::new((x + width) as f32, y as f32),
+        Point::new((x + width / 2) as f32, (y - height) as f32),
     ]
 }
+
+
+#[cfg(test)]
+#[test]
+fn test_triangle_spans_match_cross_product() {
+    let img = RgbaImage::new(64, 64);
+    let triangles = [
+        get_down_triangle_points(3, 5, 21, 16),
+        get_up_triangle_points(13, 37, 21, 16),
+        get_down_triangle_points(0, 0, 5, 4),
+        get_up_triangle_points(2, 4, 5, 4),
+        // 旋转后的三角形：顶点为小数，两种排列方向
+        [Point::new(2.5, 3.25), Point::new(40.75, 10.5), Point::new(12.2, 50.9)],
+        [Point::new(12.2, 50.9), Point::new(40.75, 10.5), Point::new(2.5, 3.25)],
+    ];
+    for points in triangles {
+        let triangle = points.map(|p| (p.x, p.y));
+        let mut scanned = Vec::new();
+        for_each_pixel_in_triangle(&img, triangle, |x, y, _| scanned.push((x, y)));
+        scanned.sort_unstable();
+
+        let mut expected = Vec::new();
+        for x in 0..64 {
+            for y in 0..64 {
+                let inside = is_point_inside_triangle((x as f32, y as f32), points);
+                let (fx, fy) = (x as f32, y as f32);
+                let in_box = points.iter().any(|p| p.x > fx) && points.iter().any(|p| p.y > fy)
+                    && points.iter().any(|p| p.x <= fx) && points.iter().any(|p| p.y <= fy);
+                if inside && in_box {
+                    expected.push((x, y));
+                }
+            }
+        }
+        assert_eq!(scanned, expected);
+    }
+}
+
@@ -0,0 +1,136 @@
+use std::collections::VecDeque;
+use serde::{Deserialize, Serialize};
+use crate::Grid;
+use super::canvas::GridGraph;
+
+
+/// 格子图上的形态学操作
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum GridMorphOp {
+    // 膨胀：选中与已选格子共边的格子
+    Dilate,
+    // 腐蚀：取消选中与未选格子共边的格子
+    Erode,
+}
+
+
+/// 选中格子的清理选项
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GridCleanupOptions {
+    // 移除格子数小于该值的连通区域（孤立的噪点格子），0表示不处理
+    #[serde(default)]
+    pub min_component_size: usize,
+    // 填充格子数不超过该值的封闭空洞，0表示不处理
+    #[serde(default)]
+    pub max_hole_size: usize,
+    // 按顺序执行的膨胀/腐蚀操作
+    #[serde(default)]
+    pub morphology: Vec<GridMorphOp>,
+}
+
+
+/// 清理选中的格子：依次移除小连通区域、填充封闭空洞、执行膨胀/腐蚀
+pub fn cleanup_selected_grids(grids: &mut [Grid], options: &GridCleanupOptions) {
+    let graph = GridGraph::build(grids);
+    cleanup_selected_grids_with_graph(grids, &graph, options);
+}
+
+
+/// 使用已构建的邻接图清理选中的格子
+pub fn cleanup_selected_grids_with_graph(grids: &mut [Grid], graph: &GridGraph, options: &GridCleanupOptions) {
+    if options.min_component_size > 0 {
+        for component in components(grids, graph, true) {
+            if component.len() < options.min_component_size {
+                component.iter().for_each(|&i| set_selected(grids, graph, i, false));
+            }
+        }
+    }
+
+    if options.max_hole_size > 0 {
+        for component in components(grids, graph, false) {
+            // 与画布边缘相连的未选区域不是空洞
+            let enclosed = component.iter().all(|&i| !graph.is_boundary(i));
+            if enclosed && component.len() <= options.max_hole_size {
+                component.iter().for_each(|&i| set_selected(grids, graph, i, true));
+            }
+        }
+    }
+
+    for op in &options.morphology {
+        let target = *op == GridMorphOp::Dilate;
+        // 先收集再修改，保证一次操作只基于操作前的状态
+        let changed: Vec<usize> = (0..grids.len())
+            .filter(|&i| is_selected(&grids[i]) != target)
+            .filter(|&i| graph.edge_neighbors(i).iter().any(|&j| is_selected(&grids[j]) == target))
+            .collect();
+        changed.into_iter().for_each(|i| set_selected(grids, graph, i, target));
+    }
+}
+
+
+fn is_selected(grid: &Grid) -> bool {
+    grid.ext.selected.unwrap_or(false)
+}
+
+
+fn set_selected(grids: &mut [Grid], graph: &GridGraph, index: usize, selected: bool) {
+    if selected && grids[index].ext.palette_index.is_none() {
+        // 新选中的格子沿用相邻选中格子的调色板颜色
+        grids[index].ext.palette_index = graph.edge_neighbors(index).iter()
+            .find_map(|&j| grids[j].ext.palette_index.filter(|_| is_selected(&grids[j])));
+    }
+    if !selected {
+        grids[index].ext.palette_index = None;
+    }
+    grids[index].ext.selected = Some(selected);
+}
+
+
+/// 按选中状态划分共边连通区域
+fn components(grids: &[Grid], graph: &GridGraph, selected: bool) -> Vec<Vec<usize>> {
+    let mut visited = vec![false; grids.len()];
+    let mut result = Vec::new();
+    for start in 0..grids.len() {
+        if visited[start] || is_selected(&grids[start]) != selected {
+            continue;
+        }
+        visited[start] = true;
+        let mut component = Vec::new();
+        let mut queue = VecDeque::from([start]);
+        while let Some(i) = queue.pop_front() {
+            component.push(i);
+            for &j in graph.edge_neighbors(i) {
+                if !visited[j] && is_selected(&grids[j]) == selected {
+                    visited[j] = true;
+                    queue.push_back(j);
+                }
+            }
+        }
+        result.push(component);
+    }
+    result
+}
+
+
+#[cfg(test)]
+#[test]
+fn test_cleanup_removes_noise_and_fills_holes() {
+    use crate::{generate_enmty_canvas_grids, GridFillOptions};
+
+    // 5x5的矩形格子，中间3x3选中但中心为空洞，角落有一个孤立格子
+    let mut grids = generate_enmty_canvas_grids(50, 50, GridFillOptions::rectangle(10, 10)).unwrap();
+    for (i, grid) in grids.iter_mut().enumerate() {
+        let (row, col) = (i / 5, i % 5);
+        let ring = (1..=3).contains(&row) && (1..=3).contains(&col) && !(row == 2 && col == 2);
+        grid.ext.selected = Some(ring || i == 24);
+    }
+    cleanup_selected_grids(&mut grids, &GridCleanupOptions {
+        min_component_size: 2,
+        max_hole_size: 1,
+        morphology: vec![],
+    });
+    let selected: Vec<usize> = (0..25).filter(|&i| is_selected(&grids[i])).collect();
+    assert_eq!(selected, vec![6, 7, 8, 11, 12, 13, 16, 17, 18]);
+}
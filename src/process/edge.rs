@@ -0,0 +1,144 @@
+use crate::error::Result;
+use image::{GrayImage, ImageBuffer, Luma, RgbaImage};
+use imageproc::{distance_transform::euclidean_squared_distance_transform, gradients::sobel_gradients};
+use crate::{utils::is_bg_pixel, Color, EdgeAwareParam, Grid, ProcessError};
+use super::canvas::for_each_pixel_in_grid;
+
+/// sobel梯度幅值不低于该值的像素视为边缘（前景掩码为0/255时，直线边缘处的幅值约为1020）
+const EDGE_MAGNITUDE_THRESHOLD: u16 = 255;
+
+
+/// logo的边缘及笔画特征图，每张logo只需计算一次
+pub struct EdgeFeatureMaps {
+    // 前景掩码，前景为255
+    foreground: GrayImage,
+    // 前景像素到最近背景像素的距离
+    distance_to_bg: ImageBuffer<Luma<f32>, Vec<f32>>,
+    // 前景轮廓的sobel梯度幅值
+    gradients: ImageBuffer<Luma<u16>, Vec<u16>>,
+}
+
+impl EdgeFeatureMaps {
+    /// 以bg_color为背景色，计算logo的前景掩码、距离变换和边缘梯度
+    pub fn new(img: &RgbaImage, bg_color: Color) -> Self {
+        let bg = bg_color.to_rgb();
+        let foreground = GrayImage::from_fn(img.width(), img.height(), |x, y| {
+            let p = img.get_pixel(x, y);
+            if !is_bg_pixel((p[0], p[1], p[2]), bg) {
+                Luma([255])
+            } else {
+                Luma([0])
+            }
+        });
+
+        // 距离变换以非0像素为目标，这里需要的是到背景的距离，因此对掩码取反
+        let background = GrayImage::from_fn(img.width(), img.height(), |x, y| {
+            Luma([255 - foreground.get_pixel(x, y)[0]])
+        });
+        let squared = euclidean_squared_distance_transform(&background);
+        let distance_to_bg = ImageBuffer::from_fn(img.width(), img.height(), |x, y| {
+            Luma([squared.get_pixel(x, y)[0].sqrt() as f32])
+        });
+
+        let gradients = sobel_gradients(&foreground);
+        Self { foreground, distance_to_bg, gradients }
+    }
+
+    // 图像外按背景处理
+    fn distance_at(&self, x: i64, y: i64) -> f32 {
+        if x < 0 || y < 0 || x >= self.distance_to_bg.width() as i64 || y >= self.distance_to_bg.height() as i64 {
+            return 0.0;
+        }
+        self.distance_to_bg.get_pixel(x as u32, y as u32)[0]
+    }
+
+    /// 估计穿过前景像素(x, y)的笔画宽度：
+    /// 笔画中心到背景的距离为d时，奇数宽度的中心像素两侧都更靠近背景，宽度为2d-1；
+    /// 偶数宽度的中心是两个距离相同的像素，宽度为2d。取水平和垂直方向中较窄的一个
+    pub fn stroke_width_at(&self, x: u32, y: u32) -> f32 {
+        let d = self.distance_at(x as i64, y as i64);
+        let across = |dx: i64, dy: i64| {
+            let before = self.distance_at(x as i64 - dx, y as i64 - dy) >= d;
+            let after = self.distance_at(x as i64 + dx, y as i64 + dy) >= d;
+            match (before, after) {
+                (false, false) => 2.0 * d - 1.0,
+                (true, true) => f32::INFINITY,
+                _ => 2.0 * d,
+            }
+        };
+        // 两个方向都不是局部最大值时，距离本身给出宽度的下界
+        across(1, 0).min(across(0, 1)).min(2.0 * d)
+    }
+}
+
+
+/// 边缘感知选取：前景覆盖率达到阈值的格子直接选中；
+/// 覆盖率不足时，若格子被宽度不小于min_stroke_width的笔画穿过且含有足够的边缘像素，同样选中
+pub fn pick_grid_edge_aware(img: &RgbaImage, grid: &mut Grid, maps: &EdgeFeatureMaps, param: &EdgeAwareParam) -> Result<()> {
+    let mut total_pixel_count = 0u32;
+    let mut foreground_count = 0u32;
+    let mut edge_count = 0u32;
+    let mut stroke_width = 0f32;
+
+    for_each_pixel_in_grid(img, grid, |x, y, _| {
+        total_pixel_count += 1;
+        if maps.foreground.get_pixel(x, y)[0] > 0 {
+            foreground_count += 1;
+            stroke_width = stroke_width.max(maps.stroke_width_at(x, y));
+        }
+        if maps.gradients.get_pixel(x, y)[0] >= EDGE_MAGNITUDE_THRESHOLD {
+            edge_count += 1;
+        }
+    })?;
+
+    if total_pixel_count == 0 {
+        return Err(ProcessError::EmptyGrid(grid.seq.clone()));
+    }
+
+    let remaining_area_ratio = foreground_count as f32 / total_pixel_count as f32;
+    let edge_ratio = edge_count as f32 / total_pixel_count as f32;
+
+    let selected = remaining_area_ratio >= param.min_remaining_ratio
+        || (foreground_count > 0
+            && stroke_width >= param.min_stroke_width
+            && edge_ratio >= param.min_edge_ratio);
+
+    grid.ext.remaining_area_ratio = Some(remaining_area_ratio);
+    grid.ext.edge_ratio = Some(edge_ratio);
+    grid.ext.stroke_width = Some(stroke_width);
+    grid.ext.selected = Some(selected);
+    Ok(())
+}
+
+
+#[cfg(test)]
+#[test]
+fn test_pick_grid_edge_aware_stroke_width() {
+    use crate::{generate_enmty_canvas_grids, GridFillOptions};
+
+    // 白底上一条竖线穿过20x20的格子，覆盖率远低于阈值
+    let pick = |line_width: u32| -> Grid {
+        let img = RgbaImage::from_fn(20, 20, |x, _| {
+            if (9..9 + line_width).contains(&x) { image::Rgba([0, 0, 0, 255]) } else { image::Rgba([255, 255, 255, 255]) }
+        });
+        let maps = EdgeFeatureMaps::new(&img, Color::WHITE);
+        let param = EdgeAwareParam { bg_color: Color::WHITE, min_remaining_ratio: 0.5, min_stroke_width: 2.0, min_edge_ratio: 0.05 };
+        let mut grid = generate_enmty_canvas_grids(20, 20, GridFillOptions::rectangle(20, 20)).unwrap().remove(0);
+        pick_grid_edge_aware(&img, &mut grid, &maps, &param).unwrap();
+        grid
+    };
+
+    // 2px的线条两侧各有两列边缘像素
+    let thick = pick(2);
+    assert_eq!(thick.ext.stroke_width, Some(2.0));
+    assert_eq!(thick.ext.edge_ratio, Some(0.2));
+    assert_eq!(thick.ext.selected, Some(true));
+
+    // 1px的线条边缘占比足够，但笔画过细
+    let thin = pick(1);
+    assert_eq!(thin.ext.stroke_width, Some(1.0));
+    assert!(thin.ext.edge_ratio.unwrap() >= 0.05);
+    assert_eq!(thin.ext.selected, Some(false));
+
+    assert_eq!(pick(3).ext.stroke_width, Some(3.0));
+}
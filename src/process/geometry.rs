@@ -0,0 +1,277 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{Grid, Point};
+
+
+impl Point {
+    /// 绕origin逆时针(y轴向下时为顺时针)旋转angle度
+    pub fn rotate(&self, angle: f32, origin: Point) -> Point {
+        let (sin, cos) = angle.to_radians().sin_cos();
+        let (dx, dy) = (self.x - origin.x, self.y - origin.y);
+        Point::new(origin.x + dx * cos - dy * sin, origin.y + dx * sin + dy * cos)
+    }
+}
+
+
+/// 轴对齐的矩形，x/y为左上角
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Rect {
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self { x, y, width, height }
+    }
+
+    /// 由两个角点构造，角点顺序任意
+    pub fn from_corners(a: Point, b: Point) -> Self {
+        Rect::new(a.x.min(b.x), a.y.min(b.y), (a.x - b.x).abs(), (a.y - b.y).abs())
+    }
+
+    pub fn max_x(&self) -> f32 {
+        self.x + self.width
+    }
+
+    pub fn max_y(&self) -> f32 {
+        self.y + self.height
+    }
+
+    /// 点在矩形内或边上
+    pub fn contains(&self, p: Point) -> bool {
+        p.x >= self.x && p.x <= self.max_x() && p.y >= self.y && p.y <= self.max_y()
+    }
+
+    /// 两矩形是否重叠(仅边相接不算)
+    pub fn intersects(&self, other: &Rect) -> bool {
+        self.x < other.max_x() && other.x < self.max_x() && self.y < other.max_y() && other.y < self.max_y()
+    }
+}
+
+
+/// 多边形，顶点按顺时针或逆时针排列均可
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct Polygon(pub Vec<Point>);
+
+impl Polygon {
+    pub fn points(&self) -> &[Point] {
+        &self.0
+    }
+
+    fn edges(&self) -> impl Iterator<Item = (Point, Point)> + '_ {
+        let n = self.0.len();
+        (0..n).map(move |i| (self.0[i], self.0[(i + 1) % n]))
+    }
+
+    /// 以第一个顶点为原点的边，避免大坐标的叉积在f32下相互抵消时丢失精度
+    fn local_edges(&self) -> impl Iterator<Item = ((f64, f64), (f64, f64))> + '_ {
+        let origin = self.0.first().copied().unwrap_or_default();
+        let local = move |p: Point| ((p.x - origin.x) as f64, (p.y - origin.y) as f64);
+        self.edges().map(move |(a, b)| (local(a), local(b)))
+    }
+
+    /// 有向面积，顶点在y轴向下的坐标系中顺时针排列时为正
+    pub fn signed_area(&self) -> f32 {
+        (self.local_edges().map(|(a, b)| a.0 * b.1 - b.0 * a.1).sum::<f64>() / 2.0) as f32
+    }
+
+    pub fn area(&self) -> f32 {
+        self.signed_area().abs()
+    }
+
+    /// 质心；退化为线段或点时取顶点的平均值
+    pub fn centroid(&self) -> Point {
+        let area = self.signed_area();
+        if area.abs() <= f32::EPSILON {
+            let n = self.0.len().max(1) as f32;
+            let (sx, sy) = self.0.iter().fold((0.0, 0.0), |(sx, sy), p| (sx + p.x, sy + p.y));
+            return Point::new(sx / n, sy / n);
+        }
+        let (cx, cy) = self.local_edges().fold((0.0, 0.0), |(cx, cy), (a, b)| {
+            let cross = a.0 * b.1 - b.0 * a.1;
+            (cx + (a.0 + b.0) * cross, cy + (a.1 + b.1) * cross)
+        });
+        let (origin, area) = (self.0[0], 6.0 * area as f64);
+        Point::new(origin.x + (cx / area) as f32, origin.y + (cy / area) as f32)
+    }
+
+    /// 外接矩形，没有顶点时为空矩形
+    pub fn bbox(&self) -> Rect {
+        let Some(first) = self.0.first() else { return Rect::default() };
+        let (min, max) = self.0.iter().fold((*first, *first), |(min, max), p| (
+            Point::new(min.x.min(p.x), min.y.min(p.y)),
+            Point::new(max.x.max(p.x), max.y.max(p.y)),
+        ));
+        Rect::from_corners(min, max)
+    }
+
+    /// 点在多边形内或边上
+    pub fn contains(&self, p: Point) -> bool {
+        let mut inside = false;
+        for (a, b) in self.edges() {
+            if on_segment(p, a, b) {
+                return true;
+            }
+            // 射线法：统计向右的水平射线与边的交点数
+            if (a.y > p.y) != (b.y > p.y) && p.x < a.x + (p.y - a.y) * (b.x - a.x) / (b.y - a.y) {
+                inside = !inside;
+            }
+        }
+        inside
+    }
+
+    pub fn translate(&self, dx: f32, dy: f32) -> Polygon {
+        self.map(|p| Point::new(p.x + dx, p.y + dy))
+    }
+
+    /// 以origin为中心缩放
+    pub fn scale(&self, sx: f32, sy: f32, origin: Point) -> Polygon {
+        self.map(|p| Point::new(origin.x + (p.x - origin.x) * sx, origin.y + (p.y - origin.y) * sy))
+    }
+
+    /// 绕origin旋转angle度
+    pub fn rotate(&self, angle: f32, origin: Point) -> Polygon {
+        self.map(|p| p.rotate(angle, origin))
+    }
+
+    fn map(&self, f: impl Fn(&Point) -> Point) -> Polygon {
+        Polygon(self.0.iter().map(f).collect())
+    }
+
+    /// 与矩形的交集(Sutherland-Hodgman裁剪)，不相交时返回空多边形
+    pub fn clip_to_rect(&self, rect: &Rect) -> Polygon {
+        let mut points = self.0.clone();
+        // 依次用矩形的四条边裁剪，距离函数非负表示在边的内侧
+        let edges: [&dyn Fn(Point) -> f32; 4] = [
+            &|p| p.x - rect.x,
+            &|p| rect.max_x() - p.x,
+            &|p| p.y - rect.y,
+            &|p| rect.max_y() - p.y,
+        ];
+        for distance in edges {
+            points = clip_half_plane(&points, distance);
+        }
+        Polygon(points)
+    }
+
+    /// 是否与矩形有重叠面积
+    pub fn intersects_rect(&self, rect: &Rect) -> bool {
+        self.bbox().intersects(rect) && self.clip_to_rect(rect).area() > 0.0
+    }
+}
+
+fn on_segment(p: Point, a: Point, b: Point) -> bool {
+    let cross = (b.x - a.x) * (p.y - a.y) - (b.y - a.y) * (p.x - a.x);
+    cross.abs() <= 1e-4 * ((b.x - a.x).abs() + (b.y - a.y).abs()).max(1.0)
+        && p.x >= a.x.min(b.x) && p.x <= a.x.max(b.x)
+        && p.y >= a.y.min(b.y) && p.y <= a.y.max(b.y)
+}
+
+/// 保留distance非负一侧的部分
+fn clip_half_plane(points: &[Point], distance: &dyn Fn(Point) -> f32) -> Vec<Point> {
+    let mut clipped = Vec::with_capacity(points.len() + 1);
+    for (i, &b) in points.iter().enumerate() {
+        let a = points[(i + points.len() - 1) % points.len()];
+        let (da, db) = (distance(a), distance(b));
+        if (da >= 0.0) != (db >= 0.0) {
+            let t = da / (da - db);
+            clipped.push(Point::new(a.x + (b.x - a.x) * t, a.y + (b.y - a.y) * t));
+        }
+        if db >= 0.0 {
+            clipped.push(b);
+        }
+    }
+    clipped
+}
+
+
+/// 格子的几何属性，均以浮点坐标计算
+impl Grid {
+    pub fn polygon(&self) -> Polygon {
+        Polygon(self.points.clone())
+    }
+
+    pub fn area(&self) -> f32 {
+        self.polygon().area()
+    }
+
+    pub fn centroid(&self) -> Point {
+        self.polygon().centroid()
+    }
+
+    pub fn bbox(&self) -> Rect {
+        self.polygon().bbox()
+    }
+
+    /// 点在格子内或边上
+    pub fn contains(&self, p: Point) -> bool {
+        self.polygon().contains(p)
+    }
+
+    pub fn translate(&self, dx: f32, dy: f32) -> Polygon {
+        self.polygon().translate(dx, dy)
+    }
+
+    pub fn scale(&self, sx: f32, sy: f32, origin: Point) -> Polygon {
+        self.polygon().scale(sx, sy, origin)
+    }
+
+    pub fn rotate(&self, angle: f32, origin: Point) -> Polygon {
+        self.polygon().rotate(angle, origin)
+    }
+
+    /// 格子与矩形的交集
+    pub fn intersection_with_rect(&self, rect: &Rect) -> Polygon {
+        self.polygon().clip_to_rect(rect)
+    }
+
+    pub fn intersects_rect(&self, rect: &Rect) -> bool {
+        self.polygon().intersects_rect(rect)
+    }
+}
+
+
+#[cfg(test)]
+#[test]
+fn test_grid_geometry() {
+    use crate::GridShape;
+
+    let grid = Grid {
+        seq: "R1D1".to_string(),
+        shape: GridShape::Triangle,
+        points: vec![Point::new(0.0, 0.0), Point::new(6.0, 0.0), Point::new(3.0, 6.0)],
+        ext: Default::default(),
+    };
+    assert_eq!(grid.area(), 18.0);
+    assert_eq!(grid.centroid(), Point::new(3.0, 2.0));
+    assert_eq!(grid.bbox(), Rect::new(0.0, 0.0, 6.0, 6.0));
+    assert!(grid.contains(Point::new(3.0, 3.0)));
+    assert!(grid.contains(Point::new(3.0, 0.0)));
+    assert!(!grid.contains(Point::new(0.5, 5.0)));
+
+    let moved = grid.translate(10.0, 0.0).rotate(90.0, Point::new(10.0, 0.0));
+    assert!((moved.area() - 18.0).abs() < 1e-3);
+    assert!(moved.contains(Point::new(8.0, 3.0)));
+    assert_eq!(grid.scale(2.0, 0.5, Point::default()).area(), 18.0);
+
+    let far = grid.translate(8000.0, 8000.0);
+    assert_eq!(far.area(), 18.0);
+    assert_eq!(far.centroid(), Point::new(8003.0, 8002.0));
+
+    let clipped = grid.intersection_with_rect(&Rect::new(0.0, 0.0, 6.0, 3.0));
+    assert!((clipped.area() - 13.5).abs() < 1e-3);
+    assert!(grid.intersects_rect(&Rect::new(2.0, 4.0, 2.0, 2.0)));
+    assert!(!grid.intersects_rect(&Rect::new(6.0, 0.0, 2.0, 2.0)));
+}
+
+#[cfg(test)]
+#[test]
+fn test_point_keeps_integer_wire_format() {
+    assert_eq!(serde_json::to_string(&Point::new(3.0, 4.0)).unwrap(), r#"{"x":3,"y":4}"#);
+    assert_eq!(serde_json::to_string(&Point::new(2.5, 0.0)).unwrap(), r#"{"x":2.5,"y":0}"#);
+    let point: Point = serde_json::from_str(r#"{"x":3,"y":4.5}"#).unwrap();
+    assert_eq!(point, Point::new(3.0, 4.5));
+}
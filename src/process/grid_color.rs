@@ -0,0 +1,135 @@
+use crate::error::Result;
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+use crate::{utils::find_nearest_color, Color, Grid};
+use super::canvas::calc_avg_color_of_grid;
+
+
+/// 选中格子的着色方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum GridColorMode {
+    // 所有选中格子使用同一颜色
+    #[default]
+    Fixed,
+    // 选中格子使用各自区域的平均色值（真彩色马赛克）
+    AvgColor,
+    // 选中格子使用按调色板分类得到的颜色
+    Palette,
+}
+
+
+/// 格子着色选项
+#[derive(Debug, Clone)]
+pub struct GridColorOptions {
+    pub mode: GridColorMode,
+    // 选中格子的颜色，Fixed模式下使用
+    pub selected_color: Color,
+    // 未选中格子的颜色，为None时不设置
+    pub unselected_color: Option<Color>,
+    // 格子边框颜色，为None时不设置
+    pub border_color: Option<Color>,
+    // AvgColor模式下，将平均色值吸附到最接近的调色板颜色；Palette模式下按格子的palette_index取色
+    pub palette: Option<Vec<Color>>,
+}
+
+impl GridColorOptions {
+    pub fn new(mode: GridColorMode, selected_color: Color) -> Self {
+        Self {
+            mode,
+            selected_color,
+            unselected_color: None,
+            border_color: None,
+            palette: None,
+        }
+    }
+}
+
+
+/// 根据着色选项设置格子的fill_color和border_color
+pub fn paint_grids(img: &RgbaImage, grids: &mut [Grid], options: &GridColorOptions) -> Result<()> {
+    let palette = options.palette.as_deref().filter(|p| !p.is_empty());
+    for grid in grids.iter_mut() {
+        if grid.ext.selected.unwrap_or(false) {
+            let fill_color = match options.mode {
+                GridColorMode::Fixed => options.selected_color,
+                GridColorMode::AvgColor => {
+                    let avg_color = match grid.ext.avg_color {
+                        Some(color) => color,
+                        None => {
+                            let color = calc_avg_color_of_grid(img, grid)?;
+                            grid.ext.avg_color = Some(color);
+                            color
+                        }
+                    };
+                    // 马赛克格子统一使用不透明色
+                    let color = Color::from_rgb(avg_color.to_rgb());
+                    palette.and_then(|p| find_nearest_color(color, p).map(|i| p[i]))
+                        .unwrap_or(color)
+                },
+                GridColorMode::Palette => {
+                    palette.zip(grid.ext.palette_index)
+                        .and_then(|(p, i)| p.get(i).copied())
+                        .unwrap_or(options.selected_color)
+                },
+            };
+            grid.ext.fill_color = Some(fill_color);
+        } else if let Some(color) = options.unselected_color {
+            grid.ext.fill_color = Some(color);
+        }
+        if let Some(color) = options.border_color {
+            grid.ext.border_color = Some(color);
+        }
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+#[test]
+fn test_paint_grids_modes() {
+    use crate::{GridShape, Point};
+
+    // 左半红色，右半蓝色
+    let mut img = RgbaImage::from_pixel(20, 10, image::Rgba([0, 0, 200, 255]));
+    for y in 0..10 {
+        for x in 0..10 {
+            img.put_pixel(x, y, image::Rgba([200, 0, 0, 255]));
+        }
+    }
+    let grid = |left: f32, palette_index: Option<usize>| {
+        let mut grid = Grid {
+            seq: format!("R1C{}", left as u32 / 10 + 1),
+            shape: GridShape::Rectangle,
+            points: vec![Point::new(left, 0.0), Point::new(left + 10.0, 0.0), Point::new(left + 10.0, 10.0), Point::new(left, 10.0)],
+            ext: Default::default(),
+        };
+        grid.ext.selected = Some(true);
+        grid.ext.palette_index = palette_index;
+        grid
+    };
+    let red = Color::from_rgb((200, 0, 0));
+    let blue = Color::from_rgb((0, 0, 200));
+    let green = Color::from_rgb((0, 255, 0));
+    let gray = Color::from_rgb((128, 128, 128));
+    let fill_colors = |options: &GridColorOptions| {
+        let mut grids = vec![grid(0.0, Some(1)), grid(10.0, None)];
+        grids.push(Grid { ext: Default::default(), ..grid(0.0, None) });
+        paint_grids(&img, &mut grids, options).unwrap();
+        grids.iter().map(|g| g.ext.fill_color).collect::<Vec<_>>()
+    };
+
+    let mut options = GridColorOptions::new(GridColorMode::Fixed, green);
+    options.unselected_color = Some(gray);
+    assert_eq!(fill_colors(&options), vec![Some(green), Some(green), Some(gray)]);
+
+    options.mode = GridColorMode::AvgColor;
+    assert_eq!(fill_colors(&options), vec![Some(red), Some(blue), Some(gray)]);
+    // 平均色吸附到最接近的调色板颜色
+    options.palette = Some(vec![Color::from_rgb((255, 0, 0)), Color::from_rgb((0, 0, 255))]);
+    assert_eq!(fill_colors(&options), vec![Some(Color::from_rgb((255, 0, 0))), Some(Color::from_rgb((0, 0, 255))), Some(gray)]);
+
+    // 按palette_index取色，没有下标的格子使用选中色
+    options.mode = GridColorMode::Palette;
+    assert_eq!(fill_colors(&options), vec![Some(Color::from_rgb((0, 0, 255))), Some(green), Some(gray)]);
+}
@@ -1,53 +1,273 @@
-use std::path::PathBuf;
-use image::{ImageBuffer, Rgba};
-use anyhow::Result;
+use std::{io::Cursor, path::PathBuf};
+use image::{DynamicImage, ImageBuffer, ImageFormat, RgbaImage};
+use crate::error::Result;
 use imageproc::drawing::{draw_hollow_polygon_mut, draw_polygon_mut};
-use crate::Grid;
+use serde::{Deserialize, Serialize};
+use crate::{Color, Grid, ProcessError};
 
 
-/// 画带有格子的画布
-pub fn draw_canvas_with_grids(
-    canvas_width: u32, 
-    canvas_height: u32,
-    canvas_color: Rgba<u8>,
-    grids: Vec<Grid>,
-    path: PathBuf,
-) -> Result<()> {
+/// 渲染输出的图片编码格式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RenderFormat {
+    #[default]
+    Png,
+    Jpeg,
+    Webp,
+}
+
+impl RenderFormat {
+    /// 对应的http content-type
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            RenderFormat::Png => "image/png",
+            RenderFormat::Jpeg => "image/jpeg",
+            RenderFormat::Webp => "image/webp",
+        }
+    }
+
+    /// 按文件扩展名推断格式，不区分大小写
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "png" => Some(RenderFormat::Png),
+            "jpg" | "jpeg" => Some(RenderFormat::Jpeg),
+            "webp" => Some(RenderFormat::Webp),
+            _ => None,
+        }
+    }
+}
+
+impl From<RenderFormat> for ImageFormat {
+    fn from(format: RenderFormat) -> Self {
+        match format {
+            RenderFormat::Png => ImageFormat::Png,
+            RenderFormat::Jpeg => ImageFormat::Jpeg,
+            RenderFormat::Webp => ImageFormat::WebP,
+        }
+    }
+}
+
+
+/// 画布渲染选项
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RenderOptions {
+    pub canvas_width: u32,
+    pub canvas_height: u32,
+    pub canvas_color: Color,
+    #[serde(default)]
+    pub format: RenderFormat,
+}
+
+impl RenderOptions {
+    pub fn new(canvas_width: u32, canvas_height: u32, canvas_color: Color) -> Self {
+        Self {
+            canvas_width,
+            canvas_height,
+            canvas_color,
+            format: RenderFormat::default(),
+        }
+    }
+
+    pub fn with_format(mut self, format: RenderFormat) -> Self {
+        self.format = format;
+        self
+    }
+}
+
+
+/// 在内存中渲染带有格子的画布；未设置fill_color/border_color的格子不绘制对应部分
+pub fn render_canvas_with_grids(grids: &[Grid], options: &RenderOptions) -> Result<RgbaImage> {
     // 创建一个新的空白画布
-    let mut img = ImageBuffer::from_pixel(canvas_width,  canvas_height, canvas_color);
-    
+    let mut img = ImageBuffer::from_pixel(options.canvas_width, options.canvas_height, options.canvas_color.into());
+
     // 填充格子
-    for grid in &grids {
-        let mut points: Vec<imageproc::point::Point<i32>> = Vec::with_capacity(grid.points.len());
-        for point in &grid.points {
-            points.push(imageproc::point::Point {
-                x: point.x as i32,
-                y: point.y as i32,
-            });
-        }
-        // println!("正在绘制格子：{:?}", grid);
-        let fill_color = grid.ext.fill_color.expect("Each grid should have an fill_color when drawing.");
+    for grid in grids {
+        let Some(fill_color) = grid.ext.fill_color else {
+            continue;
+        };
+        let points = outline(grid.points.iter().map(|point| imageproc::point::Point {
+            x: point.x.round() as i32,
+            y: point.y.round() as i32,
+        }));
+        // 裁剪出的细长格子取整后可能退化为线段或点
+        if points.len() < 3 {
+            continue;
+        }
         draw_polygon_mut(&mut img, &points, fill_color.into());
-
     }
 
     // 画格子的边框
-    for grid in &grids  {
-        let mut points: Vec<imageproc::point::Point<f32>> = Vec::with_capacity(grid.points.len());
-        for point in &grid.points {
-            points.push(imageproc::point::Point {
-                x: point.x as f32,
-                y: point.y as f32,
-            });
+    for grid in grids {
+        let Some(border_color) = grid.ext.border_color else {
+            continue;
+        };
+        let points = outline(grid.points.iter().map(|point| imageproc::point::Point {
+            x: point.x,
+            y: point.y,
+        }));
+        if points.len() < 2 {
+            continue;
+        }
+        draw_hollow_polygon_mut(&mut img, &points, border_color.into());
+    }
+
+    Ok(img)
+}
+
+/// 去掉相邻的重复顶点及与第一个顶点重合的末尾顶点，imageproc要求多边形的首尾顶点不同
+fn outline<T: PartialEq>(points: impl Iterator<Item = T>) -> Vec<T> {
+    let mut points: Vec<T> = points.collect();
+    points.dedup();
+    while points.len() > 1 && points.first() == points.last() {
+        points.pop();
+    }
+    points
+}
+
+
+/// 将top图片按指定不透明度(0~1)叠加到base图片的(x, y)位置，超出base的部分忽略
+pub fn overlay_image(base: &mut RgbaImage, top: &RgbaImage, x: u32, y: u32, opacity: f32) {
+    let opacity = opacity.clamp(0.0, 1.0);
+    for (tx, ty, pixel) in top.enumerate_pixels() {
+        let (bx, by) = (x + tx, y + ty);
+        if bx >= base.width() || by >= base.height() {
+            continue;
+        }
+        // 源像素自身的透明度与整体不透明度叠加
+        let alpha = opacity * pixel[3] as f32 / 255.0;
+        let dst = base.get_pixel_mut(bx, by);
+        for c in 0..3 {
+            dst[c] = (pixel[c] as f32 * alpha + dst[c] as f32 * (1.0 - alpha)).round() as u8;
+        }
+        dst[3] = dst[3].max((alpha * 255.0).round() as u8);
+    }
+}
+
+
+/// 将两张图片左右并排拼接，空白处用background填充
+pub fn concat_side_by_side(left: &RgbaImage, right: &RgbaImage, background: Color) -> RgbaImage {
+    let width = left.width() + right.width();
+    let height = left.height().max(right.height());
+    let mut img = ImageBuffer::from_pixel(width, height, background.into());
+    overlay_image(&mut img, left, 0, 0, 1.0);
+    overlay_image(&mut img, right, left.width(), 0, 1.0);
+    img
+}
+
+
+/// 将图片编码为指定格式的字节流（jpeg不支持透明通道，会先转为rgb）
+pub fn encode_image(img: &RgbaImage, format: RenderFormat) -> Result<Vec<u8>> {
+    let mut buf = Cursor::new(Vec::new());
+    match format {
+        RenderFormat::Jpeg => {
+            let rgb = DynamicImage::ImageRgba8(img.clone()).to_rgb8();
+            rgb.write_to(&mut buf, format.into())?;
+        },
+        _ => img.write_to(&mut buf, format.into())?,
+    }
+    Ok(buf.into_inner())
+}
+
+
+/// 渲染带有格子的画布，并按options中的格式编码
+pub fn render_canvas_to_bytes(grids: &[Grid], options: &RenderOptions) -> Result<Vec<u8>> {
+    let img = render_canvas_with_grids(grids, options)?;
+    encode_image(&img, options.format)
+}
+
+
+/// 将带有格子的画布渲染为SVG文本；未设置fill_color/border_color的格子不绘制对应部分
+pub fn render_canvas_to_svg(grids: &[Grid], options: &RenderOptions) -> String {
+    let (width, height) = (options.canvas_width, options.canvas_height);
+    let mut svg = String::with_capacity(grids.len() * 96 + 256);
+    svg.push_str(&format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}">"#));
+    svg.push_str(&format!(r#"<rect width="{width}" height="{height}"{}/>"#, svg_paint("fill", options.canvas_color)));
+    for grid in grids {
+        if grid.ext.fill_color.is_none() && grid.ext.border_color.is_none() {
+            continue;
         }
-        let grid_border_color = grid.ext.border_color.expect("Each grid should have an border_color when drawing.");
-        draw_hollow_polygon_mut(&mut img, &points, grid_border_color.into());
+        let points: Vec<String> = grid.points.iter().map(|p| format!("{},{}", p.x, p.y)).collect();
+        let fill = grid.ext.fill_color.map(|c| svg_paint("fill", c)).unwrap_or_else(|| r#" fill="none""#.to_string());
+        let stroke = grid.ext.border_color.map(|c| svg_paint("stroke", c)).unwrap_or_default();
+        svg.push_str(&format!(r#"<polygon points="{}"{}{}/>"#, points.join(" "), fill, stroke));
     }
+    svg.push_str("</svg>");
+    svg
+}
+
+/// SVG的颜色属性，透明度单独输出为*-opacity属性
+fn svg_paint(attr: &str, color: Color) -> String {
+    let alpha = color.to_rgba().3;
+    if alpha == 255 {
+        format!(r#" {}="{}""#, attr, color.to_rgb_string())
+    } else {
+        format!(r#" {}="{}" {}-opacity="{:.3}""#, attr, color.to_rgb_string(), attr, alpha as f32 / 255.0)
+    }
+}
+
 
-    // 保存图像
-    img.save(path)?;
-    
+/// 画带有格子的画布，并保存到指定路径；按扩展名输出png/jpeg/webp/svg，没有扩展名时使用options中的格式
+pub fn draw_canvas_with_grids(
+    grids: &[Grid],
+    options: &RenderOptions,
+    path: PathBuf,
+) -> Result<()> {
+    let bytes = match path.extension().and_then(|e| e.to_str()) {
+        None => render_canvas_to_bytes(grids, options)?,
+        Some(ext) if ext.eq_ignore_ascii_case("svg") => render_canvas_to_svg(grids, options).into_bytes(),
+        Some(ext) => {
+            let format = RenderFormat::from_extension(ext)
+                .ok_or_else(|| ProcessError::invalid_parameter("path", format!("unsupported image extension: {}", ext)))?;
+            render_canvas_to_bytes(grids, &options.with_format(format))?
+        },
+    };
+    std::fs::write(path, bytes)?;
     Ok(())
+}
 
+
+#[cfg(test)]
+#[test]
+fn test_render_rotated_clip_layout() {
+    use crate::{generate_enmty_canvas_grids, GridAlign, GridFillOptions, GridLayout};
+
+    let layout = GridLayout { align: GridAlign::Clip, rotation: 30.0, ..Default::default() };
+    for options in [GridFillOptions::triangle(7, 6), GridFillOptions::rectangle(5, 3)] {
+        let mut grids = generate_enmty_canvas_grids(101, 77, options.with_layout(layout)).unwrap();
+        for grid in &mut grids {
+            grid.ext.fill_color = Some(Color::BLACK);
+            grid.ext.border_color = Some(Color::WHITE);
+        }
+        let img = render_canvas_with_grids(&grids, &RenderOptions::new(101, 77, Color::WHITE)).unwrap();
+        assert_eq!(img.dimensions(), (101, 77));
+    }
 }
 
+
+#[cfg(test)]
+#[test]
+fn test_draw_canvas_format_from_extension() {
+    use crate::{generate_enmty_canvas_grids, GridFillOptions};
+
+    let mut grids = generate_enmty_canvas_grids(40, 30, GridFillOptions::rectangle(10, 10)).unwrap();
+    grids.iter_mut().for_each(|grid| grid.ext.fill_color = Some(Color::BLACK));
+    let options = RenderOptions::new(40, 30, Color::WHITE);
+
+    let dir = std::env::temp_dir().join(format!("logo-draw-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let draw = |name: &str| draw_canvas_with_grids(&grids, &options, dir.join(name)).map(|_| std::fs::read(dir.join(name)).unwrap());
+    let jpeg = draw("out.JPG").unwrap();
+    let webp = draw("out.webp").unwrap();
+    let svg = draw("out.svg").unwrap();
+    let png = draw("out").unwrap();
+    let unsupported = draw("out.gif");
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(image::guess_format(&jpeg).unwrap(), ImageFormat::Jpeg);
+    assert_eq!(image::guess_format(&webp).unwrap(), ImageFormat::WebP);
+    assert_eq!(image::guess_format(&png).unwrap(), ImageFormat::Png);
+    assert!(svg.starts_with(b"<svg"));
+    assert!(matches!(unsupported, Err(ProcessError::InvalidParameter { field: "path", .. })));
+}
@@ -1,23 +1,62 @@
 mod canvas;
 mod image_draw;
+mod grid_color;
+mod palette;
+mod edge;
+mod target_count;
+mod recommend;
+mod cleanup;
+mod pick;
+mod plugin;
+mod progress;
+mod batch;
+mod geometry;
+mod spatial;
 
-pub use image_draw::draw_canvas_with_grids;
-pub use canvas::generate_enmty_canvas_grids;
+pub use image_draw::{
+    concat_side_by_side, draw_canvas_with_grids, encode_image, overlay_image,
+    render_canvas_to_bytes, render_canvas_to_svg, render_canvas_with_grids, RenderFormat, RenderOptions,
+};
+pub use canvas::{
+    check_canvas_grids, generate_enmty_canvas_grids, generate_enmty_canvas_grids_with_graph, ColorIntegral, ForegroundIntegral,
+    GridGraph, LogoIntegrals, MAX_GRID_COUNT, MAX_GRID_SIDE,
+};
+pub use grid_color::{paint_grids, GridColorMode, GridColorOptions};
+pub use palette::{classify_grid_by_palette, extract_palette};
+pub use edge::{pick_grid_edge_aware, EdgeFeatureMaps};
+pub use cleanup::{cleanup_selected_grids, cleanup_selected_grids_with_graph, GridCleanupOptions, GridMorphOp};
+pub use recommend::{calc_iou, recommend_grid_tilings, GridRecommendOptions, GridRecommendation};
+pub use pick::resolve_pick_palettes;
+pub use progress::PickProgress;
+pub use geometry::{Polygon, Rect};
+pub use spatial::GridSpatialIndex;
+pub use batch::{batch_summary_csv, run_batch, BatchImage, BatchOptions, BatchParamMatrix, BatchReport, BatchResultRow};
+pub use plugin::{PickStrategy, PickStrategyFactory, StrategyInfo, StrategyRegistry, Tiling, TilingFactory};
+pub use target_count::{search_grids_for_target_count, TargetCountResult, TargetSearchMode};
 
-use image::{ImageBuffer, Rgba, RgbaImage};
-use anyhow::Result;
+use image::RgbaImage;
+use crate::error::Result;
+use rayon::prelude::*;
 use tracing::debug;
-use crate::{ calc_color_distance, Grid, GridFillOptions, GridPickCmd};
-use canvas::{calc_avg_color_of_grid, calc_remaining_area_ratio_in_grid};
+use crate::{Grid, GridFillOptions, GridPickCmd, ProcessError};
+use pick::{pick_grid, PickContext};
 
 
 
+/// 加载logo图片，统一转换为rgba格式
+pub fn load_logo_image(image_path: &str) -> Result<RgbaImage> {
+    let img = image::open(image_path)
+        .map_err(|source| ProcessError::LoadImage { path: image_path.to_string(), source })?;
+    Ok(img.to_rgba8())
+}
+
+
 pub fn generate_canvas_grids_by_image_path(
     image_path: &str, 
     fill_options: GridFillOptions, 
     pick_strategy: GridPickCmd) -> Result<Vec<Grid>>  {
     // 加载图片，确定画布的宽高
-    let img: ImageBuffer<Rgba<u8>, Vec<u8>> = image::open(image_path).unwrap().to_rgba8();
+    let img = load_logo_image(image_path)?;
     generate_canvas_grids_from_logo_image(&img, fill_options, pick_strategy)
 }
 
@@ -33,16 +72,55 @@ pub fn generate_canvas_grids_from_logo_image(
 
     debug!("generate_canvas_grids_from_logo_image, image width: {:?}, height: {:?}", img.width(), img.height());
     
+    generate_canvas_grids_with_integrals(img, &LogoIntegrals::new(), fill_options, pick_strategy)
+}
+
+
+/// 每处理完该数量的格子，更新一次进度并检查是否已取消
+const PROGRESS_CHUNK_SIZE: usize = 512;
+
+
+/// 复用logo的积分图生成马赛克格子信息，适用于同一张logo多次调整参数的场景
+pub fn generate_canvas_grids_with_integrals(
+    img: &RgbaImage,
+    integrals: &LogoIntegrals,
+    fill_options: GridFillOptions,
+    pick_strategy: GridPickCmd) -> Result<Vec<Grid>> {
+
+    generate_canvas_grids_with_progress(img, integrals, fill_options, pick_strategy, &PickProgress::new())
+}
+
+
+/// 生成马赛克格子信息，并通过progress报告选取进度；progress被取消时返回错误
+pub fn generate_canvas_grids_with_progress(
+    img: &RgbaImage,
+    integrals: &LogoIntegrals,
+    fill_options: GridFillOptions,
+    pick_strategy: GridPickCmd,
+    progress: &PickProgress) -> Result<Vec<Grid>> {
+
+    let mut grids = generate_enmty_canvas_grids(img.width(), img.height(), fill_options)?;
+    pick_grids_by_strategy(img, integrals, &mut grids, pick_strategy, progress)?;
+    Ok(grids)
+}
+
+
+/// 使用任意铺排方式和选取策略（包括注册的自定义实现），生成logo对应的马赛克格子信息
+pub fn generate_canvas_grids_with(
+    img: &RgbaImage,
+    tiling: &dyn Tiling,
+    pick_strategy: &dyn PickStrategy) -> Result<Vec<Grid>> {
+
     // 根据画布填充参数（填充的图形形状及尺寸），grid选择策略，生成画布上的grid信息
     let canvas_width = img.width();
     let canvas_height = img.height();
-    let mut grids = generate_enmty_canvas_grids(canvas_width, canvas_height, fill_options)?;
+    let mut grids = tiling.generate_grids(canvas_width, canvas_height)?;
 
     debug!("generate_enmty_canvas_grids, grids: {:?}", grids);
 
     // 根据指定的格子选取策略，从画布上挑选出拼接成logo的所有图形
     
-    pick_grids_by_strategy(&img, &mut grids, pick_strategy)?;
+    pick_strategy.pick_grids(img, &mut grids)?;
     
     Ok(grids)
 }
@@ -51,30 +129,31 @@ pub fn generate_canvas_grids_from_logo_image(
 
 fn pick_grids_by_strategy(
     img: &RgbaImage, 
-    grids: &mut Vec<Grid>, 
-    pick_strategy: GridPickCmd) -> Result<()> {
-
-    for grid in grids {
-        match pick_strategy {
-            GridPickCmd::AvgColorCompare(param) => {
-                // fill_avg_color(img, grids)
-                let avg_color = calc_avg_color_of_grid(img, grid)?;
-                grid.ext.avg_color = Some(avg_color);
-                // 计算差值
-                let distance = calc_color_distance(avg_color.to_rgb(), param.color.to_rgb());
-                grid.ext.color_distance = Some(distance);
-                let selected = distance >= param.min_distance && distance <= param.max_distance;
-                grid.ext.selected = Some(selected);
-
-            },
-            GridPickCmd::EliminateBgColor(param) => {
-                let remaining_area_ratio = calc_remaining_area_ratio_in_grid(img, grid, param.color)?;
-                grid.ext.remaining_area_ratio = Some(remaining_area_ratio);
-                let selected =  remaining_area_ratio >= param.min_remaining_ratio;
-                grid.ext.selected = Some(selected);
-            },
+    integrals: &LogoIntegrals,
+    grids: &mut [Grid], 
+    mut pick_strategy: GridPickCmd,
+    progress: &PickProgress) -> Result<()> {
+
+    // 未指定调色板时，从logo中自动提取
+    resolve_pick_palettes(img, &mut pick_strategy);
+    let ctx = PickContext::new(img, integrals, &pick_strategy);
+
+    // 各格子相互独立，分批并行计算
+    progress.start(grids.len());
+    grids.par_chunks_mut(PROGRESS_CHUNK_SIZE).try_for_each(|chunk| {
+        if progress.is_cancelled() {
+            return Err(ProcessError::Cancelled);
+        }
+        for grid in chunk.iter_mut() {
+            grid.ext.pick_scores = None;
+            let (selected, _) = pick_grid(img, grid, &pick_strategy, &ctx)?;
+            grid.ext.selected = Some(selected);
+            if !selected {
+                grid.ext.palette_index = None;
+            }
         }
-    }
-    Ok(())
+        progress.advance(chunk.len());
+        Ok(())
+    })
     
 }
@@ -0,0 +1,128 @@
+use crate::error::Result;
+use image::RgbaImage;
+use crate::{find_nearest_color, utils::is_bg_pixel, Color, Grid, PaletteClassifyParam, ProcessError};
+use super::canvas::for_each_pixel_in_grid;
+
+/// 提取调色板时最多采样的像素数
+const MAX_SAMPLE_PIXELS: usize = 100_000;
+
+
+/// 使用中位切分(median-cut)算法，从logo的前景像素中提取至多palette_size个主色，按像素数量降序排列
+pub fn extract_palette(img: &RgbaImage, bg_color: Color, palette_size: usize) -> Vec<Color> {
+    let bg = bg_color.to_rgb();
+    let total = (img.width() * img.height()) as usize;
+    let step = total.div_ceil(MAX_SAMPLE_PIXELS).max(1);
+
+    let pixels: Vec<[u8; 3]> = img.pixels()
+        .step_by(step)
+        // 忽略透明像素和背景像素
+        .filter(|p| p[3] >= 128 && !is_bg_pixel((p[0], p[1], p[2]), bg))
+        .map(|p| [p[0], p[1], p[2]])
+        .collect();
+    if pixels.is_empty() || palette_size == 0 {
+        return Vec::new();
+    }
+
+    let mut boxes = vec![pixels];
+    while boxes.len() < palette_size {
+        // 选取色值跨度最大的盒子进行切分
+        let Some((index, channel, range)) = boxes.iter().enumerate()
+            .filter(|(_, b)| b.len() > 1)
+            .map(|(i, b)| {
+                let (channel, range) = widest_channel(b);
+                (i, channel, range)
+            })
+            .max_by_key(|(_, _, range)| *range)
+        else {
+            break;
+        };
+        if range == 0 {
+            break;
+        }
+        let mut target = boxes.swap_remove(index);
+        target.sort_unstable_by_key(|p| p[channel]);
+        let upper = target.split_off(target.len() / 2);
+        boxes.push(target);
+        boxes.push(upper);
+    }
+
+    boxes.sort_by_key(|b| std::cmp::Reverse(b.len()));
+    boxes.iter().map(|b| average_color(b)).collect()
+}
+
+
+/// 按调色板对格子分类：统计格子内每个像素最接近的调色板颜色，
+/// 前景像素占比达到min_remaining_ratio时选中格子，并记录占比最多的调色板下标
+pub fn classify_grid_by_palette(img: &RgbaImage, grid: &mut Grid, palette: &[Color], param: &PaletteClassifyParam) -> Result<()> {
+    let bg = param.bg_color.to_rgb();
+    let mut votes = vec![0u32; palette.len()];
+    let mut total_pixel_count = 0u32;
+
+    for_each_pixel_in_grid(img, grid, |_, _, pixel| {
+        total_pixel_count += 1;
+        if is_bg_pixel((pixel[0], pixel[1], pixel[2]), bg) {
+            return;
+        }
+        if let Some(i) = find_nearest_color(Color::from_rgb((pixel[0], pixel[1], pixel[2])), palette) {
+            votes[i] += 1;
+        }
+    })?;
+
+    if total_pixel_count == 0 {
+        return Err(ProcessError::EmptyGrid(grid.seq.clone()));
+    }
+
+    let foreground_count: u32 = votes.iter().sum();
+    let ratio = foreground_count as f32 / total_pixel_count as f32;
+    let selected = foreground_count > 0 && ratio >= param.min_remaining_ratio;
+    grid.ext.remaining_area_ratio = Some(ratio);
+    grid.ext.selected = Some(selected);
+    grid.ext.palette_index = if selected {
+        votes.iter().enumerate().max_by_key(|(_, v)| **v).map(|(i, _)| i)
+    } else {
+        None
+    };
+    Ok(())
+}
+
+
+fn widest_channel(pixels: &[[u8; 3]]) -> (usize, u8) {
+    let mut min = [u8::MAX; 3];
+    let mut max = [u8::MIN; 3];
+    for p in pixels {
+        for c in 0..3 {
+            min[c] = min[c].min(p[c]);
+            max[c] = max[c].max(p[c]);
+        }
+    }
+    (0..3).map(|c| (c, max[c] - min[c])).max_by_key(|(_, r)| *r).unwrap()
+}
+
+
+fn average_color(pixels: &[[u8; 3]]) -> Color {
+    let mut sum = [0u64; 3];
+    for p in pixels {
+        for c in 0..3 {
+            sum[c] += p[c] as u64;
+        }
+    }
+    let n = pixels.len() as u64;
+    Color::from_rgb(((sum[0] / n) as u8, (sum[1] / n) as u8, (sum[2] / n) as u8))
+}
+
+
+#[cfg(test)]
+#[test]
+fn test_extract_palette_two_colors() {
+    let mut img = RgbaImage::from_pixel(20, 10, image::Rgba([255, 255, 255, 255]));
+    for y in 0..10 {
+        for x in 0..5 {
+            img.put_pixel(x, y, image::Rgba([200, 0, 0, 255]));
+            img.put_pixel(x + 10, y, image::Rgba([0, 0, 200, 255]));
+        }
+    }
+    let palette = extract_palette(&img, Color::from_rgb((255, 255, 255)), 4);
+    assert_eq!(palette.len(), 2);
+    assert!(palette.iter().any(|c| c.to_rgb() == (200, 0, 0)));
+    assert!(palette.iter().any(|c| c.to_rgb() == (0, 0, 200)));
+}
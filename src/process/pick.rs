@@ -0,0 +1,200 @@
+use std::{collections::HashMap, sync::Arc};
+use crate::error::Result;
+use image::RgbaImage;
+use crate::{calc_color_distance, Color, Grid, GridPickCmd, GridPickScore, GridPickStrategy};
+use super::canvas::{calc_avg_color_of_grid, calc_remaining_area_ratio_in_grid, ColorIntegral, ForegroundIntegral, LogoIntegrals};
+use super::{classify_grid_by_palette, extract_palette, pick_grid_edge_aware, EdgeFeatureMaps};
+
+
+/// 选取过程中按整张logo预先计算的数据
+#[derive(Default)]
+pub(super) struct PickContext {
+    // 边缘感知策略的特征图，按背景色缓存
+    edge_maps: HashMap<(u8, u8, u8), EdgeFeatureMaps>,
+    // 平均色值比较策略使用的积分图
+    color_integral: Option<Arc<ColorIntegral>>,
+    // 剔除背景色策略使用的积分图，按背景色缓存
+    foreground_integrals: HashMap<(u8, u8, u8), Arc<ForegroundIntegral>>,
+}
+
+impl PickContext {
+    /// 遍历选取命令，预先计算(或从integrals中取出)各策略需要的特征图和积分图
+    pub(super) fn new(img: &RgbaImage, integrals: &LogoIntegrals, pick_cmd: &GridPickCmd) -> Self {
+        let mut ctx = Self::default();
+        ctx.prepare(img, integrals, pick_cmd);
+        ctx
+    }
+
+    fn prepare(&mut self, img: &RgbaImage, integrals: &LogoIntegrals, pick_cmd: &GridPickCmd) {
+        match pick_cmd {
+            GridPickCmd::AvgColorCompare(_) => {
+                self.color_integral.get_or_insert_with(|| integrals.color(img));
+            },
+            GridPickCmd::EliminateBgColor(param) => {
+                self.foreground_integrals.entry(param.color.to_rgb())
+                    .or_insert_with(|| integrals.foreground(img, param.color));
+            },
+            GridPickCmd::EdgeAware(param) => {
+                self.edge_maps.entry(param.bg_color.to_rgb())
+                    .or_insert_with(|| EdgeFeatureMaps::new(img, param.bg_color));
+            },
+            GridPickCmd::All(cmds) | GridPickCmd::Any(cmds) => {
+                cmds.iter().for_each(|cmd| self.prepare(img, integrals, cmd));
+            },
+            GridPickCmd::Not(cmd) => self.prepare(img, integrals, cmd),
+            GridPickCmd::Weighted(param) => {
+                param.items.iter().for_each(|item| self.prepare(img, integrals, &item.cmd));
+            },
+            GridPickCmd::PaletteClassify(_) => {},
+        }
+    }
+}
+
+
+/// 为选取命令中未指定调色板的调色板分类策略，从logo中提取调色板；返回第一个调色板分类策略使用的调色板
+pub fn resolve_pick_palettes(img: &RgbaImage, pick_cmd: &mut GridPickCmd) -> Option<Vec<Color>> {
+    match pick_cmd {
+        GridPickCmd::PaletteClassify(param) => {
+            if param.palette.is_empty() {
+                param.palette = extract_palette(img, param.bg_color, param.palette_size);
+            }
+            Some(param.palette.clone())
+        },
+        GridPickCmd::All(cmds) | GridPickCmd::Any(cmds) => {
+            cmds.iter_mut().fold(None, |first, cmd| {
+                let palette = resolve_pick_palettes(img, cmd);
+                first.or(palette)
+            })
+        },
+        GridPickCmd::Not(cmd) => resolve_pick_palettes(img, cmd),
+        GridPickCmd::Weighted(param) => {
+            param.items.iter_mut().fold(None, |first, item| {
+                let palette = resolve_pick_palettes(img, &mut item.cmd);
+                first.or(palette)
+            })
+        },
+        _ => None,
+    }
+}
+
+
+/// 按选取命令判断单个格子是否选中，返回(是否选中, 匹配度)；
+/// 各基础策略的匹配度依次记录到grid.ext.pick_scores
+pub(super) fn pick_grid(img: &RgbaImage, grid: &mut Grid, pick_cmd: &GridPickCmd, ctx: &PickContext) -> Result<(bool, f32)> {
+    let (strategy, score) = match pick_cmd {
+        GridPickCmd::AvgColorCompare(param) => {
+            let avg_color = match &ctx.color_integral {
+                Some(integral) => integral.avg_color_of_grid(grid)?,
+                None => calc_avg_color_of_grid(img, grid)?,
+            };
+            grid.ext.avg_color = Some(avg_color);
+            // 计算差值
+            let distance = calc_color_distance(avg_color.to_rgb(), param.color.to_rgb());
+            grid.ext.color_distance = Some(distance);
+            let selected = distance >= param.min_distance && distance <= param.max_distance;
+            grid.ext.selected = Some(selected);
+            // 色差落在区间内时匹配度为1，区间外按偏离程度递减
+            let gap = (param.min_distance - distance).max(distance - param.max_distance).max(0.0);
+            (GridPickStrategy::AvgColorCompare, 1.0 - gap / 100.0)
+        },
+        GridPickCmd::EliminateBgColor(param) => {
+            let remaining_area_ratio = match ctx.foreground_integrals.get(&param.color.to_rgb()) {
+                Some(integral) => integral.remaining_area_ratio_of_grid(grid)?,
+                None => calc_remaining_area_ratio_in_grid(img, grid, param.color)?,
+            };
+            grid.ext.remaining_area_ratio = Some(remaining_area_ratio);
+            let selected =  remaining_area_ratio >= param.min_remaining_ratio;
+            grid.ext.selected = Some(selected);
+            (GridPickStrategy::EliminateBgColor, remaining_area_ratio)
+        },
+        GridPickCmd::PaletteClassify(param) => {
+            classify_grid_by_palette(img, grid, &param.palette, param)?;
+            (GridPickStrategy::PaletteClassify, grid.ext.remaining_area_ratio.unwrap_or(0.0))
+        },
+        GridPickCmd::EdgeAware(param) => {
+            let maps = ctx.edge_maps.get(&param.bg_color.to_rgb())
+                .ok_or_else(|| anyhow::anyhow!("edge feature maps are not prepared"))?;
+            pick_grid_edge_aware(img, grid, maps, param)?;
+            // 因细笔画选中的格子匹配度为1
+            let score = if grid.ext.selected.unwrap_or(false) {
+                1.0
+            } else {
+                grid.ext.remaining_area_ratio.unwrap_or(0.0)
+            };
+            (GridPickStrategy::EdgeAware, score)
+        },
+        GridPickCmd::All(cmds) => {
+            let mut selected = true;
+            let mut score = 1f32;
+            for cmd in cmds {
+                let (s, v) = pick_grid(img, grid, cmd, ctx)?;
+                selected &= s;
+                score = score.min(v);
+            }
+            return Ok((selected, score));
+        },
+        GridPickCmd::Any(cmds) => {
+            let mut selected = false;
+            let mut score = 0f32;
+            for cmd in cmds {
+                let (s, v) = pick_grid(img, grid, cmd, ctx)?;
+                selected |= s;
+                score = score.max(v);
+            }
+            return Ok((selected, score));
+        },
+        GridPickCmd::Not(cmd) => {
+            let (selected, score) = pick_grid(img, grid, cmd, ctx)?;
+            return Ok((!selected, 1.0 - score));
+        },
+        GridPickCmd::Weighted(param) => {
+            let mut weighted_sum = 0f32;
+            let mut weight_sum = 0f32;
+            for item in &param.items {
+                let (_, v) = pick_grid(img, grid, &item.cmd, ctx)?;
+                weighted_sum += item.weight * v;
+                weight_sum += item.weight;
+            }
+            let score = if weight_sum > 0.0 { weighted_sum / weight_sum } else { 0.0 };
+            return Ok((score >= param.min_score, score));
+        },
+    };
+
+    let selected = grid.ext.selected.unwrap_or(false);
+    let score = score.clamp(0.0, 1.0);
+    grid.ext.pick_scores.get_or_insert_with(Vec::new).push(GridPickScore { strategy, score, selected });
+    Ok((selected, score))
+}
+
+
+#[cfg(test)]
+#[test]
+fn test_pick_grid_composite() {
+    use crate::{generate_enmty_canvas_grids, AvgColorCompareParam, EliminateBgColorParam, GridFillOptions, WeightedPickItem, WeightedPickParam};
+
+    // 左半边为红色，右半边为白色
+    let img = RgbaImage::from_fn(20, 10, |x, _| {
+        if x < 10 { image::Rgba([255, 0, 0, 255]) } else { image::Rgba([255, 255, 255, 255]) }
+    });
+    let grids = generate_enmty_canvas_grids(20, 10, GridFillOptions::rectangle(10, 10)).unwrap();
+    let not_bg = GridPickCmd::EliminateBgColor(EliminateBgColorParam { color: Color::WHITE, min_remaining_ratio: 0.5 });
+    let near_white = GridPickCmd::AvgColorCompare(AvgColorCompareParam { color: Color::WHITE, min_distance: 0.0, max_distance: 10.0 });
+
+    let run = |cmd: GridPickCmd| -> Vec<bool> {
+        let ctx = PickContext::new(&img, &LogoIntegrals::new(), &cmd);
+        grids.iter().map(|g| {
+            let mut grid = g.clone();
+            pick_grid(&img, &mut grid, &cmd, &ctx).unwrap().0
+        }).collect()
+    };
+
+    assert_eq!(run(GridPickCmd::All(vec![not_bg.clone(), GridPickCmd::Not(Box::new(near_white.clone()))])), vec![true, false]);
+    assert_eq!(run(GridPickCmd::Any(vec![not_bg.clone(), near_white.clone()])), vec![true, true]);
+    assert_eq!(run(GridPickCmd::Weighted(WeightedPickParam {
+        items: vec![
+            WeightedPickItem { cmd: not_bg, weight: 1.0 },
+            WeightedPickItem { cmd: near_white, weight: 3.0 },
+        ],
+        min_score: 0.5,
+    })), vec![false, true]);
+}
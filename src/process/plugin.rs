@@ -0,0 +1,181 @@
+use std::sync::Arc;
+use crate::error::Result;
+use dashmap::DashMap;
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::{Grid, GridFillOptions, GridLayout, GridPickCmd, ProcessError};
+use super::{generate_enmty_canvas_grids, pick_grids_by_strategy, LogoIntegrals, PickProgress};
+
+
+/// 画布铺排方式：在画布上生成空白格子
+pub trait Tiling: Send + Sync {
+    fn generate_grids(&self, canvas_width: u32, canvas_height: u32) -> Result<Vec<Grid>>;
+
+    /// 按形状和尺寸铺排时的参数，调用方据此在生成格子前校验格子尺寸和数量
+    fn fill_options(&self) -> Option<GridFillOptions> {
+        None
+    }
+}
+
+/// 格子选取策略：根据logo图片设置每个格子的选中状态
+pub trait PickStrategy: Send + Sync {
+    fn pick_grids(&self, img: &RgbaImage, grids: &mut [Grid]) -> Result<()>;
+}
+
+impl Tiling for GridFillOptions {
+    fn generate_grids(&self, canvas_width: u32, canvas_height: u32) -> Result<Vec<Grid>> {
+        generate_enmty_canvas_grids(canvas_width, canvas_height, *self)
+    }
+
+    fn fill_options(&self) -> Option<GridFillOptions> {
+        Some(*self)
+    }
+}
+
+impl PickStrategy for GridPickCmd {
+    fn pick_grids(&self, img: &RgbaImage, grids: &mut [Grid]) -> Result<()> {
+        pick_grids_by_strategy(img, &LogoIntegrals::new(), grids, self.clone(), &PickProgress::new())
+    }
+}
+
+
+/// 根据json参数创建铺排方式
+pub type TilingFactory = Arc<dyn Fn(&Value) -> Result<Box<dyn Tiling>> + Send + Sync>;
+/// 根据json参数创建选取策略
+pub type PickStrategyFactory = Arc<dyn Fn(&Value) -> Result<Box<dyn PickStrategy>> + Send + Sync>;
+
+
+/// 已注册的铺排方式或选取策略
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StrategyInfo {
+    pub name: String,
+    pub description: String,
+}
+
+struct RegistryEntry<F> {
+    description: String,
+    factory: F,
+}
+
+
+/// 按名称注册铺排方式和选取策略，下游可在启动时注册自定义实现
+#[derive(Clone, Default)]
+pub struct StrategyRegistry {
+    tilings: Arc<DashMap<String, RegistryEntry<TilingFactory>>>,
+    pick_strategies: Arc<DashMap<String, RegistryEntry<PickStrategyFactory>>>,
+}
+
+/// 内置铺排方式的参数
+#[derive(Debug, Deserialize)]
+struct GridSizeParams {
+    width: u32,
+    height: u32,
+    #[serde(default)]
+    layout: GridLayout,
+}
+
+impl StrategyRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 创建注册了内置铺排方式和选取策略的注册表
+    pub fn with_builtins() -> Self {
+        let registry = Self::new();
+        registry.register_tiling("triangle", "三角形铺排，参数：{width, height, layout?}", Arc::new(|params| {
+            let size: GridSizeParams = serde_json::from_value(params.clone())?;
+            Ok(Box::new(GridFillOptions::triangle(size.width, size.height).with_layout(size.layout)))
+        }));
+        registry.register_tiling("rectangle", "矩形铺排，参数：{width, height, layout?}", Arc::new(|params| {
+            let size: GridSizeParams = serde_json::from_value(params.clone())?;
+            Ok(Box::new(GridFillOptions::rectangle(size.width, size.height).with_layout(size.layout)))
+        }));
+
+        let builtin_pick_strategies = [
+            ("AvgColorCompare", "区域平均色值比较"),
+            ("EliminateBgColor", "剔除背景色后按剩余像素占比选取"),
+            ("PaletteClassify", "按调色板对格子分类"),
+            ("EdgeAware", "剔除背景色并保留细笔画穿过的格子"),
+            ("All", "所有子命令都选中时选中"),
+            ("Any", "任一子命令选中时选中"),
+            ("Not", "子命令未选中时选中"),
+            ("Weighted", "子命令匹配度加权平均"),
+        ];
+        for (name, description) in builtin_pick_strategies {
+            // 参数与GridPickCmd对应变体的序列化格式一致
+            registry.register_pick_strategy(name, description, Arc::new(move |params| {
+                let cmd: GridPickCmd = serde_json::from_value(serde_json::json!({ name: params }))?;
+                Ok(Box::new(cmd))
+            }));
+        }
+        registry
+    }
+
+    /// 注册铺排方式，同名时覆盖
+    pub fn register_tiling(&self, name: &str, description: &str, factory: TilingFactory) {
+        self.tilings.insert(name.to_string(), RegistryEntry { description: description.to_string(), factory });
+    }
+
+    /// 注册选取策略，同名时覆盖
+    pub fn register_pick_strategy(&self, name: &str, description: &str, factory: PickStrategyFactory) {
+        self.pick_strategies.insert(name.to_string(), RegistryEntry { description: description.to_string(), factory });
+    }
+
+    pub fn create_tiling(&self, name: &str, params: &Value) -> Result<Box<dyn Tiling>> {
+        let entry = self.tilings.get(name)
+            .ok_or_else(|| ProcessError::NotRegistered { kind: "tiling", name: name.to_string() })?;
+        (entry.factory)(params)
+    }
+
+    pub fn create_pick_strategy(&self, name: &str, params: &Value) -> Result<Box<dyn PickStrategy>> {
+        let entry = self.pick_strategies.get(name)
+            .ok_or_else(|| ProcessError::NotRegistered { kind: "pickStrategy", name: name.to_string() })?;
+        (entry.factory)(params)
+    }
+
+    /// 已注册的铺排方式，按名称排序
+    pub fn tilings(&self) -> Vec<StrategyInfo> {
+        sorted_infos(self.tilings.iter().map(|e| (e.key().clone(), e.value().description.clone())))
+    }
+
+    /// 已注册的选取策略，按名称排序
+    pub fn pick_strategies(&self) -> Vec<StrategyInfo> {
+        sorted_infos(self.pick_strategies.iter().map(|e| (e.key().clone(), e.value().description.clone())))
+    }
+}
+
+fn sorted_infos(entries: impl Iterator<Item = (String, String)>) -> Vec<StrategyInfo> {
+    let mut infos: Vec<StrategyInfo> = entries.map(|(name, description)| StrategyInfo { name, description }).collect();
+    infos.sort_by(|a, b| a.name.cmp(&b.name));
+    infos
+}
+
+
+#[cfg(test)]
+#[test]
+fn test_registry_custom_tiling() {
+    // 自定义铺排：整张画布只有一个矩形格子
+    struct SingleCell;
+    impl Tiling for SingleCell {
+        fn generate_grids(&self, canvas_width: u32, canvas_height: u32) -> Result<Vec<Grid>> {
+            generate_enmty_canvas_grids(canvas_width, canvas_height, GridFillOptions::rectangle(canvas_width, canvas_height))
+        }
+    }
+
+    let registry = StrategyRegistry::with_builtins();
+    registry.register_tiling("single", "整张画布一个格子", Arc::new(|_| Ok(Box::new(SingleCell))));
+    assert!(registry.tilings().iter().any(|t| t.name == "single"));
+
+    let tiling = registry.create_tiling("single", &Value::Null).unwrap();
+    assert_eq!(tiling.generate_grids(40, 30).unwrap().len(), 1);
+
+    let picker = registry.create_pick_strategy(
+        "EliminateBgColor", &serde_json::json!({"color": [255, 255, 255, 255], "min_remaining_ratio": 0.5})).unwrap();
+    let img = RgbaImage::from_pixel(40, 30, image::Rgba([0, 0, 0, 255]));
+    let mut grids = tiling.generate_grids(40, 30).unwrap();
+    picker.pick_grids(&img, &mut grids).unwrap();
+    assert_eq!(grids[0].ext.selected, Some(true));
+    assert!(registry.create_tiling("hexagon", &Value::Null).is_err());
+}
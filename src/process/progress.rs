@@ -0,0 +1,54 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// 格子选取进度及取消标记，可在多个线程间共享
+#[derive(Debug, Default)]
+pub struct PickProgress {
+    // 本轮选取的格子总数
+    total: AtomicUsize,
+    // 本轮已处理的格子数
+    done: AtomicUsize,
+    // 已完成的选取轮数（按目标格子数搜索时会多次选取）
+    passes: AtomicUsize,
+    cancelled: AtomicBool,
+}
+
+impl PickProgress {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 开始新一轮选取
+    pub(crate) fn start(&self, total: usize) {
+        self.done.store(0, Ordering::Relaxed);
+        self.total.store(total, Ordering::Relaxed);
+    }
+
+    pub(crate) fn advance(&self, count: usize) {
+        let done = self.done.fetch_add(count, Ordering::Relaxed) + count;
+        if done >= self.total.load(Ordering::Relaxed) {
+            self.passes.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// 本轮选取的完成百分比，范围[0, 100]
+    pub fn percent(&self) -> f32 {
+        let total = self.total.load(Ordering::Relaxed);
+        if total == 0 {
+            return 0.0;
+        }
+        (self.done.load(Ordering::Relaxed) as f32 / total as f32 * 100.0).min(100.0)
+    }
+
+    pub fn passes(&self) -> usize {
+        self.passes.load(Ordering::Relaxed)
+    }
+
+    /// 请求取消，正在进行的选取在处理完当前一批格子后停止
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
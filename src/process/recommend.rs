@@ -0,0 +1,246 @@
+use crate::error::Result;
+use image::{imageops, GrayImage, Luma, RgbaImage};
+use imageproc::drawing::draw_polygon_mut;
+use crate::{
+    utils::is_bg_pixel, Color, EliminateBgColorParam, Grid,
+    GridFillOptions, GridPickCmd, GridShape,
+};
+use super::{generate_canvas_grids_with_integrals, render_canvas_with_grids, LogoIntegrals, RenderOptions};
+
+/// 未指定目标格子数时，候选的格子宽度
+const DEFAULT_GRID_WIDTHS: [u32; 6] = [12, 16, 20, 28, 36, 48];
+/// 指定目标格子数时，在估算宽度基础上的缩放系数
+const TARGET_WIDTH_FACTORS: [f32; 5] = [0.7, 0.85, 1.0, 1.2, 1.4];
+/// 各形状的候选宽高比（高/宽），第一个为该形状的理想比例
+const TRIANGLE_ASPECTS: [f32; 2] = [0.866, 0.8];
+const RECTANGLE_ASPECTS: [f32; 2] = [1.0, 0.75];
+
+/// 评分权重：还原度、格子数、宽高比
+const IOU_WEIGHT: f32 = 0.6;
+const COUNT_WEIGHT: f32 = 0.3;
+const ASPECT_WEIGHT: f32 = 0.1;
+
+
+/// 格子推荐选项
+#[derive(Debug, Clone)]
+pub struct GridRecommendOptions {
+    // logo背景色
+    pub bg_color: Color,
+    // 目标选中格子数（如预计到场人数）
+    pub target_count: Option<usize>,
+    // 候选的格子形状
+    pub shapes: Vec<GridShape>,
+    // 候选的格子宽度，为空时根据目标格子数估算
+    pub grid_widths: Vec<u32>,
+    // 前景像素的最小占比
+    pub min_remaining_ratio: f32,
+    // 缩略图宽度
+    pub thumbnail_width: u32,
+}
+
+impl GridRecommendOptions {
+    pub fn new(bg_color: Color) -> Self {
+        Self {
+            bg_color,
+            target_count: None,
+            shapes: vec![GridShape::Triangle, GridShape::Rectangle],
+            grid_widths: Vec::new(),
+            min_remaining_ratio: 0.5,
+            thumbnail_width: 160,
+        }
+    }
+}
+
+
+/// 一种候选的格子铺法及其评分
+#[derive(Debug, Clone)]
+pub struct GridRecommendation {
+    pub fill_options: GridFillOptions,
+    pub pick_strategy: GridPickCmd,
+    pub selected_count: usize,
+    // 选中格子掩码与logo前景掩码的交并比
+    pub iou: f32,
+    // 格子宽高比（高/宽）
+    pub aspect_ratio: f32,
+    // 综合评分，范围[0, 1]
+    pub score: f32,
+    pub thumbnail: RgbaImage,
+}
+
+
+/// 评估多种格子形状和尺寸，按综合评分从高到低返回
+pub fn recommend_grid_tilings(img: &RgbaImage, options: &GridRecommendOptions) -> Result<Vec<GridRecommendation>> {
+    let foreground = foreground_mask(img, options.bg_color);
+    let foreground_area = foreground.pixels().filter(|p| p[0] > 0).count();
+
+    let pick_strategy = GridPickCmd::EliminateBgColor(EliminateBgColorParam {
+        color: options.bg_color,
+        min_remaining_ratio: options.min_remaining_ratio,
+    });
+
+    // 所有候选铺法共用同一张logo的积分图
+    let integrals = LogoIntegrals::new();
+    let mut recommendations = Vec::new();
+    for shape in &options.shapes {
+        let aspects = match shape {
+            GridShape::Triangle => TRIANGLE_ASPECTS,
+            GridShape::Rectangle => RECTANGLE_ASPECTS,
+            // 裁剪后的多边形不能用于铺排
+            GridShape::Polygon => continue,
+        };
+        for aspect in aspects {
+            for width in candidate_widths(options, *shape, aspect, foreground_area) {
+                let height = ((width as f32 * aspect).round() as u32).max(2);
+                let fill_options = GridFillOptions::new(*shape, width, height);
+                let grids = generate_canvas_grids_with_integrals(img, &integrals, fill_options, pick_strategy.clone())?;
+                let selected_count = grids.iter().filter(|g| g.ext.selected.unwrap_or(false)).count();
+                let iou = calc_iou(&foreground, &selected_mask(img.width(), img.height(), &grids));
+                let aspect_ratio = height as f32 / width as f32;
+                let score = IOU_WEIGHT * iou
+                    + COUNT_WEIGHT * count_score(selected_count, options.target_count)
+                    + ASPECT_WEIGHT * aspect_score(aspect_ratio, aspects[0]);
+                recommendations.push(GridRecommendation {
+                    fill_options,
+                    pick_strategy: pick_strategy.clone(),
+                    selected_count,
+                    iou,
+                    aspect_ratio,
+                    score,
+                    thumbnail: render_thumbnail(img, grids, options.thumbnail_width)?,
+                });
+            }
+        }
+    }
+
+    recommendations.sort_by(|a, b| b.score.total_cmp(&a.score));
+    Ok(recommendations)
+}
+
+
+/// 计算两个掩码的交并比
+pub fn calc_iou(mask1: &GrayImage, mask2: &GrayImage) -> f32 {
+    let mut intersection = 0usize;
+    let mut union = 0usize;
+    for (p1, p2) in mask1.pixels().zip(mask2.pixels()) {
+        let (a, b) = (p1[0] > 0, p2[0] > 0);
+        if a && b {
+            intersection += 1;
+        }
+        if a || b {
+            union += 1;
+        }
+    }
+    if union == 0 {
+        return 0.0;
+    }
+    intersection as f32 / union as f32
+}
+
+
+fn candidate_widths(options: &GridRecommendOptions, shape: GridShape, aspect: f32, foreground_area: usize) -> Vec<u32> {
+    if !options.grid_widths.is_empty() {
+        return options.grid_widths.clone();
+    }
+    match options.target_count {
+        Some(target) if target > 0 => {
+            // 单个格子面积：矩形为w*h，三角形为w*h/2
+            let area_factor = match shape {
+                GridShape::Triangle => aspect / 2.0,
+                GridShape::Rectangle | GridShape::Polygon => aspect,
+            };
+            let estimate = (foreground_area as f32 / target as f32 / area_factor).sqrt();
+            let mut widths: Vec<u32> = TARGET_WIDTH_FACTORS.iter()
+                .map(|f| ((estimate * f).round() as u32).max(4))
+                .collect();
+            widths.dedup();
+            widths
+        },
+        _ => DEFAULT_GRID_WIDTHS.to_vec(),
+    }
+}
+
+
+fn count_score(selected_count: usize, target_count: Option<usize>) -> f32 {
+    match target_count {
+        Some(target) if target > 0 => {
+            1.0 - (selected_count.abs_diff(target) as f32 / target as f32).min(1.0)
+        },
+        _ => 1.0,
+    }
+}
+
+
+fn aspect_score(aspect_ratio: f32, ideal: f32) -> f32 {
+    let r = aspect_ratio / ideal;
+    r.min(1.0 / r)
+}
+
+
+fn foreground_mask(img: &RgbaImage, bg_color: Color) -> GrayImage {
+    let bg = bg_color.to_rgb();
+    GrayImage::from_fn(img.width(), img.height(), |x, y| {
+        let p = img.get_pixel(x, y);
+        if !is_bg_pixel((p[0], p[1], p[2]), bg) {
+            Luma([255])
+        } else {
+            Luma([0])
+        }
+    })
+}
+
+
+fn selected_mask(width: u32, height: u32, grids: &[Grid]) -> GrayImage {
+    let mut mask = GrayImage::new(width, height);
+    for grid in grids.iter().filter(|g| g.ext.selected.unwrap_or(false)) {
+        let points: Vec<imageproc::point::Point<i32>> = grid.points.iter()
+            .map(|p| imageproc::point::Point::new(p.x.round() as i32, p.y.round() as i32))
+            .collect();
+        draw_polygon_mut(&mut mask, &points, Luma([255]));
+    }
+    mask
+}
+
+
+pub(super) fn render_thumbnail(img: &RgbaImage, mut grids: Vec<Grid>, thumbnail_width: u32) -> Result<RgbaImage> {
+    let selected_color = Color::from_rgb((0xff, 0x00, 0x00));
+    let unselected_color = Color::from_rgb((0x90, 0x99, 0xa2));
+    for grid in grids.iter_mut() {
+        let selected = grid.ext.selected.unwrap_or(false);
+        grid.ext.fill_color = Some(if selected { selected_color } else { unselected_color });
+    }
+    let options = RenderOptions::new(img.width(), img.height(), Color::from_rgb((0x37, 0x37, 0x37)));
+    let rendered = render_canvas_with_grids(&grids, &options)?;
+    let thumbnail_width = thumbnail_width.clamp(1, img.width().max(1));
+    let thumbnail_height = ((img.height() as f32 * thumbnail_width as f32 / img.width().max(1) as f32).round() as u32).max(1);
+    Ok(imageops::resize(&rendered, thumbnail_width, thumbnail_height, imageops::FilterType::Triangle))
+}
+
+
+#[cfg(test)]
+#[test]
+fn test_calc_iou() {
+    let left_half = GrayImage::from_fn(10, 10, |x, _| Luma([if x < 5 { 255 } else { 0 }]));
+    let left_three_quarters = GrayImage::from_fn(10, 10, |x, _| Luma([if x < 8 { 255 } else { 0 }]));
+    assert_eq!(calc_iou(&left_half, &left_half), 1.0);
+    assert_eq!(calc_iou(&left_half, &left_three_quarters), 50.0 / 80.0);
+    assert_eq!(calc_iou(&GrayImage::new(10, 10), &GrayImage::new(10, 10)), 0.0);
+}
+
+
+#[cfg(test)]
+#[test]
+fn test_recommend_ranks_target_count_first() {
+    // 整张logo都是前景，各候选尺寸都能铺满
+    let img = RgbaImage::from_pixel(120, 120, image::Rgba([0, 0, 0, 255]));
+    let mut options = GridRecommendOptions::new(Color::WHITE);
+    options.shapes = vec![GridShape::Rectangle];
+    options.grid_widths = vec![8, 12, 20];
+    options.target_count = Some(100);
+
+    let recommendations = recommend_grid_tilings(&img, &options).unwrap();
+    assert_eq!(recommendations.len(), 6);
+    let best = &recommendations[0];
+    assert_eq!(best.fill_options.grid_size(), (12, 12));
+    assert_eq!(best.selected_count, 100);
+    assert!(recommendations.windows(2).all(|w| w[0].score >= w[1].score));
+}
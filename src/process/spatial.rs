@@ -0,0 +1,138 @@
+use crate::{Grid, Point, Polygon, Rect};
+
+
+/// 格子的空间索引：将格子所在区域划分为均匀的桶，每个桶记录与之外接矩形重叠的格子，
+/// 用于按坐标查找格子(点击)和按矩形框选格子
+#[derive(Debug, Clone, Default)]
+pub struct GridSpatialIndex {
+    polygons: Vec<Polygon>,
+    bounds: Rect,
+    cell_size: f32,
+    cols: usize,
+    rows: usize,
+    buckets: Vec<Vec<usize>>,
+}
+
+impl GridSpatialIndex {
+    /// 按多边形建立索引，查询结果为多边形的下标
+    pub fn new(polygons: Vec<Polygon>) -> Self {
+        let bboxes: Vec<Rect> = polygons.iter().map(|p| p.bbox()).collect();
+        let Some(bounds) = bboxes.iter().copied().reduce(union) else { return Self::default() };
+
+        // 桶的边长取格子外接矩形的平均边长，每个格子大致落在1~4个桶中；
+        // 格子稀疏分布时加大桶的边长，桶数不超过格子数的4倍
+        let avg_side = bboxes.iter().map(|b| b.width.max(b.height)).sum::<f32>() / bboxes.len() as f32;
+        let max_buckets = bboxes.len() * 4;
+        let mut cell_size = avg_side.max((bounds.width * bounds.height / max_buckets as f32).sqrt()).max(1.0);
+        let (mut cols, mut rows) = bucket_dims(&bounds, cell_size);
+        while cols * rows > max_buckets {
+            cell_size *= 2.0;
+            (cols, rows) = bucket_dims(&bounds, cell_size);
+        }
+
+        let mut index = Self { polygons, bounds, cell_size, cols, rows, buckets: vec![Vec::new(); cols * rows] };
+        for (i, bbox) in bboxes.iter().enumerate() {
+            let ((c0, r0), (c1, r1)) = index.cell_range(bbox);
+            for r in r0..=r1 {
+                for c in c0..=c1 {
+                    index.buckets[r * cols + c].push(i);
+                }
+            }
+        }
+        index
+    }
+
+    pub fn from_grids(grids: &[Grid]) -> Self {
+        Self::new(grids.iter().map(Grid::polygon).collect())
+    }
+
+    pub fn len(&self) -> usize {
+        self.polygons.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.polygons.is_empty()
+    }
+
+    /// 包含该点的格子；点落在相邻格子的公共边上时返回下标最小的格子
+    pub fn hit(&self, p: Point) -> Option<usize> {
+        if self.polygons.is_empty() || !self.bounds.contains(p) {
+            return None;
+        }
+        let (c, r) = self.cell_of(p);
+        self.buckets[r * self.cols + c].iter()
+            .copied()
+            .find(|&i| self.polygons[i].contains(p))
+    }
+
+    /// 与矩形有重叠面积的格子，按下标升序返回
+    pub fn query_rect(&self, rect: &Rect) -> Vec<usize> {
+        if self.polygons.is_empty() || !self.bounds.intersects(rect) {
+            return Vec::new();
+        }
+        let ((c0, r0), (c1, r1)) = self.cell_range(rect);
+        let mut found: Vec<usize> = (r0..=r1)
+            .flat_map(|r| (c0..=c1).map(move |c| r * self.cols + c))
+            .flat_map(|b| self.buckets[b].iter().copied())
+            .collect();
+        found.sort_unstable();
+        found.dedup();
+        found.retain(|&i| self.polygons[i].intersects_rect(rect));
+        found
+    }
+
+    fn cell_of(&self, p: Point) -> (usize, usize) {
+        let col = ((p.x - self.bounds.x) / self.cell_size).floor().max(0.0) as usize;
+        let row = ((p.y - self.bounds.y) / self.cell_size).floor().max(0.0) as usize;
+        (col.min(self.cols - 1), row.min(self.rows - 1))
+    }
+
+    fn cell_range(&self, rect: &Rect) -> ((usize, usize), (usize, usize)) {
+        (self.cell_of(Point::new(rect.x, rect.y)), self.cell_of(Point::new(rect.max_x(), rect.max_y())))
+    }
+}
+
+fn bucket_dims(bounds: &Rect, cell_size: f32) -> (usize, usize) {
+    ((bounds.width / cell_size).floor() as usize + 1, (bounds.height / cell_size).floor() as usize + 1)
+}
+
+fn union(a: Rect, b: Rect) -> Rect {
+    let (x, y) = (a.x.min(b.x), a.y.min(b.y));
+    Rect::new(x, y, a.max_x().max(b.max_x()) - x, a.max_y().max(b.max_y()) - y)
+}
+
+
+#[cfg(test)]
+#[test]
+fn test_spatial_index_hit_and_rect_query() {
+    use crate::{generate_enmty_canvas_grids, GridFillOptions};
+
+    let grids = generate_enmty_canvas_grids(100, 60, GridFillOptions::triangle(10, 8)).unwrap();
+    let index = GridSpatialIndex::from_grids(&grids);
+    assert_eq!(index.len(), grids.len());
+
+    for p in [Point::new(3.0, 1.0), Point::new(47.5, 30.2), Point::new(95.0, 55.0)] {
+        let expected = grids.iter().position(|g| g.contains(p));
+        assert_eq!(index.hit(p), expected);
+    }
+    assert_eq!(index.hit(Point::new(500.0, 10.0)), None);
+
+    let rect = Rect::new(20.0, 10.0, 25.0, 18.0);
+    let expected: Vec<usize> = (0..grids.len()).filter(|&i| grids[i].intersects_rect(&rect)).collect();
+    assert!(!expected.is_empty());
+    assert_eq!(index.query_rect(&rect), expected);
+}
+
+#[cfg(test)]
+#[test]
+fn test_spatial_index_sparse_grids() {
+    let square = |x: f32, y: f32| Polygon(vec![
+        Point::new(x, y), Point::new(x + 1.0, y), Point::new(x + 1.0, y + 1.0), Point::new(x, y + 1.0),
+    ]);
+    let index = GridSpatialIndex::new(vec![square(0.0, 0.0), square(8191.0, 8191.0)]);
+    assert!(index.buckets.len() <= 8);
+    assert_eq!(index.hit(Point::new(0.5, 0.5)), Some(0));
+    assert_eq!(index.hit(Point::new(8191.5, 8191.5)), Some(1));
+    assert_eq!(index.hit(Point::new(4000.0, 4000.0)), None);
+    assert_eq!(index.query_rect(&Rect::new(8000.0, 8000.0, 500.0, 500.0)), vec![1]);
+}
@@ -0,0 +1,197 @@
+use crate::error::Result;
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+use crate::{Grid, GridFillOptions, GridPickCmd};
+use super::{generate_canvas_grids_with_progress, LogoIntegrals, PickProgress};
+
+/// 搜索时格子边长的下限
+const MIN_GRID_SIZE: u32 = 4;
+/// 阈值二分搜索的迭代次数
+const THRESHOLD_SEARCH_ITERATIONS: usize = 16;
+
+
+/// 按目标格子数搜索时，允许调整的参数
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TargetSearchMode {
+    // 只调整格子尺寸（保持宽高比）
+    GridSize,
+    // 只调整选取阈值
+    Threshold,
+    // 先调整格子尺寸，再微调选取阈值
+    #[default]
+    Both,
+}
+
+
+/// 按目标格子数搜索的结果
+#[derive(Debug, Clone)]
+pub struct TargetCountResult {
+    pub grids: Vec<Grid>,
+    // 最终使用的画布填充参数
+    pub fill_options: GridFillOptions,
+    // 最终使用的选取策略
+    pub pick_strategy: GridPickCmd,
+    // 选中的格子数
+    pub selected_count: usize,
+}
+
+
+/// 搜索格子尺寸和/或选取阈值，使选中的格子数尽量接近target_count；
+/// 调整格子尺寸时，画布上的格子总数不超过max_grid_count。
+/// 搜索过程中反复对同一张logo选取，共用integrals中的积分图
+#[allow(clippy::too_many_arguments)]
+pub fn search_grids_for_target_count(
+    img: &RgbaImage,
+    integrals: &LogoIntegrals,
+    progress: &PickProgress,
+    fill_options: GridFillOptions,
+    pick_strategy: GridPickCmd,
+    target_count: usize,
+    max_grid_count: u64,
+    mode: TargetSearchMode) -> Result<TargetCountResult> {
+
+    let mut best = evaluate(img, integrals, progress, fill_options, pick_strategy.clone())?;
+
+    if mode != TargetSearchMode::Threshold {
+        // 选中数量随格子尺寸增大而减少，按宽度二分，高度按原宽高比缩放
+        let (w0, h0) = fill_options.grid_size();
+        let aspect = h0 as f32 / w0.max(1) as f32;
+        let sized = |width: u32| {
+            let height = ((width as f32 * aspect).round() as u32).max(MIN_GRID_SIZE);
+            fill_options.with_grid_size(width, height)
+        };
+        let mut hi = img.width().min(img.height()).max(MIN_GRID_SIZE);
+        let mut lo = min_width_within_count(img, &sized, hi, max_grid_count);
+        while lo <= hi {
+            let width = lo + (hi - lo) / 2;
+            let result = evaluate(img, integrals, progress, sized(width), pick_strategy.clone())?;
+            let height = result.fill_options.grid_size().1;
+            debug!("target count search, grid size: {}x{}, selected: {}", width, height, result.selected_count);
+            let count = result.selected_count;
+            best = closer(best, result, target_count);
+            if count == target_count {
+                break;
+            } else if count > target_count {
+                lo = width + 1;
+            } else {
+                hi = width - 1;
+            }
+        }
+    }
+
+    if mode != TargetSearchMode::GridSize {
+        // 选中数量随阈值增大而减少，在合理范围内二分
+        let fill_options = best.fill_options;
+        let (mut lo, mut hi) = best.pick_strategy.pick_threshold_range();
+        for _ in 0..THRESHOLD_SEARCH_ITERATIONS {
+            let threshold = (lo + hi) / 2.0;
+            let result = evaluate(img, integrals, progress, fill_options, best.pick_strategy.with_pick_threshold(threshold))?;
+            debug!("target count search, threshold: {}, selected: {}", threshold, result.selected_count);
+            let count = result.selected_count;
+            best = closer(best, result, target_count);
+            if count == target_count {
+                break;
+            } else if count > target_count {
+                lo = threshold;
+            } else {
+                hi = threshold;
+            }
+        }
+    }
+
+    Ok(best)
+}
+
+
+/// 格子总数不超过max_grid_count的最小宽度；格子数随宽度增大而减少，没有满足条件的宽度时返回max_width
+fn min_width_within_count<F>(img: &RgbaImage, sized: &F, max_width: u32, max_grid_count: u64) -> u32
+where F: Fn(u32) -> GridFillOptions {
+    let within = |width: u32| sized(width).grid_count(img.width(), img.height()) <= max_grid_count;
+    let (mut lo, mut hi) = (MIN_GRID_SIZE, max_width);
+    while lo < hi {
+        let width = lo + (hi - lo) / 2;
+        if within(width) {
+            hi = width;
+        } else {
+            lo = width + 1;
+        }
+    }
+    lo
+}
+
+
+fn evaluate(
+    img: &RgbaImage,
+    integrals: &LogoIntegrals,
+    progress: &PickProgress,
+    fill_options: GridFillOptions,
+    pick_strategy: GridPickCmd) -> Result<TargetCountResult> {
+    let grids = generate_canvas_grids_with_progress(img, integrals, fill_options, pick_strategy.clone(), progress)?;
+    let selected_count = grids.iter().filter(|g| g.ext.selected.unwrap_or(false)).count();
+    Ok(TargetCountResult { grids, fill_options, pick_strategy, selected_count })
+}
+
+
+fn closer(a: TargetCountResult, b: TargetCountResult, target_count: usize) -> TargetCountResult {
+    if b.selected_count.abs_diff(target_count) < a.selected_count.abs_diff(target_count) {
+        b
+    } else {
+        a
+    }
+}
+
+
+#[cfg(test)]
+#[test]
+fn test_search_respects_grid_count_limit() {
+    use crate::{Color, EliminateBgColorParam};
+
+    let img = RgbaImage::from_pixel(200, 150, image::Rgba([0, 0, 0, 255]));
+    let pick = GridPickCmd::EliminateBgColor(EliminateBgColorParam { color: Color::WHITE, min_remaining_ratio: 0.5 });
+    // 目标数远大于上限时，格子尺寸缩小到上限为止
+    let result = search_grids_for_target_count(
+        &img, &LogoIntegrals::new(), &PickProgress::new(), GridFillOptions::rectangle(20, 20), pick,
+        10_000, 500, TargetSearchMode::GridSize).unwrap();
+    assert!(result.grids.len() <= 500);
+    assert!(result.grids.len() > 400);
+}
+
+
+#[cfg(test)]
+#[test]
+fn test_search_reaches_target_count() {
+    use crate::{Color, EliminateBgColorParam};
+
+    // 前景像素的占比从左到右逐渐增加，格子的覆盖率各不相同
+    let img = RgbaImage::from_fn(200, 200, |x, y| {
+        if (x * 7 + y * 13) % 200 < x { image::Rgba([0, 0, 0, 255]) } else { image::Rgba([255, 255, 255, 255]) }
+    });
+    let integrals = LogoIntegrals::new();
+    let pick = GridPickCmd::EliminateBgColor(EliminateBgColorParam { color: Color::WHITE, min_remaining_ratio: 0.5 });
+    let search = |pick: &GridPickCmd, mode: TargetSearchMode| {
+        search_grids_for_target_count(
+            &img, &integrals, &PickProgress::new(), GridFillOptions::rectangle(10, 10), pick.clone(),
+            120, 100_000, mode).unwrap()
+    };
+
+    // 选中数与目标数的差距在5%以内
+    let within = |result: &TargetCountResult| result.selected_count.abs_diff(120) <= 6;
+
+    // 只调整格子尺寸时阈值不变，只调整阈值时格子尺寸不变
+    let result = search(&pick, TargetSearchMode::GridSize);
+    assert!(within(&result));
+    assert_eq!(result.pick_strategy.pick_threshold(), 0.5);
+    let result = search(&pick, TargetSearchMode::Threshold);
+    assert!(within(&result));
+    assert_eq!(result.fill_options.grid_size(), (10, 10));
+    assert!(within(&search(&pick, TargetSearchMode::Both)));
+
+    // Not组合的阈值范围是反向的，同样能收敛到目标数
+    let not = GridPickCmd::Not(Box::new(pick));
+    let result = search(&not, TargetSearchMode::Threshold);
+    assert!(within(&result));
+    assert_eq!(result.fill_options.grid_size(), (10, 10));
+    assert!(within(&search(&not, TargetSearchMode::Both)));
+}
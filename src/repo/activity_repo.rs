@@ -1,44 +1,59 @@
+use std::sync::Arc;
 use dashmap::DashMap;
 use anyhow::Result;
-use crate::{ActivityDO, ActivityInfoResult, ActivityRepo, ApiError};
+use crate::{ActivityDO, ActivityInfoResult, ActivityRepo, ApiError, GridSpatialIndex, Polygon};
 
+#[derive(Default)]
 pub struct ActivityMemoryRepo{
-    activities: DashMap<String, ActivityDO>
+    activities: DashMap<String, ActivityDO>,
+    // 活动格子的形状创建后不再变化，插入时建立空间索引
+    grid_indexes: DashMap<String, Arc<GridSpatialIndex>>,
 }
 
 impl ActivityMemoryRepo{
     pub fn new() -> Self{
         Self{
-            activities: DashMap::new()
+            activities: DashMap::new(),
+            grid_indexes: DashMap::new(),
         }
     }
 }
 
 impl ActivityRepo for ActivityMemoryRepo {
     fn insert_activity(&self, activity: ActivityDO) -> Result<(), ApiError> {
+        let polygons = activity.grids.iter()
+            .map(|grid| Polygon(grid.points.clone()))
+            .collect();
+        self.grid_indexes.insert(activity.id.clone(), Arc::new(GridSpatialIndex::new(polygons)));
         self.activities.insert(activity.id.clone(), activity);
         Ok(())
     }
 
+    fn get_grid_index(&self, activity_id: &str) -> Option<Arc<GridSpatialIndex>> {
+        self.grid_indexes.get(activity_id).map(|item| item.value().clone())
+    }
+
     fn get_activity(&self, id: &str) -> Option<ActivityDO> {
         self.activities.get(id).map(|item| item.value().clone())
     }
 
-    fn mark_grid_of_activity(&self, activity_id: &str, seq: &str) -> Result<()> {
+    fn mark_grid_of_activity(&self, activity_id: &str, seq: &str, participant: Option<String>) -> Result<()> {
         if let Some(mut activity) = self.activities.get_mut(activity_id) {
             if let Some(grid_index) = activity.value().grids.iter().position(|grid| grid.seq == seq) {
                 // 更新找到的网格的marked属性
-                activity.value_mut().grids[grid_index].marked = true;
+                let grid = &mut activity.value_mut().grids[grid_index];
+                grid.marked = true;
+                grid.participant = participant;
                 
                 // 由于使用了DashMap，此处无需显式保存，更新已自动反映在内存中
-                return Ok(());
+                Ok(())
             } else {
                 // 如果没有找到匹配的网格序列号，可以考虑返回一个错误或日志记录
-                return Err(anyhow::anyhow!("Grid with seq {} not found in activity {}", seq, activity_id));
+                Err(anyhow::anyhow!("Grid with seq {} not found in activity {}", seq, activity_id))
             }
         } else {
             // 活动ID未找到时的处理
-            return Err(anyhow::anyhow!("Activity with id {} not found", activity_id));
+            Err(anyhow::anyhow!("Activity with id {} not found", activity_id))
         }
     }
     
@@ -46,11 +61,12 @@ impl ActivityRepo for ActivityMemoryRepo {
         if let Some(mut activity) = self.activities.get_mut(activity_id) {
             activity.value_mut().grids.iter_mut().for_each(|grid| {
                 grid.marked = false;
+                grid.participant = None;
             });
             Ok(())
         } else {
             // 活动ID未找到时的处理
-            return Err(ApiError::BizError("ACTIVITY_NOT_FOUND".to_string(), format!("Activity with id {} not found", activity_id)));
+            Err(ApiError::BizError("ACTIVITY_NOT_FOUND".to_string(), format!("Activity with id {} not found", activity_id)))
         }
     }
     
@@ -3,6 +3,7 @@ use anyhow::Result;
 
 use crate::{ImageDO, ImageRepo};
 
+#[derive(Default)]
 pub struct ImageMemoryRepo{
     images: DashMap<String, ImageDO>
 }
@@ -1,8 +1,9 @@
 mod activity_repo;
 mod image_repo;
 
+use std::sync::Arc;
 use anyhow::Result;
-use crate::{ApiError, GridShape, Point};
+use crate::{ApiError, Color, GridShape, GridSpatialIndex, Point};
 
 pub use activity_repo::*;
 pub use image_repo::*;
@@ -14,7 +15,7 @@ pub struct ActivityDO{
     pub grids: Vec<ActivityGridDO>,
     pub canvas_width: u32,
     pub canvas_height: u32,
-    pub canvas_color: String,
+    pub canvas_color: Color,
 }
 
 
@@ -24,8 +25,12 @@ pub struct ActivityGridDO{
     pub points: Vec<Point>,
     pub shape: GridShape,
     pub marked: bool,
-    pub unmarked_color: String,
-    pub marked_color: String,
+    pub unmarked_color: Color,
+    pub marked_color: Color,
+    // 调色板分类得到的下标，marked_color取自调色板时有值
+    pub palette_index: Option<usize>,
+    // 签到的参与者
+    pub participant: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -40,8 +45,10 @@ pub trait ActivityRepo{
     fn list_activities(&self) -> Vec<ActivityInfoResult>;
 
     fn insert_activity(&self, activity: ActivityDO) -> Result<(), ApiError>;
-    fn mark_grid_of_activity(&self, activity_id: &str, seq: &str) -> Result<()>;
+    fn mark_grid_of_activity(&self, activity_id: &str, seq: &str, participant: Option<String>) -> Result<()>;
     fn reset_activity(&self, activity_id: &str) -> Result<(), ApiError>;
+    /// 活动格子的空间索引，下标与ActivityDO.grids一致
+    fn get_grid_index(&self, activity_id: &str) -> Option<Arc<GridSpatialIndex>>;
 }
 
 
@@ -1,8 +1,13 @@
+use crate::Color;
+
+/// 与背景色差值不超过该值的像素视为背景
+pub(crate) const BG_COLOR_TOLERANCE: f32 = 5.0;
+
 /// 计算两个色值的差异,返回值范围:[0,100]
 /// 当输入的两个色值完全相同时，返回值为0
 /// 当输入的两个色值完全不同时，返回值为100
 pub fn calc_color_distance(color1: (u8,u8,u8), color2: (u8,u8,u8)) -> f32{
-    const SQRT_3: f32 = 1.7320508075688772; // 直接定义sqrt(3)
+    const SQRT_3: f32 = 1.732_050_8; // 直接定义sqrt(3)
 
     // 将RGB值从[0, 255]转换为[0, 1]
     let color1_normalized = (
@@ -25,6 +30,27 @@ pub fn calc_color_distance(color1: (u8,u8,u8), color2: (u8,u8,u8)) -> f32{
     ((diff_r + diff_g + diff_b).sqrt() / SQRT_3).clamp(0.0, 1.0) * 100.0
 }
 
+/// 判断像素是否为背景色，等价于 calc_color_distance(pixel, bg) <= BG_COLOR_TOLERANCE，
+/// 直接比较距离的平方，避免逐像素开方
+#[inline]
+pub(crate) fn is_bg_pixel(pixel: (u8, u8, u8), bg: (u8, u8, u8)) -> bool {
+    let dr = pixel.0 as i32 - bg.0 as i32;
+    let dg = pixel.1 as i32 - bg.1 as i32;
+    let db = pixel.2 as i32 - bg.2 as i32;
+    // calc_color_distance = sqrt(sum) / (255 * sqrt(3)) * 100
+    let max_distance = BG_COLOR_TOLERANCE / 100.0 * 255.0;
+    ((dr * dr + dg * dg + db * db) as f32) <= max_distance * max_distance * 3.0
+}
+
+/// 在调色板中查找与指定颜色差值最小的颜色，返回其下标；调色板为空时返回None
+pub fn find_nearest_color(color: Color, palette: &[Color]) -> Option<usize> {
+    palette.iter()
+        .map(|c| calc_color_distance(color.to_rgb(), c.to_rgb()))
+        .enumerate()
+        .min_by(|(_, d1), (_, d2)| d1.total_cmp(d2))
+        .map(|(i, _)| i)
+}
+
 // test
 #[cfg(test)]
 #[test]
@@ -33,5 +59,9 @@ fn test_calculate_color_diff(){
     let color2 = (0, 0, 0);
     assert_eq!(calc_color_distance(color1, color2), 100.0);
     assert_eq!(calc_color_distance(color1, color1), 0.0);
+    for v in 230..=255 {
+        let pixel = (v, v, 255);
+        assert_eq!(is_bg_pixel(pixel, color1), calc_color_distance(pixel, color1) <= BG_COLOR_TOLERANCE);
+    }
 
 }
\ No newline at end of file
@@ -1,37 +1,91 @@
-use std::sync::Arc;
+use std::{borrow::Cow, collections::HashSet, sync::Arc};
 use anyhow::Result;
-use axum::{extract::State, Json};
+use axum::extract::State;
 use serde::{Deserialize, Serialize};
-use crate::{ActivityDO, ActivityGridDO, ActivityRepo, ApiError, ApiResponse, AppState, GridShape, Point};
+use validator::{Validate, ValidationError};
+use crate::web::validate::{field_validation_error, validate_grid_points, validate_points_in_canvas};
+use crate::{ValidatedJson, MAX_CANVAS_SIDE, Color, ActivityDO, ActivityGridDO, ActivityRepo, ApiError, ApiResponse, AppState, GridShape, Point};
 
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Validate)]
 #[serde(rename_all = "camelCase")]
+#[validate(schema(function = "validate_activity_grids"))]
 pub struct ActivityCreateReq {
+    #[validate(length(min = 1, max = 64))]
     pub name: String,
+    #[validate(range(min = 1, max = "MAX_CANVAS_SIDE"))]
     pub canvas_width: u32,
+    #[validate(range(min = 1, max = "MAX_CANVAS_SIDE"))]
     pub canvas_height: u32,
-    pub canvas_color: String,
+    pub canvas_color: Color,
+    // 调色板，与转换结果中的palette一致；格子指定了paletteIndex时按下标取标记色
+    #[validate(length(min = 1, max = 64))]
+    pub palette: Option<Vec<Color>>,
+    #[validate(length(min = 1, max = 100_000), nested)]
     pub grids: Vec<ActivityGrid>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Validate)]
 #[serde(rename_all = "camelCase")]
+#[validate(schema(function = "validate_activity_grid_points", skip_on_field_errors = false))]
 pub struct ActivityGrid {
+    #[validate(length(min = 1, max = 32))]
     pub seq: String,
     pub points: Vec<Point>,
     pub shape: GridShape,
     pub marked: bool,
-    pub marked_color: String,
-    pub unmarked_color: String,
+    // 标记后的颜色，指定了paletteIndex时可省略
+    pub marked_color: Option<Color>,
+    pub unmarked_color: Color,
+    // 调色板分类得到的下标，标记后使用调色板中对应的颜色
+    pub palette_index: Option<usize>,
+}
+
+impl ActivityGrid {
+    /// 标记色：优先取调色板中的颜色，其次取markedColor
+    fn resolve_marked_color(&self, palette: Option<&[Color]>) -> Option<Color> {
+        match self.palette_index {
+            Some(index) => palette.and_then(|p| p.get(index)).copied(),
+            None => self.marked_color,
+        }
+    }
+}
+
+fn validate_activity_grid_points(grid: &ActivityGrid) -> Result<(), ValidationError> {
+    validate_grid_points(grid.shape, &grid.points)
+}
+
+/// 格子须在画布内，序号不能重复，且能确定标记色
+fn validate_activity_grids(req: &ActivityCreateReq) -> Result<(), ValidationError> {
+    let mut seqs = HashSet::with_capacity(req.grids.len());
+    for (i, grid) in req.grids.iter().enumerate() {
+        validate_points_in_canvas(&grid.points, req.canvas_width, req.canvas_height)
+            .map_err(|e| with_field(e, format!("grids[{}].points", i)))?;
+        if !seqs.insert(grid.seq.as_str()) {
+            return Err(field_validation_error(format!("grids[{}].seq", i), "duplicate_seq", format!("duplicate seq: {}", grid.seq)));
+        }
+        if grid.resolve_marked_color(req.palette.as_deref()).is_none() {
+            return Err(match grid.palette_index {
+                Some(index) => field_validation_error(format!("grids[{}].paletteIndex", i), "out_of_range",
+                    format!("palette index {} out of range, palette size: {}", index, req.palette.as_ref().map_or(0, Vec::len))),
+                None => field_validation_error(format!("grids[{}].markedColor", i), "required",
+                    "markedColor is required without paletteIndex"),
+            });
+        }
+    }
+    Ok(())
+}
 
+fn with_field(mut error: ValidationError, field: String) -> ValidationError {
+    error.add_param(Cow::Borrowed("field"), &field);
+    error
 }
 
 
 
 pub async fn activity_create_handler(
     State(app_state): State<Arc<AppState>>,
-    Json(req): Json<ActivityCreateReq>) -> Result<ApiResponse<String>, ApiError> {
+    ValidatedJson(req): ValidatedJson<ActivityCreateReq>) -> Result<ApiResponse<String>, ApiError> {
     let activity_id = "ACTIVITY_001".to_string();
     app_state.activity_repo.insert_activity(ActivityDO{
         id: activity_id.clone(),
@@ -42,11 +96,46 @@ pub async fn activity_create_handler(
         grids: req.grids.iter().map(|grid| ActivityGridDO{
             seq: grid.seq.clone(),
             points: grid.points.clone(),
-            shape: grid.shape.clone(),
+            shape: grid.shape,
             marked: grid.marked,
-            marked_color: grid.marked_color.clone(),
-            unmarked_color: grid.unmarked_color.clone(),
+            // 校验已保证能确定标记色
+            marked_color: grid.resolve_marked_color(req.palette.as_deref()).unwrap_or(grid.unmarked_color),
+            unmarked_color: grid.unmarked_color,
+            palette_index: grid.palette_index,
+            participant: None,
         }).collect(),
     })?;
     Ok(ApiResponse::ok(activity_id))
 }
+
+
+#[cfg(test)]
+#[test]
+fn test_marked_color_from_palette() {
+    let req = |palette: serde_json::Value, grid: serde_json::Value| -> ActivityCreateReq {
+        let mut grid_json = serde_json::json!({
+            "seq": "R1C1", "points": [[0, 0], [10, 0], [10, 10], [0, 10]], "shape": "rectangle",
+            "marked": false, "unmarkedColor": "#9099A2",
+        });
+        grid_json.as_object_mut().unwrap().extend(grid.as_object().unwrap().clone());
+        serde_json::from_value(serde_json::json!({
+            "name": "test", "canvasWidth": 20, "canvasHeight": 20, "canvasColor": "#373737",
+            "palette": palette, "grids": [grid_json],
+        })).unwrap()
+    };
+    let palette = serde_json::json!(["#ff0000", "#0000ff"]);
+
+    let ok = req(palette.clone(), serde_json::json!({"paletteIndex": 1, "markedColor": "#00ff00"}));
+    assert!(ok.validate().is_ok());
+    assert_eq!(ok.grids[0].resolve_marked_color(ok.palette.as_deref()), Some(Color::from_rgb((0, 0, 255))));
+
+    let fallback = req(serde_json::Value::Null, serde_json::json!({"markedColor": "#00ff00"}));
+    assert_eq!(fallback.grids[0].resolve_marked_color(None), Some(Color::from_rgb((0, 255, 0))));
+
+    let field = |req: ActivityCreateReq| match ApiError::from(req.validate().unwrap_err()) {
+        ApiError::Validation(fields) => (fields[0].field.clone(), fields[0].code.clone()),
+        e => panic!("unexpected error: {:?}", e),
+    };
+    assert_eq!(field(req(palette, serde_json::json!({"paletteIndex": 2}))), ("grids[0].paletteIndex".to_string(), "out_of_range".to_string()));
+    assert_eq!(field(req(serde_json::Value::Null, serde_json::json!({}))), ("grids[0].markedColor".to_string(), "required".to_string()));
+}
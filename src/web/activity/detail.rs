@@ -2,7 +2,7 @@ use std::sync::Arc;
 use anyhow::Result;
 use axum::extract::{Query, State};
 use serde::{Deserialize, Serialize};
-use crate::{ActivityDO, ActivityRepo, ApiError, ApiResponse, AppState, GridShape, Point};
+use crate::{ActivityDO, ActivityRepo, Color, ApiError, ApiResponse, AppState, GridShape, Point};
 
 
 
@@ -18,7 +18,7 @@ pub struct ActivityDetailReply{
     pub id: String,
     pub canvas_width: u32,
     pub canvas_height: u32,
-    pub canvas_color: String,
+    pub canvas_color: Color,
     pub grids: Vec<ActivityGrid>,
 }
 
@@ -29,8 +29,10 @@ pub struct ActivityGrid {
     pub points: Vec<Point>,
     pub shape: GridShape,
     pub marked: bool,
-    pub marked_color: String,
-    pub unmarked_color: String,
+    pub marked_color: Color,
+    pub unmarked_color: Color,
+    pub palette_index: Option<usize>,
+    pub participant: Option<String>,
 
 }
  
@@ -40,11 +42,8 @@ pub async fn activity_detail_handler(
     Query(req): Query<ActivityDetailQueryReq>,
 ) -> Result<ApiResponse<ActivityDetailReply>, ApiError> {
 
-    let activity = app_state.activity_repo.get_activity(req.id.as_str());
-    if activity.is_none() {
-        return Err(ApiError::BizError("ACTIVITY_NOT_FOUND".into(), format!("activity not found, id: {}", req.id)));
-    }
-    let activity = activity.unwrap();
+    let activity = app_state.activity_repo.get_activity(req.id.as_str())
+        .ok_or_else(|| ApiError::BizError("ACTIVITY_NOT_FOUND".into(), format!("activity not found, id: {}", req.id)))?;
 
     Ok(ApiResponse::ok(to_activity_detail_reply(activity)))
 }
@@ -54,17 +53,19 @@ fn to_activity_detail_reply(activity: ActivityDO) -> ActivityDetailReply {
     let grids = activity.grids.iter().map(|grid| ActivityGrid{
         seq: grid.seq.clone(),
         points: grid.points.clone(),
-        shape: grid.shape.clone(),
+        shape: grid.shape,
         marked: grid.marked,
-        marked_color: grid.marked_color.clone(),
-        unmarked_color: grid.unmarked_color.clone(),
+        marked_color: grid.marked_color,
+        unmarked_color: grid.unmarked_color,
+        palette_index: grid.palette_index,
+        participant: grid.participant.clone(),
     }).collect();
 
     ActivityDetailReply{
         id: activity.id.clone(),
         canvas_width: activity.canvas_width,
         canvas_height: activity.canvas_height,
-        canvas_color: activity.canvas_color.clone(),
+        canvas_color: activity.canvas_color,
         grids,
     }
 }
\ No newline at end of file
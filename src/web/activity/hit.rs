@@ -0,0 +1,79 @@
+use std::sync::Arc;
+use anyhow::Result;
+use axum::extract::{Query, State};
+use serde::{Deserialize, Serialize};
+use crate::{ActivityDO, ActivityRepo, ApiError, ApiResponse, AppState, GridSpatialIndex, Point, Rect};
+
+
+#[derive(Debug, Deserialize)]
+pub struct ActivityHitQueryReq {
+    pub id: String,
+    pub x: f32,
+    pub y: f32,
+}
+
+/// 框选区域，x/y为左上角
+#[derive(Debug, Deserialize)]
+pub struct ActivitySelectQueryReq {
+    pub id: String,
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActivityHitGrid {
+    pub seq: String,
+    // 是否已签到
+    pub marked: bool,
+    // 签到的参与者
+    pub participant: Option<String>,
+}
+
+
+/// 查找画布坐标处的格子，未落在任何格子上时返回null
+pub async fn activity_hit_handler(
+    State(app_state): State<Arc<AppState>>,
+    Query(req): Query<ActivityHitQueryReq>,
+) -> Result<ApiResponse<Option<ActivityHitGrid>>, ApiError> {
+    let (activity, index) = load_activity_with_index(&app_state, &req.id)?;
+    let grid = index.hit(Point::new(req.x, req.y))
+        .map(|i| to_hit_grid(&activity, i));
+    Ok(ApiResponse::ok(grid))
+}
+
+
+/// 框选：返回与矩形区域有重叠的格子
+pub async fn activity_select_handler(
+    State(app_state): State<Arc<AppState>>,
+    Query(req): Query<ActivitySelectQueryReq>,
+) -> Result<ApiResponse<Vec<ActivityHitGrid>>, ApiError> {
+    if !(req.width >= 0.0 && req.height >= 0.0) {
+        return Err(ApiError::InvalidParameter("width/height".into(), "must not be negative".into()));
+    }
+    let (activity, index) = load_activity_with_index(&app_state, &req.id)?;
+    let grids = index.query_rect(&Rect::new(req.x, req.y, req.width, req.height))
+        .into_iter()
+        .map(|i| to_hit_grid(&activity, i))
+        .collect();
+    Ok(ApiResponse::ok(grids))
+}
+
+
+fn load_activity_with_index(app_state: &AppState, id: &str) -> Result<(ActivityDO, Arc<GridSpatialIndex>), ApiError> {
+    let not_found = || ApiError::BizError("ACTIVITY_NOT_FOUND".into(), format!("activity not found, id: {}", id));
+    let activity = app_state.activity_repo.get_activity(id).ok_or_else(not_found)?;
+    let index = app_state.activity_repo.get_grid_index(id).ok_or_else(not_found)?;
+    Ok((activity, index))
+}
+
+fn to_hit_grid(activity: &ActivityDO, i: usize) -> ActivityHitGrid {
+    let grid = &activity.grids[i];
+    ActivityHitGrid {
+        seq: grid.seq.clone(),
+        marked: grid.marked,
+        participant: grid.participant.clone(),
+    }
+}
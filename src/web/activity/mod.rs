@@ -3,6 +3,7 @@ mod detail;
 mod sign_in;
 mod reset;
 mod create;
+mod hit;
 
 use std::sync::Arc;
 use axum::{routing::{get, post}, Router};
@@ -15,7 +16,8 @@ use self::{
     list::activity_list_handler,
     reset::activity_reset_in_handler, 
     sign_in::activity_sign_in_handler, 
-    create::activity_create_handler
+    create::activity_create_handler,
+    hit::{activity_hit_handler, activity_select_handler},
 };
 
 
@@ -31,4 +33,6 @@ pub fn activity_routes() -> Router<Arc<AppState>> {
         .route("/signIn", post(activity_sign_in_handler))
         .route("/reset", get(activity_reset_in_handler))
         .route("/create", post(activity_create_handler))
+        .route("/hit", get(activity_hit_handler))
+        .route("/select", get(activity_select_handler))
 }
@@ -1,26 +1,32 @@
 use std::sync::Arc;
 use anyhow::Result;
-use axum::{extract::State,Json};
+use axum::extract::State;
 use serde::{Deserialize, Serialize};
 use tracing::info;
-use crate::{ ActivityRepo, ApiError, ApiResponse, AppState};
+use validator::Validate;
+use crate::{ ActivityRepo, ApiError, ApiResponse, AppState, ValidatedJson};
 
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Validate)]
 #[serde(rename_all = "camelCase")]
 pub struct ActivitySignInReq {
+    #[validate(length(min = 1, max = 64))]
     pub activity_id: String,
+    #[validate(length(min = 1, max = 32))]
     pub seq: String,
+    // 签到的参与者
+    #[validate(length(min = 1, max = 64))]
+    pub participant: Option<String>,
 } 
 
 
 /// 活动签到
 pub async fn activity_sign_in_handler(
     State(app_state): State<Arc<AppState>>,
-    Json(req): Json<ActivitySignInReq>,
+    ValidatedJson(req): ValidatedJson<ActivitySignInReq>,
 ) -> Result<ApiResponse<()>, ApiError> {
     info!("activity_sign_in, activiti_id: {}, seq: {}", req.activity_id, req.seq);
-    app_state.activity_repo.mark_grid_of_activity(req.activity_id.as_str(), req.seq.as_str())
+    app_state.activity_repo.mark_grid_of_activity(req.activity_id.as_str(), req.seq.as_str(), req.participant.clone())
         .map_err(|e| ApiError::BizError("MARK_GRID_FAILED".into(), format!("mark grid failed, activity_id: {}, seq: {}, error: {}", req.activity_id, req.seq, e)))?;
 
     Ok(ApiResponse::ok(()))
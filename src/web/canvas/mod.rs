@@ -1,26 +1,54 @@
 
-use std::sync::Arc;
-use axum::{routing::post, Json, Router};
+use std::{collections::BTreeMap, sync::Arc};
+use axum::{routing::post, Router};
 use serde::{Deserialize, Serialize};
 use anyhow::Result;
-use crate::{generate_enmty_canvas_grids, ApiError, ApiResponse, AppState, GridFillOptions, GridShape, Point};
+use validator::{Validate, ValidationError};
+use crate::web::validate::{validate_grid_count, validate_grid_size};
+use crate::{generate_enmty_canvas_grids_with_graph, ValidatedJson, MAX_CANVAS_SIDE, Color, ApiError, GridGraph, ApiResponse, AppState, GridFillOptions, GridLayout, GridShape, Point};
+
+// 新生成格子的初始颜色和标记颜色
+const UNMARKED_COLOR: Color = Color::from_rgb((0x90, 0x99, 0xA2));
+const MARKED_COLOR: Color = Color::from_rgb((0xFF, 0x00, 0x00));
 
 
 /// 将logo图片转换为canvas上马赛克的形状
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Validate)]
 #[serde(rename_all = "camelCase")]
+#[validate(schema(function = "validate_canvas_grids_req"))]
 pub struct GetCanvasGridsReq{
+    #[validate(range(min = 1, max = "MAX_CANVAS_SIDE"))]
     pub canvas_width: u32,
+    #[validate(range(min = 1, max = "MAX_CANVAS_SIDE"))]
     pub canvas_height: u32,
     pub grid_shape: GridShape,
+    #[validate(custom(function = "validate_grid_size"))]
     pub grid_size: (u32, u32),
+    // 画布留白及格子的对齐方式
+    #[serde(default)]
+    pub grid_layout: GridLayout,
+    // 是否返回格子邻接关系
+    #[serde(default)]
+    pub with_graph: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GetCanvasGridsReply{
 
     pub grids: Vec<MosaicGrid>,
+    // 格子序号 -> 相邻格子
+    pub graph: Option<BTreeMap<String, GridNeighbors>>,
+
+}
 
+/// 格子的相邻格子序号
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GridNeighbors {
+    // 共边的相邻格子
+    pub edge: Vec<String>,
+    // 仅共顶点的相邻格子
+    pub vertex: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -35,44 +63,60 @@ pub struct MosaicGrid {
     // 是否选中
     pub selected: bool,
     // 初始颜色
-    pub unmarked_color: String,
+    pub unmarked_color: Color,
     // 标记颜色
-    pub marked_color: String,
+    pub marked_color: Color,
+
+}
 
+
+impl GetCanvasGridsReq {
+    fn fill_options(&self) -> GridFillOptions {
+        let (w, h) = self.grid_size;
+        GridFillOptions::new(self.grid_shape, w, h).with_layout(self.grid_layout)
+    }
+}
+
+fn validate_canvas_grids_req(req: &GetCanvasGridsReq) -> Result<(), ValidationError> {
+    validate_grid_count(req.fill_options(), req.canvas_width, req.canvas_height)
 }
 
 
 pub async fn get_canvas_grids_handler(
-    Json(req): Json<GetCanvasGridsReq>,
+    ValidatedJson(req): ValidatedJson<GetCanvasGridsReq>,
 ) -> Result<ApiResponse<GetCanvasGridsReply>, ApiError> {
-    let (w, h) = req.grid_size;
-    let options = match req.grid_shape {
-        GridShape::Rectangle => {
-            GridFillOptions::Rectangle(w, h)
-        }
-        GridShape::Triangle => {
-            GridFillOptions::Triangle(w, h)
-        }
-    };
-    
-    let grids = generate_enmty_canvas_grids(
+    let options = req.fill_options();
+
+    let (grids, graph) = generate_enmty_canvas_grids_with_graph(
         req.canvas_width, req.canvas_height, options
-    ).map_err(|e| ApiError::BizError("GENERATE_CANVAS_GRIDS_ERROR".to_string(), e.to_string()))?;
+    ).map_err(|e| ApiError::from_process("GENERATE_CANVAS_GRIDS_ERROR", e))?;
+    let graph = req.with_graph.then(|| to_neighbors_map(&graph));
     let grids = grids.into_iter().map(|grid| MosaicGrid{
         seq: grid.seq,
         points: grid.points,
         shape: grid.shape,
         selected: false,
-        unmarked_color: "#9099A2ff".to_string(),
-        marked_color: "#ff0000ff".to_string(),
+        unmarked_color: UNMARKED_COLOR,
+        marked_color: MARKED_COLOR,
     }).collect();
     
     let reply = GetCanvasGridsReply{
-        grids
+        grids,
+        graph,
     };
     Ok(ApiResponse::ok(reply))
 }
 
+fn to_neighbors_map(graph: &GridGraph) -> BTreeMap<String, GridNeighbors> {
+    (0..graph.len()).map(|i| {
+        let neighbors = GridNeighbors {
+            edge: graph.edge_neighbors(i).iter().map(|&j| graph.seq(j).to_string()).collect(),
+            vertex: graph.vertex_neighbors(i).iter().map(|&j| graph.seq(j).to_string()).collect(),
+        };
+        (graph.seq(i).to_string(), neighbors)
+    }).collect()
+}
+
 
 pub fn canvas_routes() -> Router<Arc<AppState>> {
     Router::new()
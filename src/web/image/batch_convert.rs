@@ -0,0 +1,130 @@
+use std::{borrow::Cow, sync::Arc};
+
+use axum::extract::State;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+use validator::{Validate, ValidationError};
+
+use crate::web::validate::{field_validation_error, validate_grid_count, validate_grid_size};
+use crate::{
+    batch_summary_csv, encode_image, run_batch, run_blocking, ApiError, ApiResponse, AppState, BatchImage,
+    BatchOptions, BatchParamMatrix, BatchResultRow, GridPickCmd, ImageRepo, RenderFormat, ValidatedJson,
+};
+
+/// 单次批量转换最多的(logo, 参数组合)数
+const MAX_BATCH_CELLS: usize = 256;
+/// 缩略图的最大宽度
+const MAX_CELL_WIDTH: u32 = 480;
+
+
+/// 批量转换请求：多个logo × 参数矩阵
+#[derive(Debug, Clone, Serialize, Deserialize, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchConvertReq {
+    #[validate(length(min = 1))]
+    pub image_ids: Vec<String>,
+    #[validate(custom(function = "validate_matrix"))]
+    pub matrix: BatchParamMatrix,
+    // 联系表中缩略图的宽度
+    pub cell_width: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchConvertReply {
+    pub rows: Vec<BatchResultRow>,
+    // 与rows相同内容的csv文本
+    pub summary_csv: String,
+    // png联系表，每行一个logo，每列一组参数，data uri格式
+    pub contact_sheet: String,
+}
+
+
+/// 格子尺寸须在范围内，阈值须在选取策略的取值范围内
+fn validate_matrix(matrix: &BatchParamMatrix) -> Result<(), ValidationError> {
+    for size in &matrix.grid_sizes {
+        validate_grid_size(size).map_err(|mut e| {
+            e.add_param(Cow::Borrowed("field"), &"matrix.gridSizes");
+            e
+        })?;
+    }
+    let (lo, hi) = threshold_bounds(&matrix.pick_strategy);
+    if let Some(threshold) = matrix.thresholds.iter().find(|&&t| !(lo..=hi).contains(&t)) {
+        return Err(field_validation_error("matrix.thresholds", "range",
+            format!("threshold {} must be between {} and {}", threshold, lo, hi)));
+    }
+    Ok(())
+}
+
+/// 阈值的取值范围：平均色值比较策略为[0, 最大色差]，其余策略的占比和匹配度为[0, 1]
+fn threshold_bounds(cmd: &GridPickCmd) -> (f32, f32) {
+    match cmd {
+        GridPickCmd::AvgColorCompare(param) => (0.0, param.max_distance),
+        GridPickCmd::All(cmds) | GridPickCmd::Any(cmds) => cmds.first().map(threshold_bounds).unwrap_or((0.0, 1.0)),
+        GridPickCmd::Not(cmd) => threshold_bounds(cmd),
+        _ => (0.0, 1.0),
+    }
+}
+
+
+/// 对多个logo按参数矩阵批量转换，返回各组合的选中格子数及联系表
+pub async fn batch_convert_handler(
+    State(app_state): State<Arc<AppState>>,
+    ValidatedJson(req): ValidatedJson<BatchConvertReq>,
+) -> Result<ApiResponse<BatchConvertReply>, ApiError> {
+    info!("batch convert, req: {:?}", req);
+    run_blocking(move || batch_convert(&app_state, req)).await.map(ApiResponse::ok)
+}
+
+
+fn batch_convert(app_state: &AppState, req: BatchConvertReq) -> Result<BatchConvertReply, ApiError> {
+    let cells = req.image_ids.len() * req.matrix.combinations().len();
+    if cells == 0 {
+        return Err(ApiError::InvalidParameter("matrix".to_string(), "no image or parameter combination".to_string()));
+    }
+    if cells > MAX_BATCH_CELLS {
+        return Err(ApiError::InvalidParameter(
+            "matrix".to_string(), format!("{} combinations exceed the limit of {}", cells, MAX_BATCH_CELLS)));
+    }
+
+    let mut logos = Vec::with_capacity(req.image_ids.len());
+    for image_id in &req.image_ids {
+        let image_info = app_state.image_repo.get_image(image_id)
+            .ok_or_else(|| ApiError::BizError("IMAGE_NOT_FOUND".to_string(), format!("image {} not found", image_id)))?;
+        let logo = app_state.logo_cache.load(&image_info.id, &image_info.path)
+            .map_err(|e| ApiError::BizError("IMAGE_LOAD_FAILED".to_string(), e.to_string()))?;
+        logos.push((image_info.name, logo));
+    }
+    // 每种形状和尺寸都须能放进每个logo的画布
+    for (fill_options, _) in req.matrix.combinations() {
+        for (_, logo) in &logos {
+            validate_grid_count(fill_options, logo.image.width(), logo.image.height()).map_err(|mut e| {
+                let field = match e.params.get("field").and_then(|f| f.as_str()) {
+                    Some("gridShape") => "matrix.gridShapes",
+                    _ => "matrix.gridSizes",
+                };
+                e.add_param(Cow::Borrowed("field"), &field);
+                ApiError::from(e)
+            })?;
+        }
+    }
+    let images: Vec<BatchImage> = logos.iter()
+        .map(|(name, logo)| BatchImage { name: name.clone(), image: &logo.image })
+        .collect();
+
+    let mut options = BatchOptions::default();
+    if let Some(width) = req.cell_width {
+        options.cell_width = width.clamp(1, MAX_CELL_WIDTH);
+    }
+    let report = run_batch(&images, &req.matrix, &options)
+        .map_err(|e| ApiError::from_process("CONVERT_FAILED", e))?;
+    let png = encode_image(&report.contact_sheet, RenderFormat::Png)
+        .map_err(|e| ApiError::from_process("RENDER_FAILED", e))?;
+
+    Ok(BatchConvertReply {
+        summary_csv: batch_summary_csv(&report.rows),
+        rows: report.rows,
+        contact_sheet: format!("data:{};base64,{}", RenderFormat::Png.content_type(), STANDARD.encode(png)),
+    })
+}
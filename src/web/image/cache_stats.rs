@@ -0,0 +1,25 @@
+use std::sync::Arc;
+
+use axum::extract::State;
+use serde::{Deserialize, Serialize};
+
+use crate::{ApiError, ApiResponse, AppState, CacheStats};
+
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheStatsReply {
+    // 解码后的logo缓存
+    pub logo: CacheStats,
+    // 转换结果缓存
+    pub convert: CacheStats,
+}
+
+
+/// 查询logo缓存和转换结果缓存的命中统计
+pub async fn cache_stats_handler(State(app_state): State<Arc<AppState>>) -> Result<ApiResponse<CacheStatsReply>, ApiError> {
+    Ok(ApiResponse::ok(CacheStatsReply {
+        logo: app_state.logo_cache.stats(),
+        convert: app_state.convert_cache.stats(),
+    }))
+}
@@ -1,31 +1,98 @@
-use std::{str::FromStr, sync::Arc};
+use std::{borrow::Cow, str::FromStr, sync::Arc};
 
-use axum::{extract::State, Json};
+use axum::extract::State;
 use serde::{Deserialize, Serialize};
 use tracing::info;
+use validator::{Validate, ValidationError};
 
-use crate::{generate_canvas_grids_by_image_path, ApiError, ApiResponse, AppState, AvgColorCompareParam, Color, EliminateBgColorParam, GridFillOptions, GridPickCmd, GridPickStrategy, GridShape, ImageRepo, Point};
+use image::RgbaImage;
+use crate::web::validate::{validate_color, validate_colors, validate_grid_count, validate_grid_size, validation_error};
+use crate::{canonical_json, hash_bytes, ValidatedJson, MAX_GRID_COUNT, cleanup_selected_grids, generate_canvas_grids_with_progress, run_blocking, PickProgress, CachedLogo, PickStrategy, StrategyRegistry, Tiling, resolve_pick_palettes, GridCleanupOptions, GridPickScore, paint_grids, search_grids_for_target_count, Grid, TargetSearchMode, ApiError, ApiResponse, AppState, AvgColorCompareParam, Color, EdgeAwareParam, EliminateBgColorParam, GridColorMode, GridColorOptions, GridFillOptions, GridLayout, GridPickCmd, GridPickStrategy, GridShape, ImageRepo, PaletteClassifyParam, Point};
 
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Validate)]
 #[serde(rename_all = "camelCase")]
 pub struct MosaicGridsConvertReq{
+    #[validate(length(min = 1))]
     pub image_id: String,
     pub grid_shape: GridShape,
+    #[validate(length(equal = 2), custom(function = "validate_grid_size_vec"))]
     pub grid_size: Vec<u32>,
+    // 画布留白及格子的对齐方式
+    #[serde(default)]
+    pub grid_layout: GridLayout,
+    #[serde(default)]
     pub grid_pick_strategy: GridPickStrategy,
+    #[serde(default)]
+    #[validate(nested)]
     pub grid_pick_options: GridPickOptions,
+    // 完整的选取命令，支持All/Any/Not/Weighted组合；指定后忽略gridPickStrategy和gridPickOptions
+    pub grid_pick_cmd: Option<GridPickCmd>,
+    // 按名称使用注册的铺排方式，指定后忽略gridShape和gridSize
+    pub tiling: Option<StrategySpec>,
+    // 按名称使用注册的选取策略，指定后忽略其他选取参数
+    pub pick_strategy: Option<StrategySpec>,
+    #[validate(custom(function = "validate_color"))]
     pub grid_selected_color: String,
-
+    // 选中格子的着色方式
+    #[serde(default)]
+    pub grid_color_mode: GridColorMode,
+    // 调色板：真彩色模式下用于吸附颜色，调色板分类策略下用于格子分类
+    #[validate(length(min = 1, max = 64), custom(function = "validate_colors"))]
+    pub palette: Option<Vec<String>>,
+    // 目标选中格子数（如预计到场人数），指定后自动搜索格子尺寸和/或选取阈值
+    #[validate(range(min = 1, max = 100_000))]
+    pub target_grid_count: Option<usize>,
+    // 按目标格子数搜索时允许调整的参数
+    #[serde(default)]
+    pub target_search_mode: TargetSearchMode,
+    // 选取后的清理：移除孤立格子、填充空洞、膨胀/腐蚀
+    pub cleanup: Option<GridCleanupOptions>,
 }
 
+/// 注册表中的铺排方式或选取策略及其参数
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
+pub struct StrategySpec {
+    pub name: String,
+    #[serde(default)]
+    pub params: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Validate)]
+#[serde(rename_all = "camelCase")]
 pub struct GridPickOptions{
 
+    #[validate(custom(function = "validate_distance_range"))]
     pub color_distance_range:Option<(u8, u8)>,
+    #[validate(range(min = 0.0, max = 1.0))]
     pub remaining_ratio: Option<f32>,
+    #[validate(custom(function = "validate_color"))]
     pub target_color: Option<String>,
+    // 自动提取调色板时的颜色数量
+    #[validate(range(min = 1, max = 64))]
+    pub palette_size: Option<usize>,
+    // 边缘感知策略：细笔画的最小宽度(像素)
+    #[validate(range(min = 0.0, max = 1024.0))]
+    pub min_stroke_width: Option<f32>,
+    // 边缘感知策略：细笔画格子中边缘像素的最小占比
+    #[validate(range(min = 0.0, max = 1.0))]
+    pub min_edge_ratio: Option<f32>,
+}
+
+fn validate_grid_size_vec(grid_size: &[u32]) -> Result<(), ValidationError> {
+    match grid_size {
+        [w, h] => validate_grid_size(&(*w, *h)),
+        // 长度错误由length校验报告
+        _ => Ok(()),
+    }
+}
+
+fn validate_distance_range(&(min, max): &(u8, u8)) -> Result<(), ValidationError> {
+    if min > max {
+        return Err(validation_error("range", format!("min distance {} is greater than max distance {}", min, max)));
+    }
+    Ok(())
 }
 
 
@@ -39,7 +106,40 @@ pub struct MosaicGridsConvertReply {
     pub canvas_height: u32,
     // 网格信息
     pub grids: Vec<MosaicGrid>,
+    // 实际使用的调色板
+    pub palette: Option<Vec<String>>,
+    // 按目标格子数搜索得到的参数
+    pub target_search: Option<TargetSearchReport>,
+}
+
+/// 缓存的转换结果及生成它的规范请求(请求参数和logo文件哈希)
+#[derive(Debug)]
+pub struct CachedConvertReply {
+    pub request: String,
+    pub reply: MosaicGridsConvertReply,
+}
+
+impl CachedConvertReply {
+    /// 缓存权重：按请求和格子数据估算的字节数
+    pub fn weight(&self) -> usize {
+        let strings = |values: &Option<Vec<String>>| values.iter().flatten().map(String::len).sum::<usize>();
+        let grids: usize = self.reply.grids.iter().map(|g| {
+            size_of::<MosaicGrid>() + g.seq.len() + g.shape.len() + g.color.len()
+                + g.points.len() * size_of::<Point>()
+                + g.avg_color.as_ref().map_or(0, String::len)
+                + g.pick_scores.as_ref().map_or(0, |s| s.len() * size_of::<GridPickScore>())
+        }).sum();
+        size_of::<Self>() + self.request.len() + grids + strings(&self.reply.palette)
+    }
+}
 
+/// 按目标格子数搜索得到的参数
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TargetSearchReport {
+    pub grid_size: (u32, u32),
+    pub pick_threshold: f32,
+    pub selected_count: usize,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -57,65 +157,223 @@ pub struct MosaicGrid {
     pub avg_color: Option<String>,
     pub color_distance: Option<f32>,
     pub remaining_area_ratio: Option<f32>,
+    pub palette_index: Option<usize>,
+    pub edge_ratio: Option<f32>,
+    pub stroke_width: Option<f32>,
+    pub pick_scores: Option<Vec<GridPickScore>>,
 }
 
 
-/// 给定图片和参数，给出多边形马赛克填充的canvas数据
-pub async fn convert_to_mosaic_grids(
-    State(app_state): State<Arc<AppState>>,
-    Json(req): Json<MosaicGridsConvertReq>,
-) -> Result<ApiResponse<MosaicGridsConvertReply>, ApiError> {
-
-    info!("convert image into mosaic grids, req: {:?}", req);
-    let image_id = req.image_id;
-    let image_info;
-
-    match app_state.image_repo.get_image(image_id.as_str()) {
-        Some(image) => image_info = image,
-        None => return Err(ApiError::BizError("IMAGE_NOT_FOUND".to_string(), "image not found".to_string())),
-    }
-
-
-    let fill_options = match req.grid_shape {
-        GridShape::Triangle => {
-            let grid_size =  req.grid_size;
-            GridFillOptions::Triangle(grid_size[0], grid_size[1])
-
-        },
-        GridShape::Rectangle => {
-            let grid_size =  req.grid_size;
-            GridFillOptions::Rectangle(grid_size[0], grid_size[1])
-        },
+/// 根据请求参数，确定画布填充的图形形状及尺寸
+pub(super) fn build_fill_options(req: &MosaicGridsConvertReq) -> Result<GridFillOptions, ApiError> {
+    let (w, h) = match req.grid_size.as_slice() {
+        [w, h, ..] => (*w, *h),
+        _ => return Err(ApiError::InvalidParameter("gridSize".to_string(), "expected [width, height]".to_string())),
     };
-    
-    info!("fill_options: {:?}", fill_options);
+    Ok(GridFillOptions::new(req.grid_shape, w, h).with_layout(req.grid_layout))
+}
 
-    
-    let pick_strategy = match req.grid_pick_strategy {
+/// 根据请求参数，确定格子的选取策略；bg_color为logo的背景色
+pub(super) fn build_pick_strategy(req: &MosaicGridsConvertReq, bg_color: Color) -> Result<GridPickCmd, ApiError> {
+    if let Some(cmd) = &req.grid_pick_cmd {
+        return Ok(cmd.clone());
+    }
+    let cmd = match req.grid_pick_strategy {
         GridPickStrategy::AvgColorCompare => {
-            let color_str = req.grid_pick_options.target_color.as_ref().unwrap().as_str();
-            let range = req.grid_pick_options.color_distance_range.unwrap();
+            let color_str = req.grid_pick_options.target_color.as_deref()
+                .ok_or_else(|| ApiError::InvalidParameter("gridPickOptions.targetColor".to_string(), "required by AvgColorCompare".to_string()))?;
+            let range = req.grid_pick_options.color_distance_range
+                .ok_or_else(|| ApiError::InvalidParameter("gridPickOptions.colorDistanceRange".to_string(), "required by AvgColorCompare".to_string()))?;
             let min_distance = range.0  as f32;
             let max_distance = range.1  as f32;
             GridPickCmd::AvgColorCompare(AvgColorCompareParam{
-                color: Color::from_str(color_str).unwrap(),
+                color: parse_color("gridPickOptions.targetColor", color_str)?,
                 min_distance,
                 max_distance,
             })
         },
         GridPickStrategy::EliminateBgColor => {
             GridPickCmd::EliminateBgColor(EliminateBgColorParam{
-                color: "#ffffffff".into(),
+                color: bg_color,
+                min_remaining_ratio: req.grid_pick_options.remaining_ratio.unwrap_or(0.1),
+            })
+        },
+        GridPickStrategy::PaletteClassify => {
+            GridPickCmd::PaletteClassify(PaletteClassifyParam{
+                palette: parse_palette(req)?.unwrap_or_default(),
+                palette_size: req.grid_pick_options.palette_size.unwrap_or(4),
+                bg_color,
                 min_remaining_ratio: req.grid_pick_options.remaining_ratio.unwrap_or(0.1),
             })
+        },
+        GridPickStrategy::EdgeAware => {
+            GridPickCmd::EdgeAware(EdgeAwareParam{
+                bg_color,
+                min_remaining_ratio: req.grid_pick_options.remaining_ratio.unwrap_or(0.3),
+                min_stroke_width: req.grid_pick_options.min_stroke_width.unwrap_or(2.0),
+                min_edge_ratio: req.grid_pick_options.min_edge_ratio.unwrap_or(0.05),
+            })
+        },
+    };
+    Ok(cmd)
+}
+
+/// 调色板分类策略未指定调色板时，从logo中提取调色板并写回策略；返回本次转换实际使用的调色板
+pub(super) fn resolve_palette(logo: &RgbaImage, pick_strategy: &mut GridPickCmd, color_options: &mut GridColorOptions) -> Option<Vec<Color>> {
+    if let Some(palette) = resolve_pick_palettes(logo, pick_strategy) {
+        color_options.palette = Some(palette);
+    }
+    color_options.palette.clone()
+}
+
+/// 内置铺排方式的参数与gridSize使用同样的校验，错误指向tiling.params
+fn validate_tiling(tiling: &dyn Tiling, canvas_width: u32, canvas_height: u32) -> Result<(), ApiError> {
+    let Some(options) = tiling.fill_options() else {
+        return Ok(());
+    };
+    validate_grid_size(&options.grid_size())
+        .and_then(|_| validate_grid_count(options, canvas_width, canvas_height))
+        .map_err(|mut e| {
+            e.add_param(Cow::Borrowed("field"), &"tiling.params");
+            e.into()
+        })
+}
+
+/// 生成画布格子并按策略选取；指定了目标格子数时，搜索最接近目标的参数；最后按需清理选中的格子。
+/// 注册表中的铺排方式和选取策略不报告进度
+pub(super) fn generate_grids(
+    logo: &CachedLogo,
+    fill_options: GridFillOptions,
+    pick_strategy: GridPickCmd,
+    req: &MosaicGridsConvertReq,
+    registry: &StrategyRegistry,
+    progress: &PickProgress) -> Result<(Vec<Grid>, Option<TargetSearchReport>), ApiError> {
+    let (mut grids, mut report) = match req.target_grid_count {
+        _ if req.tiling.is_some() || req.pick_strategy.is_some() => {
+            if req.target_grid_count.is_some() {
+                return Err(ApiError::InvalidParameter(
+                    "targetGridCount".to_string(), "registered tilings and pick strategies do not support target grid count".to_string()));
+            }
+            let tiling: Box<dyn Tiling> = match &req.tiling {
+                Some(spec) => registry.create_tiling(&spec.name, &spec.params)
+                    .map_err(|e| ApiError::InvalidParameter("tiling".to_string(), e.to_string()))?,
+                None => Box::new(fill_options),
+            };
+            let picker: Box<dyn PickStrategy> = match &req.pick_strategy {
+                Some(spec) => registry.create_pick_strategy(&spec.name, &spec.params)
+                    .map_err(|e| ApiError::InvalidParameter("pickStrategy".to_string(), e.to_string()))?,
+                None => Box::new(pick_strategy),
+            };
+            let (canvas_width, canvas_height) = logo.image.dimensions();
+            validate_tiling(tiling.as_ref(), canvas_width, canvas_height)?;
+            let mut grids = tiling.generate_grids(canvas_width, canvas_height)
+                .map_err(|e| ApiError::from_process("CONVERT_FAILED", e))?;
+            // 自定义铺排方式无法预先估算格子数，生成后再检查
+            if grids.len() as u64 > MAX_GRID_COUNT {
+                return Err(ApiError::InvalidParameter(
+                    "tiling".to_string(), format!("{} grids exceed the limit of {}", grids.len(), MAX_GRID_COUNT)));
+            }
+            picker.pick_grids(&logo.image, &mut grids)
+                .map_err(|e| ApiError::from_process("CONVERT_FAILED", e))?;
+            (grids, None)
+        },
+        Some(target_count) => {
+            let result = search_grids_for_target_count(&logo.image, &logo.integrals, progress, fill_options, pick_strategy, target_count, MAX_GRID_COUNT, req.target_search_mode)
+                .map_err(|e| ApiError::from_process("CONVERT_FAILED", e))?;
+            info!("target count search, fill_options: {:?}, pick_strategy: {:?}, selected: {}",
+                result.fill_options, result.pick_strategy, result.selected_count);
+            let report = TargetSearchReport {
+                grid_size: result.fill_options.grid_size(),
+                pick_threshold: result.pick_strategy.pick_threshold(),
+                selected_count: result.selected_count,
+            };
+            (result.grids, Some(report))
+        },
+        None => {
+            let grids = generate_canvas_grids_with_progress(&logo.image, &logo.integrals, fill_options, pick_strategy, progress)
+                .map_err(|e| ApiError::from_process("CONVERT_FAILED", e))?;
+            (grids, None)
+        },
+    };
+
+    if let Some(cleanup) = &req.cleanup {
+        cleanup_selected_grids(&mut grids, cleanup);
+        if let Some(report) = report.as_mut() {
+            report.selected_count = grids.iter().filter(|g| g.ext.selected.unwrap_or(false)).count();
         }
+    }
+    Ok((grids, report))
+}
+
+fn parse_color(field: &str, value: &str) -> Result<Color, ApiError> {
+    Color::from_str(value).map_err(|e| ApiError::InvalidParameter(field.to_string(), e.to_string()))
+}
+
+fn parse_palette(req: &MosaicGridsConvertReq) -> Result<Option<Vec<Color>>, ApiError> {
+    req.palette.as_ref()
+        .map(|palette| palette.iter().map(|c| parse_color("palette", c)).collect())
+        .transpose()
+}
+
+
+/// 根据请求参数，确定选中格子的着色方式
+pub(super) fn build_color_options(req: &MosaicGridsConvertReq) -> Result<GridColorOptions, ApiError> {
+    let mut options = GridColorOptions::new(
+        req.grid_color_mode, parse_color("gridSelectedColor", req.grid_selected_color.as_str())?);
+    options.palette = parse_palette(req)?;
+    Ok(options)
+}
+
+
+/// 给定图片和参数，给出多边形马赛克填充的canvas数据
+pub async fn convert_to_mosaic_grids(
+    State(app_state): State<Arc<AppState>>,
+    ValidatedJson(req): ValidatedJson<MosaicGridsConvertReq>,
+) -> Result<ApiResponse<MosaicGridsConvertReply>, ApiError> {
+
+    info!("convert image into mosaic grids, req: {:?}", req);
+    run_blocking(move || convert(&app_state, &req, &PickProgress::new())).await.map(ApiResponse::ok)
+}
+
+
+/// 执行转换，通过progress报告选取进度；异步转换任务也使用该函数
+pub(crate) fn convert(app_state: &AppState, req: &MosaicGridsConvertReq, progress: &PickProgress) -> Result<MosaicGridsConvertReply, ApiError> {
+    let image_id = &req.image_id;
+    let image_info = match app_state.image_repo.get_image(image_id.as_str()) {
+        Some(image) => image,
+        None => return Err(ApiError::BizError("IMAGE_NOT_FOUND".to_string(), "image not found".to_string())),
     };
 
 
+    let fill_options = build_fill_options(req)?;
+    info!("fill_options: {:?}", fill_options);
+
+    let mut pick_strategy = build_pick_strategy(req, Color::from_rgb(image_info.bg_color))?;
     info!("pick_strategy: {:?}", pick_strategy);
 
-    let grids = generate_canvas_grids_by_image_path(image_info.path.as_str(), fill_options, pick_strategy)
+    let logo = app_state.logo_cache.load(&image_info.id, &image_info.path)
         .map_err(|e| ApiError::BizError("IMAGE_NOT_FOUND".to_string(), e.to_string()))?;
+    if req.tiling.is_none() {
+        validate_grid_count(fill_options, logo.image.width(), logo.image.height())?;
+    }
+
+    // 同一张logo(文件内容不变)、同样的参数，直接返回缓存的结果；命中时比较完整的请求，排除哈希碰撞
+    let canonical_request = canonical_json(&(req, logo.file_hash))
+        .map_err(|_| ApiError::InternalServerError)?;
+    let cache_key = hash_bytes(canonical_request.as_bytes());
+    if let Some(cached) = app_state.convert_cache.get(&cache_key).filter(|c| c.request == canonical_request) {
+        info!("convert result cache hit, image_id: {}", image_id);
+        return Ok(cached.reply.clone());
+    }
+
+    let mut color_options = build_color_options(req)?;
+    let palette = resolve_palette(&logo.image, &mut pick_strategy, &mut color_options);
+    let generated = generate_grids(&logo, fill_options, pick_strategy, req, &app_state.strategy_registry, progress);
+    // 选取时可能计算了新的积分图
+    app_state.logo_cache.update_weight(&image_info.id, &logo);
+    let (mut grids, target_search) = generated?;
+    paint_grids(&logo.image, &mut grids, &color_options)
+        .map_err(|e| ApiError::from_process("CONVERT_FAILED", e))?;
 
 
     let mut mosaic_grids = Vec::with_capacity(grids.len());
@@ -134,10 +392,16 @@ pub async fn convert_to_mosaic_grids(
             points: grid.points.clone(),
             shape: grid.shape.into(),
             selected,
-            color: req.grid_selected_color.clone(),
+            color: grid.ext.fill_color
+                .map(|c| c.to_rgba_string())
+                .unwrap_or_else(|| color_options.selected_color.to_rgba_string()),
             avg_color: avg_color.map(|c| c.to_rgba_string()),
             color_distance: grid.ext.color_distance,
             remaining_area_ratio: grid.ext.remaining_area_ratio,
+            palette_index: grid.ext.palette_index,
+            edge_ratio: grid.ext.edge_ratio,
+            stroke_width: grid.ext.stroke_width,
+            pick_scores: grid.ext.pick_scores.clone(),
         };
         mosaic_grids.push(mosaic_grid);
     } 
@@ -146,6 +410,11 @@ pub async fn convert_to_mosaic_grids(
         canvas_width: image_info.width,
         canvas_height: image_info.height,
         grids: mosaic_grids,
+        palette: palette.map(|p| p.iter().map(|c| c.to_rgba_string()).collect()),
+        target_search,
     };
-    Ok(ApiResponse::ok(reply))
+    let cached = CachedConvertReply { request: canonical_request, reply: reply.clone() };
+    let weight = cached.weight();
+    app_state.convert_cache.insert(cache_key, Arc::new(cached), weight);
+    Ok(reply)
 }
@@ -0,0 +1,137 @@
+use std::{str::FromStr, sync::Arc};
+
+use axum::{extract::State, http::header, response::{IntoResponse, Response}};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+use validator::{Validate, ValidationErrors};
+
+use crate::{
+    concat_side_by_side, encode_image,
+    overlay_image, paint_grids, render_canvas_with_grids, run_blocking, ApiError, ValidatedJson, PickProgress, AppState, Color, ImageRepo, RenderFormat,
+    RenderOptions,
+};
+use crate::web::validate::{validate_color, validate_grid_count, validation_error};
+use super::convert_mosaic::{build_color_options, build_fill_options, build_pick_strategy, generate_grids, resolve_palette, MosaicGridsConvertReq};
+
+
+/// 原始logo在预览图中的展示方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum OriginalImageMode {
+    // 不展示原图
+    #[default]
+    None,
+    // 原图在左，马赛克在右
+    SideBySide,
+    // 原图叠加在马赛克上
+    Overlay,
+}
+
+
+/// 马赛克转换预览请求，转换参数与MosaicGridsConvertReq一致
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MosaicGridsPreviewReq {
+    #[serde(flatten)]
+    pub convert: MosaicGridsConvertReq,
+    // 未选中格子的颜色
+    pub grid_unselected_color: Option<String>,
+    // 格子边框颜色
+    pub grid_border_color: Option<String>,
+    // 画布背景色
+    pub canvas_color: Option<String>,
+    // 原图展示方式
+    #[serde(default)]
+    pub original_mode: OriginalImageMode,
+    // 原图不透明度，范围[0, 1]
+    pub original_opacity: Option<f32>,
+}
+
+// 转换参数在json中与预览参数展开在同一层，错误也放在同一层
+impl Validate for MosaicGridsPreviewReq {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = self.convert.validate().err().unwrap_or_default();
+        let colors = [
+            ("grid_unselected_color", &self.grid_unselected_color),
+            ("grid_border_color", &self.grid_border_color),
+            ("canvas_color", &self.canvas_color),
+        ];
+        for (field, color) in colors {
+            if let Some(Err(e)) = color.as_deref().map(validate_color) {
+                errors.add(field, e);
+            }
+        }
+        if self.original_opacity.is_some_and(|v| !(0.0..=1.0).contains(&v)) {
+            errors.add("original_opacity", validation_error("range", "must be between 0 and 1"));
+        }
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+}
+
+
+/// 按转换参数生成马赛克预览图(png)
+pub async fn convert_preview_handler(
+    State(app_state): State<Arc<AppState>>,
+    ValidatedJson(req): ValidatedJson<MosaicGridsPreviewReq>,
+) -> Result<Response, ApiError> {
+    info!("preview mosaic grids, req: {:?}", req);
+    let bytes = run_blocking(move || render_preview(&app_state, &req)).await?;
+    Ok(([(header::CONTENT_TYPE, RenderFormat::Png.content_type())], bytes).into_response())
+}
+
+
+fn render_preview(app_state: &AppState, req: &MosaicGridsPreviewReq) -> Result<Vec<u8>, ApiError> {
+
+    let image_info = app_state.image_repo.get_image(req.convert.image_id.as_str())
+        .ok_or_else(|| ApiError::BizError("IMAGE_NOT_FOUND".to_string(), "image not found".to_string()))?;
+
+    let unselected_color = parse_color("gridUnselectedColor", req.grid_unselected_color.as_ref(), "#9099A2ff")?;
+    let border_color = parse_color("gridBorderColor", req.grid_border_color.as_ref(), "#ffffffff")?;
+    let canvas_color = parse_color("canvasColor", req.canvas_color.as_ref(), "#373737ff")?;
+
+    let fill_options = build_fill_options(&req.convert)?;
+    let mut pick_strategy = build_pick_strategy(&req.convert, Color::from_rgb(image_info.bg_color))?;
+
+    let cached_logo = app_state.logo_cache.load(&image_info.id, &image_info.path)
+        .map_err(|e| ApiError::BizError("IMAGE_LOAD_FAILED".to_string(), e.to_string()))?;
+    let logo = &cached_logo.image;
+    if req.convert.tiling.is_none() {
+        validate_grid_count(fill_options, logo.width(), logo.height())?;
+    }
+    let mut color_options = build_color_options(&req.convert)?;
+    resolve_palette(logo, &mut pick_strategy, &mut color_options);
+    let (mut grids, _) = generate_grids(&cached_logo, fill_options, pick_strategy, &req.convert, &app_state.strategy_registry, &PickProgress::new())?;
+
+    color_options.unselected_color = Some(unselected_color);
+    color_options.border_color = Some(border_color);
+    paint_grids(logo, &mut grids, &color_options)
+        .map_err(|e| ApiError::from_process("CONVERT_FAILED", e))?;
+
+    let options = RenderOptions::new(logo.width(), logo.height(), canvas_color);
+    let mosaic = render_canvas_with_grids(&grids, &options)
+        .map_err(|e| ApiError::from_process("RENDER_FAILED", e))?;
+
+    let opacity = req.original_opacity.unwrap_or(0.5);
+    let preview = match req.original_mode {
+        OriginalImageMode::None => mosaic,
+        OriginalImageMode::Overlay => {
+            let mut mosaic = mosaic;
+            overlay_image(&mut mosaic, logo, 0, 0, opacity);
+            mosaic
+        },
+        OriginalImageMode::SideBySide => {
+            let mut original = image::ImageBuffer::from_pixel(logo.width(), logo.height(), canvas_color.into());
+            overlay_image(&mut original, logo, 0, 0, opacity);
+            concat_side_by_side(&original, &mosaic, canvas_color)
+        },
+    };
+
+    encode_image(&preview, RenderFormat::Png)
+        .map_err(|e| ApiError::from_process("RENDER_FAILED", e))
+}
+
+
+fn parse_color(field: &str, value: Option<&String>, default: &str) -> Result<Color, ApiError> {
+    let value = value.map(|v| v.as_str()).unwrap_or(default);
+    Color::from_str(value).map_err(|e| ApiError::InvalidParameter(field.to_string(), e.to_string()))
+}
@@ -1,43 +1,26 @@
 mod list;
 mod convert_mosaic;
+mod convert_preview;
+mod recommend;
+mod cache_stats;
+mod batch_convert;
 
 use std::sync::Arc;
 use axum::{routing::{get, post}, Router};
-use serde::{Deserialize, Serialize};
-use self::{list::image_list_handler, convert_mosaic::convert_to_mosaic_grids};
+use self::{list::image_list_handler, convert_mosaic::convert_to_mosaic_grids, convert_preview::convert_preview_handler, recommend::recommend_grid_handler, cache_stats::cache_stats_handler, batch_convert::batch_convert_handler};
 use crate::AppState;
 
-
-/// 将logo图片转换为canvas上马赛克的形状
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-struct LogoImageConvertReq{
-    pub canvas_width: u32,
-    pub canvas_height: u32,
-    pub fill_shape_type: String,
-    pub fill_shape_size: Vec<u32>,
-    pub logo_image_id: String
-}
-
-
-
-
-
-
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct MosaicGridsConvertStrategy{
-
-    pub name: String,
-    pub options: Vec<f32>,
-
-}
-
+pub use convert_mosaic::{CachedConvertReply, MosaicGridsConvertReply};
+pub(crate) use convert_mosaic::{convert, MosaicGridsConvertReq};
 
 
 pub fn image_routes() -> Router<Arc<AppState>> {
     Router::new()
         .route("/list", get(image_list_handler))
         .route("/convert_to_mosaic_grids", post(convert_to_mosaic_grids))
+        .route("/convert_preview", post(convert_preview_handler))
+        .route("/recommend_grid", post(recommend_grid_handler))
+        .route("/cache_stats", get(cache_stats_handler))
+        .route("/batch_convert", post(batch_convert_handler))
 }
 
@@ -0,0 +1,104 @@
+use std::sync::Arc;
+
+use axum::{extract::State, Json};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::{
+    encode_image, recommend_grid_tilings, run_blocking, ApiError, ApiResponse, AppState, Color,
+    GridRecommendOptions, GridShape, ImageRepo, RenderFormat,
+};
+
+
+/// 格子铺法推荐请求
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GridRecommendReq {
+    pub image_id: String,
+    // 预计到场人数
+    pub target_grid_count: Option<usize>,
+    // 候选的格子形状，默认三角形和矩形
+    pub grid_shapes: Option<Vec<GridShape>>,
+    // 候选的格子宽度，默认根据目标格子数估算
+    pub grid_widths: Option<Vec<u32>>,
+    // 前景像素的最小占比
+    pub remaining_ratio: Option<f32>,
+    // 缩略图宽度
+    pub thumbnail_width: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GridRecommendReply {
+    pub recommendations: Vec<GridRecommendItem>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GridRecommendItem {
+    pub grid_shape: GridShape,
+    pub grid_size: (u32, u32),
+    pub remaining_ratio: f32,
+    pub selected_count: usize,
+    // 选中格子与logo前景的交并比
+    pub iou: f32,
+    // 格子宽高比（高/宽）
+    pub aspect_ratio: f32,
+    pub score: f32,
+    // png缩略图，data uri格式
+    pub thumbnail: String,
+}
+
+
+/// 评估多种格子形状和尺寸，返回按评分排序的推荐列表
+pub async fn recommend_grid_handler(
+    State(app_state): State<Arc<AppState>>,
+    Json(req): Json<GridRecommendReq>,
+) -> Result<ApiResponse<GridRecommendReply>, ApiError> {
+    info!("recommend grid tilings, req: {:?}", req);
+    run_blocking(move || recommend(&app_state, req)).await.map(ApiResponse::ok)
+}
+
+
+fn recommend(app_state: &AppState, req: GridRecommendReq) -> Result<GridRecommendReply, ApiError> {
+
+    let image_info = app_state.image_repo.get_image(req.image_id.as_str())
+        .ok_or_else(|| ApiError::BizError("IMAGE_NOT_FOUND".to_string(), "image not found".to_string()))?;
+    let logo = app_state.logo_cache.load(&image_info.id, &image_info.path)
+        .map_err(|e| ApiError::BizError("IMAGE_LOAD_FAILED".to_string(), e.to_string()))?;
+
+    let mut options = GridRecommendOptions::new(Color::from_rgb(image_info.bg_color));
+    options.target_count = req.target_grid_count;
+    if let Some(shapes) = req.grid_shapes {
+        options.shapes = shapes;
+    }
+    options.grid_widths = req.grid_widths.unwrap_or_default();
+    if let Some(ratio) = req.remaining_ratio {
+        options.min_remaining_ratio = ratio;
+    }
+    if let Some(width) = req.thumbnail_width {
+        options.thumbnail_width = width;
+    }
+
+    let recommendations = recommend_grid_tilings(&logo.image, &options)
+        .map_err(|e| ApiError::from_process("RECOMMEND_FAILED", e))?;
+
+    let mut items = Vec::with_capacity(recommendations.len());
+    for recommendation in recommendations {
+        let png = encode_image(&recommendation.thumbnail, RenderFormat::Png)
+            .map_err(|e| ApiError::from_process("RENDER_FAILED", e))?;
+        items.push(GridRecommendItem {
+            grid_shape: recommendation.fill_options.shape(),
+            grid_size: recommendation.fill_options.grid_size(),
+            remaining_ratio: recommendation.pick_strategy.pick_threshold(),
+            selected_count: recommendation.selected_count,
+            iou: recommendation.iou,
+            aspect_ratio: recommendation.aspect_ratio,
+            score: recommendation.score,
+            thumbnail: format!("data:{};base64,{}", RenderFormat::Png.content_type(), STANDARD.encode(png)),
+        });
+    }
+
+    Ok(GridRecommendReply { recommendations: items })
+}
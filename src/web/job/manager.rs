@@ -0,0 +1,263 @@
+use std::{
+    sync::{atomic::{AtomicUsize, Ordering}, Arc, Mutex, MutexGuard},
+    time::Instant,
+};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Semaphore;
+use tracing::error;
+use uuid::Uuid;
+use crate::{ApiError, PickProgress};
+use crate::web::image::MosaicGridsConvertReply;
+
+/// 默认同时执行的转换任务数
+pub const DEFAULT_JOB_WORKERS: usize = 2;
+/// 默认最多排队(含执行中)的转换任务数
+pub const DEFAULT_JOB_QUEUE_CAPACITY: usize = 16;
+/// 最多保留的已结束任务数，超出时清理最早结束的任务
+const MAX_FINISHED_JOBS: usize = 64;
+
+
+/// 转换任务状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+impl JobStatus {
+    fn is_finished(self) -> bool {
+        matches!(self, JobStatus::Completed | JobStatus::Failed | JobStatus::Cancelled)
+    }
+}
+
+
+struct JobState {
+    status: JobStatus,
+    result: Option<Arc<MosaicGridsConvertReply>>,
+    // 失败时的(错误码, 错误信息)
+    error: Option<(String, String)>,
+    finished_at: Option<Instant>,
+}
+
+/// 异步转换任务
+pub struct ConvertJob {
+    pub id: String,
+    pub progress: PickProgress,
+    state: Mutex<JobState>,
+}
+
+impl ConvertJob {
+    fn new() -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            progress: PickProgress::new(),
+            state: Mutex::new(JobState { status: JobStatus::Queued, result: None, error: None, finished_at: None }),
+        }
+    }
+
+    pub fn status(&self) -> JobStatus {
+        self.lock().status
+    }
+
+    pub fn result(&self) -> Option<Arc<MosaicGridsConvertReply>> {
+        self.lock().result.clone()
+    }
+
+    pub fn error(&self) -> Option<(String, String)> {
+        self.lock().error.clone()
+    }
+
+    /// 排队中的任务转为执行中；任务已在排队时被取消则返回false
+    fn start(&self) -> bool {
+        let mut state = self.lock();
+        if state.status != JobStatus::Queued {
+            return false;
+        }
+        state.status = JobStatus::Running;
+        true
+    }
+
+    /// 取消排队中的任务，立即结束；任务已开始执行或已结束则返回false
+    fn cancel_queued(&self) -> bool {
+        let mut state = self.lock();
+        if state.status != JobStatus::Queued {
+            return false;
+        }
+        *state = JobState { status: JobStatus::Cancelled, result: None, error: None, finished_at: Some(Instant::now()) };
+        true
+    }
+
+    fn finish(&self, status: JobStatus, result: Option<Arc<MosaicGridsConvertReply>>, error: Option<(String, String)>) {
+        let mut state = self.lock();
+        *state = JobState { status, result, error, finished_at: Some(Instant::now()) };
+    }
+
+    fn lock(&self) -> MutexGuard<'_, JobState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+
+/// 转换任务管理：限制同时执行的任务数和排队任务数，保存任务状态和结果
+pub struct JobManager {
+    jobs: DashMap<String, Arc<ConvertJob>>,
+    workers: Arc<Semaphore>,
+    queue_capacity: usize,
+    // 排队及执行中的任务数
+    pending: AtomicUsize,
+}
+
+impl Default for JobManager {
+    fn default() -> Self {
+        Self::new(DEFAULT_JOB_WORKERS, DEFAULT_JOB_QUEUE_CAPACITY)
+    }
+}
+
+impl JobManager {
+    pub fn new(workers: usize, queue_capacity: usize) -> Self {
+        Self {
+            jobs: DashMap::new(),
+            workers: Arc::new(Semaphore::new(workers.max(1))),
+            queue_capacity,
+            pending: AtomicUsize::new(0),
+        }
+    }
+
+    /// 创建排队中的任务；队列已满时返回错误
+    pub fn enqueue(&self) -> Result<Arc<ConvertJob>, ApiError> {
+        let reserved = self.pending.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| {
+            (n < self.queue_capacity).then_some(n + 1)
+        });
+        if reserved.is_err() {
+            return Err(ApiError::BizError("JOB_QUEUE_FULL".to_string(), "too many conversion jobs, please retry later".to_string()));
+        }
+        self.prune_finished();
+        let job = Arc::new(ConvertJob::new());
+        self.jobs.insert(job.id.clone(), job.clone());
+        Ok(job)
+    }
+
+    /// 等待空闲的执行槽位后，在阻塞线程池中执行任务；任务在排队时被取消则不再执行
+    pub async fn run<F>(&self, job: Arc<ConvertJob>, f: F)
+    where F: FnOnce(&PickProgress) -> Result<MosaicGridsConvertReply, ApiError> + Send + 'static {
+        let Ok(permit) = self.workers.clone().acquire_owned().await else {
+            if job.cancel_queued() {
+                self.pending.fetch_sub(1, Ordering::SeqCst);
+            }
+            return;
+        };
+        // 排队时被取消的任务已结束，并已释放排队名额
+        if !job.start() {
+            return;
+        }
+
+        let running = job.clone();
+        let outcome = tokio::task::spawn_blocking(move || f(&running.progress)).await;
+        match outcome {
+            _ if job.progress.is_cancelled() => job.finish(JobStatus::Cancelled, None, None),
+            Ok(Ok(reply)) => job.finish(JobStatus::Completed, Some(Arc::new(reply)), None),
+            Ok(Err(e)) => {
+                let (_, code, message, _) = e.into_parts();
+                job.finish(JobStatus::Failed, None, Some((code, message)));
+            },
+            Err(e) => {
+                error!("conversion job {} failed: {}", job.id, e);
+                job.finish(JobStatus::Failed, None, Some(("INTERNAL_SERVER_ERRROR".to_string(), "Internal Server Error".to_string())));
+            },
+        }
+        drop(permit);
+        self.pending.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    pub fn get(&self, job_id: &str) -> Option<Arc<ConvertJob>> {
+        self.jobs.get(job_id).map(|job| job.clone())
+    }
+
+    /// 取消任务：排队中的任务立即结束并释放排队名额，执行中的任务在处理完当前一批格子后停止
+    pub fn cancel(&self, job_id: &str) -> Option<Arc<ConvertJob>> {
+        let job = self.get(job_id)?;
+        if !job.status().is_finished() {
+            job.progress.cancel();
+            if job.cancel_queued() {
+                self.pending.fetch_sub(1, Ordering::SeqCst);
+            }
+        }
+        Some(job)
+    }
+
+    fn prune_finished(&self) {
+        let mut finished: Vec<(Instant, String)> = self.jobs.iter()
+            .filter_map(|job| job.lock().finished_at.map(|t| (t, job.id.clone())))
+            .collect();
+        if finished.len() < MAX_FINISHED_JOBS {
+            return;
+        }
+        finished.sort();
+        for (_, id) in finished.iter().take(finished.len() + 1 - MAX_FINISHED_JOBS) {
+            self.jobs.remove(id);
+        }
+    }
+}
+
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_job_queue_capacity_and_cancel() {
+    let manager = JobManager::new(1, 1);
+    let job = manager.enqueue().unwrap();
+    assert!(manager.enqueue().is_err());
+
+    // 排队中被取消的任务不再执行
+    manager.cancel(&job.id);
+    manager.run(job.clone(), |_| panic!("cancelled job should not run")).await;
+    assert_eq!(job.status(), JobStatus::Cancelled);
+
+    let job = manager.enqueue().unwrap();
+    manager.run(job.clone(), |_| Err(ApiError::BizError("CONVERT_FAILED".to_string(), "failed".to_string()))).await;
+    assert_eq!(job.status(), JobStatus::Failed);
+    assert_eq!(job.error().unwrap().0, "CONVERT_FAILED");
+}
+
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_cancel_queued_job_releases_queue() {
+    let manager = Arc::new(JobManager::new(1, 2));
+    let (release, wait) = std::sync::mpsc::channel::<()>();
+
+    // 占满唯一的执行槽位
+    let running = manager.enqueue().unwrap();
+    let running_task = tokio::spawn({
+        let (manager, job) = (manager.clone(), running.clone());
+        async move { manager.run(job, move |_| { wait.recv().ok(); Err(ApiError::InternalServerError) }).await }
+    });
+    while running.status() != JobStatus::Running {
+        tokio::task::yield_now().await;
+    }
+
+    let queued = manager.enqueue().unwrap();
+    let queued_task = tokio::spawn({
+        let (manager, job) = (manager.clone(), queued.clone());
+        async move { manager.run(job, |_| panic!("cancelled job should not run")).await }
+    });
+    assert!(manager.enqueue().is_err());
+
+    // 取消后立即结束，并释放排队名额
+    manager.cancel(&queued.id);
+    assert_eq!(queued.status(), JobStatus::Cancelled);
+    assert_eq!(manager.pending.load(Ordering::SeqCst), 1);
+    let next = manager.enqueue().unwrap();
+
+    release.send(()).unwrap();
+    running_task.await.unwrap();
+    queued_task.await.unwrap();
+    assert_eq!(running.status(), JobStatus::Failed);
+    assert_eq!(queued.status(), JobStatus::Cancelled);
+    assert_eq!(next.status(), JobStatus::Queued);
+    assert_eq!(manager.pending.load(Ordering::SeqCst), 1);
+}
@@ -0,0 +1,108 @@
+mod manager;
+
+use std::sync::Arc;
+use axum::{extract::{Path, State}, routing::{get, post}, Router};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+use crate::{ApiError, ApiResponse, AppState, ValidatedJson};
+use super::image::{convert, MosaicGridsConvertReply, MosaicGridsConvertReq};
+
+pub use manager::{ConvertJob, JobManager, JobStatus, DEFAULT_JOB_QUEUE_CAPACITY, DEFAULT_JOB_WORKERS};
+
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JobStatusReply {
+    pub job_id: String,
+    pub status: JobStatus,
+    // 当前一轮格子选取的完成百分比
+    pub percent: f32,
+    // 已完成的选取轮数，按目标格子数搜索时会多次选取
+    pub passes: usize,
+    pub err_code: Option<String>,
+    pub err_message: Option<String>,
+}
+
+impl From<&ConvertJob> for JobStatusReply {
+    fn from(job: &ConvertJob) -> Self {
+        let status = job.status();
+        let error = job.error();
+        Self {
+            job_id: job.id.clone(),
+            status,
+            percent: if status == JobStatus::Completed { 100.0 } else { job.progress.percent() },
+            passes: job.progress.passes(),
+            err_code: error.as_ref().map(|e| e.0.clone()),
+            err_message: error.map(|e| e.1),
+        }
+    }
+}
+
+
+pub fn job_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/convert", post(submit_convert_job_handler))
+        .route("/:id", get(job_status_handler))
+        .route("/:id/result", get(job_result_handler))
+        .route("/:id/cancel", post(cancel_job_handler))
+}
+
+
+/// 提交异步转换任务，立即返回任务id
+pub async fn submit_convert_job_handler(
+    State(app_state): State<Arc<AppState>>,
+    ValidatedJson(req): ValidatedJson<MosaicGridsConvertReq>,
+) -> Result<ApiResponse<JobStatusReply>, ApiError> {
+    let job = app_state.job_manager.enqueue()?;
+    info!("submit conversion job {}, req: {:?}", job.id, req);
+    let reply = JobStatusReply::from(job.as_ref());
+
+    let state = app_state.clone();
+    tokio::spawn(async move {
+        let worker_state = state.clone();
+        state.job_manager.run(job, move |progress| convert(&worker_state, &req, progress)).await;
+    });
+    Ok(ApiResponse::ok(reply))
+}
+
+
+/// 查询任务状态及进度
+pub async fn job_status_handler(
+    State(app_state): State<Arc<AppState>>,
+    Path(job_id): Path<String>,
+) -> Result<ApiResponse<JobStatusReply>, ApiError> {
+    let job = app_state.job_manager.get(&job_id).ok_or_else(job_not_found)?;
+    Ok(ApiResponse::ok(JobStatusReply::from(job.as_ref())))
+}
+
+
+/// 获取已完成任务的转换结果
+pub async fn job_result_handler(
+    State(app_state): State<Arc<AppState>>,
+    Path(job_id): Path<String>,
+) -> Result<ApiResponse<MosaicGridsConvertReply>, ApiError> {
+    let job = app_state.job_manager.get(&job_id).ok_or_else(job_not_found)?;
+    if let Some((code, message)) = job.error() {
+        return Err(ApiError::BizError(code, message));
+    }
+    match job.result() {
+        Some(reply) => Ok(ApiResponse::ok(reply.as_ref().clone())),
+        None => Err(ApiError::BizError("JOB_NOT_COMPLETED".to_string(), format!("job is {:?}", job.status()))),
+    }
+}
+
+
+/// 取消任务
+pub async fn cancel_job_handler(
+    State(app_state): State<Arc<AppState>>,
+    Path(job_id): Path<String>,
+) -> Result<ApiResponse<JobStatusReply>, ApiError> {
+    let job = app_state.job_manager.cancel(&job_id).ok_or_else(job_not_found)?;
+    info!("cancel conversion job {}", job_id);
+    Ok(ApiResponse::ok(JobStatusReply::from(job.as_ref())))
+}
+
+
+fn job_not_found() -> ApiError {
+    ApiError::BizError("JOB_NOT_FOUND".to_string(), "job not found".to_string())
+}
@@ -1,17 +1,26 @@
 mod image;
 mod activity;
 mod canvas;
+mod strategy;
+mod job;
+mod validate;
 
 
-use std::sync::Arc;
+use std::{path::PathBuf, sync::Arc};
 use axum::{http::StatusCode, response::{IntoResponse, Response}, Json, Router};
 use serde::Serialize;
 use thiserror::Error;
+use tracing::error;
 
-use image::image_routes;
+use image::{image_routes, CachedConvertReply};
 use activity::activity_routes;
 use canvas::canvas_routes;
-use crate::{repo::ActivityMemoryRepo, ImageMemoryRepo};
+use strategy::strategy_routes;
+use job::job_routes;
+
+pub use validate::{FieldError, ValidatedJson, MAX_CANVAS_SIDE};
+pub use job::{JobManager, JobStatus, DEFAULT_JOB_QUEUE_CAPACITY, DEFAULT_JOB_WORKERS};
+use crate::{repo::ActivityMemoryRepo, ImageMemoryRepo, ProcessError, LogoCache, StrategyRegistry, WeightedLruCache};
 
 #[derive(Error, Debug)]
 pub enum ApiError{
@@ -21,6 +30,19 @@ pub enum ApiError{
     BizError(String, String),
     #[error("InvalidParameter, message:{1}")]
     InvalidParameter(String, String),
+    #[error("Validation failed, fields:{0:?}")]
+    Validation(Vec<FieldError>),
+}
+
+impl ApiError {
+    /// 将处理过程的错误转换为接口错误：参数类错误对应到具体字段，其余使用给定的业务错误码
+    pub(crate) fn from_process(code: &str, e: ProcessError) -> ApiError {
+        match (&e, e.field()) {
+            (ProcessError::Cancelled, _) => ApiError::BizError("CANCELLED".to_string(), e.to_string()),
+            (_, Some(field)) => ApiError::InvalidParameter(field.to_string(), e.to_string()),
+            _ => ApiError::BizError(code.to_string(), e.to_string()),
+        }
+    }
 }
 
 
@@ -31,24 +53,43 @@ pub struct ApiResponse<T> {
     pub err_code: Option<String>,
     pub err_message: Option<String>,
     pub data: T,
+    // 参数校验失败时，各字段的错误
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub field_errors: Option<Vec<FieldError>>,
 }
 
-impl IntoResponse for ApiError {
-    fn into_response(self) -> Response {
-        let (status, code, message) = match self {
-            ApiError::InternalServerError => (StatusCode::INTERNAL_SERVER_ERROR, "INTERNAL_SERVER_ERRROR".into(), "Internal Server Error".into()),
+impl ApiError {
+    /// 拆分为HTTP状态码、错误码、错误信息及字段级的错误
+    pub(crate) fn into_parts(self) -> (StatusCode, String, String, Option<Vec<FieldError>>) {
+        match self {
+            ApiError::InternalServerError => (StatusCode::INTERNAL_SERVER_ERROR, "INTERNAL_SERVER_ERRROR".into(), "Internal Server Error".into(), None),
             // 为其他错误类型匹配相应的HTTP状态码和消息
-            ApiError::BizError(code, message) => (StatusCode::OK, code, message),
+            ApiError::BizError(code, message) => (StatusCode::OK, code, message, None),
             ApiError::InvalidParameter(field, message) => (
-                StatusCode::BAD_REQUEST, "INVALID_PARAMETER".into(), format!("参数[{}]无效：{}", field, message)
+                StatusCode::BAD_REQUEST, "INVALID_PARAMETER".into(), format!("参数[{}]无效：{}", field, message),
+                Some(vec![FieldError::new(field, "invalid", message)]),
             ),
-        };
+            ApiError::Validation(errors) => {
+                let message = errors.iter()
+                    .map(|e| format!("参数[{}]无效：{}", e.field, e.message))
+                    .collect::<Vec<_>>()
+                    .join("；");
+                (StatusCode::BAD_REQUEST, "INVALID_PARAMETER".into(), message, Some(errors))
+            },
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (status, code, message, field_errors) = self.into_parts();
 
         let api_response = ApiResponse {
             success: false,
             err_code: Some(code),
             err_message: Some(message),
             data: (),
+            field_errors,
         };
         let json_response = Json(api_response);
         (status, json_response.into_response()).into_response()
@@ -63,6 +104,7 @@ where T: Serialize{
             err_code: None,
             err_message: None,
             data,
+            field_errors: None,
         }
     }
 }
@@ -82,14 +124,35 @@ where
 
 pub struct AppState {
     /// logo图片存储路径
-    pub logo_image_dir_path: &'static str,
+    pub logo_image_dir_path: PathBuf,
     /// 前端静态资源路径
-    pub static_path: &'static str,
+    pub static_path: PathBuf,
     /// logo图片集
     pub image_repo: ImageMemoryRepo,
     /// 活动repo
     pub activity_repo: ActivityMemoryRepo,
-    
+    /// 铺排方式及选取策略注册表
+    pub strategy_registry: StrategyRegistry,
+    /// 解码后的logo缓存
+    pub logo_cache: LogoCache,
+    /// 转换结果缓存，按请求参数和logo文件哈希的规范文本索引
+    pub convert_cache: WeightedLruCache<u64, Arc<CachedConvertReply>>,
+    /// 异步转换任务
+    pub job_manager: JobManager,
+
+}
+
+
+/// 在阻塞线程池中执行耗时的图片处理，避免占用tokio的工作线程
+pub(crate) async fn run_blocking<T, F>(f: F) -> Result<T, ApiError>
+where
+    F: FnOnce() -> Result<T, ApiError> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f).await.map_err(|e| {
+        error!("blocking task failed: {}", e);
+        ApiError::InternalServerError
+    })?
 }
 
 
@@ -98,4 +161,6 @@ pub fn api_routes() -> Router<Arc<AppState>> {
         .nest("/canvas", canvas_routes())
         .nest("/image", image_routes())
         .nest("/activity", activity_routes())
+        .nest("/strategies", strategy_routes())
+        .nest("/jobs", job_routes())
 }
\ No newline at end of file
@@ -0,0 +1,32 @@
+use std::sync::Arc;
+use axum::{extract::State, routing::get, Router};
+use serde::{Deserialize, Serialize};
+use crate::{ApiError, ApiResponse, AppState, StrategyInfo};
+
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StrategyListReply {
+    // 已注册的铺排方式
+    pub tilings: Vec<StrategyInfo>,
+    // 已注册的格子选取策略
+    pub pick_strategies: Vec<StrategyInfo>,
+}
+
+
+pub fn strategy_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(strategy_list_handler))
+}
+
+
+/// 列出已注册的铺排方式和选取策略
+pub async fn strategy_list_handler(
+    State(app_state): State<Arc<AppState>>,
+) -> Result<ApiResponse<StrategyListReply>, ApiError> {
+    let registry = &app_state.strategy_registry;
+    Ok(ApiResponse::ok(StrategyListReply {
+        tilings: registry.tilings(),
+        pick_strategies: registry.pick_strategies(),
+    }))
+}
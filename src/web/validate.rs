@@ -0,0 +1,208 @@
+use std::{borrow::Cow, str::FromStr};
+
+use axum::{async_trait, extract::{rejection::JsonRejection, FromRequest, Request}, Json};
+use serde::{de::DeserializeOwned, Serialize};
+use validator::{Validate, ValidationError, ValidationErrors, ValidationErrorsKind};
+
+use crate::{check_canvas_grids, ApiError, Color, GridFillOptions, GridShape, Point, ProcessError, MAX_GRID_SIDE};
+
+/// 画布宽高上限(像素)
+pub const MAX_CANVAS_SIDE: u32 = 8192;
+
+
+/// 字段级的校验错误，field为请求中的字段路径，如grids[2].points
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FieldError {
+    pub field: String,
+    pub code: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: impl Into<String>, code: impl Into<String>, message: impl Into<String>) -> Self {
+        Self { field: field.into(), code: code.into(), message: message.into() }
+    }
+}
+
+
+/// 反序列化json请求体后执行校验，失败时返回字段级的错误
+pub struct ValidatedJson<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for ValidatedJson<T>
+where
+    T: DeserializeOwned + Validate,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(value) = Json::<T>::from_request(req, state).await.map_err(json_rejection_error)?;
+        value.validate()?;
+        Ok(ValidatedJson(value))
+    }
+}
+
+/// 字段类型或取值不合法(如颜色无法解析)时，错误对应到json中的字段路径
+fn json_rejection_error(rejection: JsonRejection) -> ApiError {
+    if let JsonRejection::JsonDataError(e) = &rejection {
+        let path_error = std::iter::successors(std::error::Error::source(e), |e| e.source())
+            .find_map(|e| e.downcast_ref::<serde_path_to_error::Error<serde_json::Error>>());
+        if let Some(e) = path_error.filter(|e| e.path().to_string() != ".") {
+            return ApiError::Validation(vec![FieldError::new(e.path().to_string(), "invalid", e.inner().to_string())]);
+        }
+    }
+    ApiError::InvalidParameter("body".to_string(), rejection.body_text())
+}
+
+impl From<ValidationErrors> for ApiError {
+    fn from(errors: ValidationErrors) -> Self {
+        let mut field_errors = Vec::new();
+        flatten_errors("", &errors, &mut field_errors);
+        field_errors.sort_by(|a, b| a.field.cmp(&b.field));
+        ApiError::Validation(field_errors)
+    }
+}
+
+impl From<ValidationError> for ApiError {
+    fn from(error: ValidationError) -> Self {
+        let mut errors = ValidationErrors::new();
+        errors.add("__all__", error);
+        errors.into()
+    }
+}
+
+/// 将嵌套的校验错误展开为字段路径；结构体级别的错误通过field参数指明字段
+fn flatten_errors(prefix: &str, errors: &ValidationErrors, out: &mut Vec<FieldError>) {
+    for (field, kind) in errors.errors() {
+        let path = join_path(prefix, &to_camel_case(field));
+        match kind {
+            ValidationErrorsKind::Field(errors) => out.extend(errors.iter().map(|e| {
+                let path = match e.params.get("field").and_then(|f| f.as_str()) {
+                    Some(field) => join_path(prefix, field),
+                    None => path.clone(),
+                };
+                let message = e.message.as_deref().map(str::to_string)
+                    .unwrap_or_else(|| default_message(e));
+                FieldError::new(path, e.code.as_ref(), message)
+            })),
+            ValidationErrorsKind::Struct(errors) => flatten_errors(&path, errors, out),
+            ValidationErrorsKind::List(items) => {
+                for (i, errors) in items {
+                    flatten_errors(&format!("{}[{}]", path, i), errors, out);
+                }
+            },
+        }
+    }
+}
+
+fn join_path(prefix: &str, field: &str) -> String {
+    match (prefix.is_empty(), field) {
+        (true, _) => field.to_string(),
+        (false, "__all__") => prefix.to_string(),
+        (false, _) => format!("{}.{}", prefix, field),
+    }
+}
+
+fn to_camel_case(field: &str) -> String {
+    let mut parts = field.split('_');
+    let mut name = parts.next().unwrap_or_default().to_string();
+    for part in parts {
+        let mut chars = part.chars();
+        if let Some(c) = chars.next() {
+            name.push(c.to_ascii_uppercase());
+            name.extend(chars);
+        }
+    }
+    name
+}
+
+fn default_message(e: &ValidationError) -> String {
+    let param = |name: &str| e.params.get(name).map(|v| v.to_string());
+    match (e.code.as_ref(), param("min"), param("max"), param("equal")) {
+        (_, _, _, Some(equal)) => format!("must have length {}", equal),
+        ("range", Some(min), Some(max), _) => format!("must be between {} and {}", min, max),
+        ("length", Some(min), Some(max), _) => format!("length must be between {} and {}", min, max),
+        ("range", Some(min), None, _) => format!("must be at least {}", min),
+        ("length", Some(min), None, _) => format!("length must be at least {}", min),
+        ("range", None, Some(max), _) => format!("must be at most {}", max),
+        ("length", None, Some(max), _) => format!("length must be at most {}", max),
+        (code, ..) => format!("failed check: {}", code),
+    }
+}
+
+
+/// 构造带消息的校验错误
+pub(crate) fn validation_error(code: &'static str, message: impl Into<String>) -> ValidationError {
+    ValidationError::new(code).with_message(Cow::Owned(message.into()))
+}
+
+/// 构造结构体级别的校验错误，并指明出错的字段
+pub(crate) fn field_validation_error(field: impl Into<String>, code: &'static str, message: impl Into<String>) -> ValidationError {
+    let mut error = validation_error(code, message);
+    error.add_param(Cow::Borrowed("field"), &field.into());
+    error
+}
+
+/// 颜色须能被Color解析
+pub(crate) fn validate_color(value: &str) -> Result<(), ValidationError> {
+    Color::from_str(value).map(|_| ()).map_err(|e| validation_error("color", e.to_string()))
+}
+
+pub(crate) fn validate_colors(values: &[String]) -> Result<(), ValidationError> {
+    values.iter().try_for_each(|v| validate_color(v))
+}
+
+/// 格子宽高须在[1, MAX_GRID_SIDE]内
+pub(crate) fn validate_grid_size(&(width, height): &(u32, u32)) -> Result<(), ValidationError> {
+    if (1..=MAX_GRID_SIDE).contains(&width) && (1..=MAX_GRID_SIDE).contains(&height) {
+        return Ok(());
+    }
+    Err(validation_error("range", format!("grid size {}x{} must be between 1 and {}", width, height, MAX_GRID_SIDE)))
+}
+
+/// 格子须能放进画布，且格子总数不超过上限；与命令行共用check_canvas_grids
+pub(crate) fn validate_grid_count(fill_options: GridFillOptions, canvas_width: u32, canvas_height: u32) -> Result<(), ValidationError> {
+    check_canvas_grids(canvas_width, canvas_height, &fill_options).map_err(|e| match e {
+        ProcessError::InvalidParameter { field, code, message } => field_validation_error(field, code, message),
+        e => validation_error("invalid", e.to_string()),
+    })
+}
+
+/// 格子顶点数须与形状一致
+pub(crate) fn validate_grid_points(shape: GridShape, points: &[Point]) -> Result<(), ValidationError> {
+    match shape.vertex_count() {
+        Some(count) if points.len() != count => Err(field_validation_error("points", "points_count",
+            format!("{:?} grid must have {} points, got {}", shape, count, points.len()))),
+        None if points.len() < 3 => Err(field_validation_error("points", "points_count",
+            format!("polygon grid must have at least 3 points, got {}", points.len()))),
+        _ => Ok(()),
+    }
+}
+
+/// 格子顶点须在画布内
+pub(crate) fn validate_points_in_canvas(points: &[Point], canvas_width: u32, canvas_height: u32) -> Result<(), ValidationError> {
+    let (width, height) = (canvas_width as f32, canvas_height as f32);
+    match points.iter().find(|p| !(p.x >= 0.0 && p.x <= width && p.y >= 0.0 && p.y <= height)) {
+        Some(p) => Err(validation_error("points_out_of_canvas",
+            format!("point ({}, {}) is outside canvas {}x{}", p.x, p.y, canvas_width, canvas_height))),
+        None => Ok(()),
+    }
+}
+
+
+#[cfg(test)]
+#[test]
+fn test_flatten_nested_validation_errors() {
+    let mut grid_errors = ValidationErrors::new();
+    grid_errors.add("marked_color", validation_error("color", "bad color"));
+    let mut errors = ValidationErrors::new();
+    errors.add("canvas_width", ValidationError::new("range"));
+    errors.add("__all__", field_validation_error("gridSize", "too_many_grids", "too many"));
+    errors.errors_mut().insert("grids", ValidationErrorsKind::List([(2, Box::new(grid_errors))].into()));
+
+    let ApiError::Validation(fields) = ApiError::from(errors) else { panic!("expected validation error") };
+    let fields: Vec<_> = fields.iter().map(|f| (f.field.as_str(), f.code.as_str())).collect();
+    assert_eq!(fields, vec![("canvasWidth", "range"), ("gridSize", "too_many_grids"), ("grids[2].markedColor", "color")]);
+}